@@ -2,22 +2,40 @@
 #![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
 
 use {
+	clap::Parser,
 	core::mem::size_of,
-	d2sw_tiled_project::{ds1, stdoutRaw, toml_toStringPretty, ReadExt},
+	d2sw_tiled_project::{ds1, stdoutRaw, toml_toStringPretty, Error, ReadExt},
 	std::io::{self, BufRead, Read, Write},
 };
 
-fn main() -> Result<(), ds1::VersionMismatchError> {
-	let cursor = &mut io::Cursor::new(readToVec(io::stdin()).unwrap());
+#[derive(Parser, Clone)]
+pub struct Args {
+	/// Write `layers` as a table of named grids (`wall1`, `orientation1`, ..., `floor1`, ..., `shadow`, `tag`)
+	///   of `main:sub:prop` cells instead of packed integers.
+	#[clap(long)]
+	namedLayers: bool,
+}
+
+fn main() -> Result<(), Error> {
+	run(Args::parse(), io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(Args { namedLayers }: Args, stdin: impl Read, mut stdout: impl Write) -> Result<(), Error> {
+	let cursor = &mut io::Cursor::new(readToVec(stdin).unwrap());
 	let ds1RootStruct = &ds1::RootStruct::new(cursor)?;
 	let remaining = cursor.remaining();
 	eprintln!("v{} {}", ds1RootStruct.version, remaining);
-	(if remaining == size_of::<i32>() { ReadExt::consumeZeros } else { io::Cursor::consume })(
-		cursor, remaining,
-	);
-	stdoutRaw()
-		.write_all(&toml_toStringPretty(ds1RootStruct).unwrap_or_else(|err| panic!("{err}")).into_bytes())
-		.unwrap();
+	if remaining == size_of::<i32>() {
+		cursor.consumeZeros(remaining)?;
+	} else {
+		cursor.consume(remaining);
+	}
+	let toml = if namedLayers {
+		toml_toStringPretty(&ds1RootStruct.withNamedLayers())
+	} else {
+		toml_toStringPretty(ds1RootStruct)
+	};
+	stdout.write_all(&toml.unwrap_or_else(|err| panic!("{err}")).into_bytes()).unwrap();
 
 	fn readToVec(mut reader: impl Read) -> io::Result<Vec<u8>> {
 		let mut vec = Vec::new();
@@ -2,16 +2,35 @@
 #![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
 
 use {
-	d2sw_tiled_project::{stdoutRaw, PAL_LEN},
-	std::io::{self, Read, Write},
+	clap::Parser,
+	d2sw_tiled_project::{
+		palette::{Format, Palette},
+		stdoutRaw,
+	},
+	std::io::{self, BufWriter, Read, Write},
 };
 
+#[derive(Parser)]
+pub struct Args {
+	/// What the input is: `pal-dat` (Diablo II's, BGR), `png-pal` (RGB, what every `--png-pal` takes),
+	///   `png` (an indexed PNG's PLTE), `jasc` (.pal), `gimp` (.gpl), `act` (Photoshop) or `hex`.
+	#[clap(long, default_value_t = Format::PalDat)]
+	from: Format,
+
+	/// What to write it as, one of the same.
+	#[clap(long, default_value_t = Format::PngPAL)]
+	to: Format,
+}
+
 fn main() {
-	let pal = &mut Vec::<u8>::with_capacity(PAL_LEN);
-	io::stdin().read_to_end(pal).unwrap();
-	assert_eq!(pal.len(), PAL_LEN);
-	for i in (0..pal.len()).step_by(3) {
-		pal.swap(i + 0, i + 2);
-	}
-	stdoutRaw().write_all(pal).unwrap();
+	run(Args::parse(), io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(Args { from, to }: Args, mut stdin: impl Read, stdout: impl Write) {
+	let bytes = &mut Vec::new();
+	stdin.read_to_end(bytes).unwrap();
+	let palette = Palette::read(from, bytes).unwrap_or_else(|err| panic!("{from}: {err}"));
+	let stdout = &mut BufWriter::new(stdout);
+	palette.write(to, stdout).unwrap();
+	stdout.flush().unwrap();
 }
@@ -2,12 +2,16 @@
 #![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
 
 use {
-	d2sw_tiled_project::{ds1, io_readToString, stdoutRaw},
-	std::io::{self, BufWriter},
+	d2sw_tiled_project::{ds1, io_readToString, stdoutRaw, Error},
+	std::io::{self, BufWriter, Read, Write},
 };
 
-fn main() {
-	toml::from_str::<ds1::RootStruct>(&io_readToString(io::stdin()).unwrap())
+fn main() -> Result<(), Error> {
+	run(io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(stdin: impl Read, stdout: impl Write) -> Result<(), Error> {
+	toml::from_str::<ds1::RootStruct>(&io_readToString(stdin).unwrap())
 		.unwrap()
-		.writeTo(&mut BufWriter::new(stdoutRaw()));
+		.writeTo(&mut BufWriter::new(stdout))
 }
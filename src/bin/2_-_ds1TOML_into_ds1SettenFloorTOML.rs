@@ -4,47 +4,37 @@
 use {
 	clap::{value_parser, Parser},
 	d2sw_tiled_project::{
-		ds1::{
-			self, existsTagLayer, LAYER_DRAWING_PRIORITY_MASK, MAIN_INDEX_MASK, MAIN_INDEX_MAX,
-			MAIN_INDEX_OFFSET, ONE_SHADOW_LAYER, SUB_INDEX_MASK, SUB_INDEX_MAX, SUB_INDEX_OFFSET,
-		},
+		ds1::{self, MAIN_INDEX_MAX, SUB_INDEX_MAX},
 		io_readToString, stdoutRaw, toml_toStringPretty,
 	},
-	std::io::{self, Write},
+	std::io::{self, Read, Write},
 };
 
+#[derive(Parser)]
+pub struct Args {
+	#[clap(value_parser = value_parser!(u32).range(0..=(MAIN_INDEX_MAX as _)))]
+	mainIndex: u32,
+
+	#[clap(value_parser = value_parser!(u32).range(0..=(SUB_INDEX_MAX as _)))]
+	subIndex: u32,
+}
+
 fn main() {
-	let (settenID, ds1RootStruct) = (
-		{
-			#[derive(Parser)]
-			struct Args {
-				#[clap(value_parser = value_parser!(u32).range(0..=(MAIN_INDEX_MAX as _)))]
-				mainIndex: u32,
+	run(Args::parse(), io::stdin().lock(), stdoutRaw())
+}
 
-				#[clap(value_parser = value_parser!(u32).range(0..=(SUB_INDEX_MAX as _)))]
-				subIndex: u32,
+pub fn run(Args { mainIndex, subIndex }: Args, stdin: impl Read, mut stdout: impl Write) {
+	let ds1RootStruct = &mut toml::from_str::<ds1::RootStruct>(&io_readToString(stdin).unwrap()).unwrap();
+	for i in 0..ds1RootStruct.numFloors as usize {
+		for cell in ds1RootStruct.floorLayerMut(i).cellsMut() {
+			if cell.isEmpty() {
+				continue;
 			}
-			let Args { mainIndex, subIndex } = Args::parse();
-			mainIndex << MAIN_INDEX_OFFSET | subIndex << SUB_INDEX_OFFSET
-		},
-		&mut toml::from_str(&io_readToString(io::stdin()).unwrap()).unwrap(),
-	);
-	let &mut ds1::RootStruct { tagType, numWallLayers, ref mut layers, .. } = ds1RootStruct;
-	for floorLayer in {
-		let len = layers.len();
-		&mut layers[((numWallLayers * 2) as _)..(len - ONE_SHADOW_LAYER - existsTagLayer(tagType) as usize)]
-	} {
-		for cell in floorLayer.iter_mut() {
-			*cell = {
-				let &mut cell = cell;
-				if cell & LAYER_DRAWING_PRIORITY_MASK == 0 {
-					continue;
-				}
-				cell & !(MAIN_INDEX_MASK | SUB_INDEX_MASK) | settenID
-			};
+			cell.setMainIndex(mainIndex);
+			cell.setSubIndex(subIndex);
 		}
 	}
-	stdoutRaw()
+	stdout
 		.write_all(&toml_toStringPretty(ds1RootStruct).unwrap_or_else(|err| panic!("{err}")).into_bytes())
 		.unwrap();
 }
@@ -4,31 +4,33 @@
 use {
 	core::cmp::min,
 	d2sw_tiled_project::{
-		ds1::{
-			self, existsTagLayer, LAYER_DRAWING_PRIORITY_MASK, MAIN_INDEX_MAX, MAIN_INDEX_OFFSET,
-			ONE_SHADOW_LAYER, ORIENTATION_MASK, SUB_INDEX_MAX, SUB_INDEX_OFFSET,
-		},
+		ds1::{self, Cell, Grid},
 		dt1::FLOOR_ORIENTATION,
-		io_readToString,
+		io_readToString, stdoutRaw,
 	},
-	std::io,
+	std::io::{self, BufWriter, Read, Write},
 };
 
 fn main() {
-	let ds1::RootStruct { xMax, tagType, numWallLayers, layers, .. } =
-		toml::from_str(&io_readToString(io::stdin()).unwrap()).unwrap();
-	for i in 0..(layers.len() - ONE_SHADOW_LAYER - existsTagLayer(tagType) as usize) {
-		let isWallLayer = i < numWallLayers as usize * 2;
-		if isWallLayer && i % 2 == 1 {
-			continue;
-		}
-		let (mut j, layer) = (0, &layers[i]);
-		while j < layer.len() {
-			for _ in 0..=xMax {
-				let cell = layer[j];
-				print!(
+	run(io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(stdin: impl Read, stdout: impl Write) {
+	let stdout = &mut BufWriter::new(stdout);
+	let ds1RootStruct = &toml::from_str::<ds1::RootStruct>(&io_readToString(stdin).unwrap()).unwrap();
+	let layers = Vec::from_iter(
+		(0..ds1RootStruct.numWallLayers as usize)
+			.map(|i| (ds1RootStruct.wallLayer(i), Some(ds1RootStruct.orientationLayer(i))))
+			.chain((0..ds1RootStruct.numFloors as usize).map(|i| (ds1RootStruct.floorLayer(i), None))),
+	);
+	for (layer, orientationLayer) in layers {
+		for y in 0..layer.height() {
+			for x in 0..layer.width() {
+				let cell = layer[(x, y)];
+				write!(
+					stdout,
 					"{}",
-					if cell & LAYER_DRAWING_PRIORITY_MASK == 0 {
+					if cell.isEmpty() {
 						'ㅤ'
 					} else {
 						const HANGUL_INITIAL_MULTIPLIER: u32 = (HANGUL_MEDIAL_MAX + 1) * HANGUL_MEDIAL_MULTIPLIER;
@@ -37,20 +39,22 @@ fn main() {
 						const HANGUL_FINAL_MULTIPLIER: u32 = 1;
 						const HANGUL_UNICODE_BLOCK_START: u32 = '가' as _;
 						char::from_u32(
-							(if isWallLayer { layers[i + 1][j] & ORIENTATION_MASK } else { FLOOR_ORIENTATION as _ })
-								* HANGUL_INITIAL_MULTIPLIER
-								+ min(cell >> MAIN_INDEX_OFFSET & MAIN_INDEX_MAX, HANGUL_MEDIAL_MAX)
-									* HANGUL_MEDIAL_MULTIPLIER
-								+ (cell >> SUB_INDEX_OFFSET & SUB_INDEX_MAX) * HANGUL_FINAL_MULTIPLIER
+							orientation(orientationLayer.as_ref(), (x, y)) * HANGUL_INITIAL_MULTIPLIER
+								+ min(cell.mainIndex(), HANGUL_MEDIAL_MAX) * HANGUL_MEDIAL_MULTIPLIER
+								+ cell.subIndex() * HANGUL_FINAL_MULTIPLIER
 								+ HANGUL_UNICODE_BLOCK_START,
 						)
 						.unwrap()
 					}
-				);
-				j += 1;
+				)
+				.unwrap();
 			}
-			println!(",");
+			writeln!(stdout, ",").unwrap();
 		}
-		println!();
+		writeln!(stdout).unwrap();
+	}
+
+	fn orientation(orientationLayer: Option<&Grid<&[Cell]>>, at: (usize, usize)) -> u32 {
+		orientationLayer.map_or(FLOOR_ORIENTATION, |orientationLayer| orientationLayer[at].orientation()) as _
 	}
 }
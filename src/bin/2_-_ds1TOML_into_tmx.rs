@@ -0,0 +1,17 @@
+#![warn(clippy::pedantic, elided_lifetimes_in_paths, explicit_outlives_requirements)]
+#![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
+
+use {
+	d2sw_tiled_project::{ds1, io_readToString, stdoutRaw},
+	std::io::{self, BufWriter, Read, Write},
+};
+
+fn main() {
+	run(io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(stdin: impl Read, stdout: impl Write) {
+	toml::from_str::<ds1::RootStruct>(&io_readToString(stdin).unwrap())
+		.unwrap()
+		.writeTMXTo(&mut BufWriter::new(stdout));
+}
@@ -0,0 +1,36 @@
+#![warn(clippy::pedantic, elided_lifetimes_in_paths, explicit_outlives_requirements)]
+#![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
+
+use {
+	d2sw_tiled_project::{dc6, stdoutRaw, tar, unlet, Error, Image, PAL_LEN},
+	png::ColorType,
+	std::io::{self, BufWriter, Read, Write},
+};
+
+fn main() -> Result<(), Error> {
+	run(io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(mut stdin: impl Read, stdout: impl Write) -> Result<(), Error> {
+	let buffer = &mut Vec::<u8>::new();
+	stdin.read_to_end(buffer).unwrap();
+	let (pngPAL, dc6) = buffer.as_slice().split_at(PAL_LEN);
+	unlet!(buffer);
+
+	let dc6Metadata = &dc6::Metadata::new(dc6)?;
+	let image = Image::fromDC6(dc6Metadata, dc6)?;
+	let toml = &toml::to_string(dc6Metadata).unwrap_or_else(|err| panic!("{err}"));
+	let framesPNG = &mut Vec::new();
+	{
+		let mut png = png::Encoder::new(&mut *framesPNG, image.width as _, image.height as _);
+		png.set_color(ColorType::Indexed);
+		png.set_palette(pngPAL);
+		png.set_trns(&[0][..]);
+		png.write_header().unwrap().write_image_data(&image.data).unwrap();
+	}
+	let mut stdout = tar::Writer::new(BufWriter::new(stdout));
+	stdout.append("dc6.toml", tar::TOML, toml.as_bytes())?;
+	stdout.append("frames.png", tar::PNG, framesPNG)?;
+	stdout.finish()?;
+	Ok(())
+}
@@ -0,0 +1,57 @@
+#![warn(clippy::pedantic, elided_lifetimes_in_paths, explicit_outlives_requirements)]
+#![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
+
+use {
+	d2sw_tiled_project::{
+		dcc::{self, DCCError},
+		stdoutRaw, unlet, Image, MinAssign_MaxAssign_Ext, PAL_LEN,
+	},
+	png::ColorType,
+	std::io::{self, BufWriter, Read, Write},
+};
+
+// one row per direction, one column per frame, every frame anchored at the same point of its cell
+fn main() -> Result<(), DCCError> {
+	run(io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(mut stdin: impl Read, stdout: impl Write) -> Result<(), DCCError> {
+	let buffer = &mut Vec::<u8>::new();
+	stdin.read_to_end(buffer).unwrap();
+	let (pngPAL, dcc) = buffer.as_slice().split_at(PAL_LEN);
+	unlet!(buffer);
+
+	let directions = &dcc::decode(dcc)?;
+	let [mut left, mut top, mut right, mut bottom] = [0_i32; 4];
+	for direction in directions {
+		let [[directionLeft, directionTop], [directionRight, directionBottom]] = direction.bounds;
+		left.minAssign(directionLeft);
+		top.minAssign(directionTop);
+		right.maxAssign(directionRight);
+		bottom.maxAssign(directionBottom);
+	}
+	let [cellWidth, cellHeight] = [(right - left) as usize, (bottom - top) as usize];
+	let numFrames = directions.iter().map(|direction| direction.frames.len()).max().unwrap_or(0);
+	let image = &mut Image::fromWidthHeight(cellWidth * numFrames, cellHeight * directions.len());
+	for (row, direction) in directions.iter().enumerate() {
+		for (column, frame) in direction.frames.iter().enumerate() {
+			image.blitPixelsRectangle(
+				[
+					column * cellWidth + (frame.xOffset - left) as usize,
+					row * cellHeight + (frame.top() - top) as usize,
+				],
+				[frame.image.width, frame.image.height],
+				&frame.image,
+				[0, 0],
+			);
+		}
+	}
+	eprintln!(r#""frameWidth":{cellWidth},"frameHeight":{cellHeight},"anchor":[{},{}]"#, -left, -top);
+
+	let mut png = png::Encoder::new(BufWriter::new(stdout), image.width as _, image.height as _);
+	png.set_color(ColorType::Indexed);
+	png.set_palette(pngPAL);
+	png.set_trns(&[0][..]);
+	png.write_header().unwrap().write_image_data(&image.data).unwrap();
+	Ok(())
+}
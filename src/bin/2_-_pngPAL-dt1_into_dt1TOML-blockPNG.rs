@@ -2,26 +2,34 @@
 #![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
 
 use {
-	d2sw_tiled_project::{dt1, stdoutRaw, unlet, Image, PAL_LEN},
-	png::ColorType,
+	clap::Parser,
+	d2sw_tiled_project::{dt1, stdoutRaw, tar, unlet, Error, Layout, PAL_LEN},
 	std::io::{self, BufWriter, Read, Write},
 };
 
-fn main() -> Result<(), dt1::VersionMismatchError> {
+#[derive(Parser, Clone)]
+pub struct Args {
+	/// `columns`, or the packing heuristic to lay the blocks out with: `best-short-side-fit`, `best-area-fit`,
+	///   `bottom-left` or `skyline`.
+	#[clap(long, default_value_t)]
+	pub layout: Layout,
+}
+
+fn main() -> Result<(), Error> {
+	run(Args::parse(), io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(Args { layout }: Args, mut stdin: impl Read, stdout: impl Write) -> Result<(), Error> {
 	let buffer = &mut Vec::<u8>::new();
-	io::stdin().read_to_end(buffer).unwrap();
+	stdin.read_to_end(buffer).unwrap();
 	let (pngPAL, dt1) = buffer.as_slice().split_at(PAL_LEN);
 	unlet!(buffer);
 
-	let dt1Metadata = &dt1::Metadata::new(dt1)?;
-	let image = Image::fromDT1(&dt1Metadata.tiles, dt1);
-	let stdout = &mut BufWriter::new(stdoutRaw());
+	let (dt1Metadata, blockPNG) = &dt1::Metadata::newWithBlockPNG(pngPAL, dt1, layout)?;
 	let toml = &toml::to_string(dt1Metadata).unwrap_or_else(|err| panic!("{err}"));
-	write!(stdout, "{}\n{toml}", toml.len()).unwrap();
-	let mut png = png::Encoder::new(stdout, image.width as _, image.height as _);
-	png.set_color(ColorType::Indexed);
-	png.set_palette(pngPAL);
-	png.set_trns(&[0][..]);
-	png.write_header().unwrap().write_image_data(&image.data).unwrap();
+	let mut stdout = tar::Writer::new(BufWriter::new(stdout));
+	stdout.append("dt1.toml", tar::TOML, toml.as_bytes())?;
+	stdout.append("block.png", tar::PNG, blockPNG)?;
+	stdout.finish()?;
 	Ok(())
 }
@@ -2,76 +2,62 @@
 #![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
 
 use {
-	core::{
-		iter::{self, Map},
-		slice::Chunks,
+	clap::Parser,
+	d2sw_tiled_project::{
+		palette::{cubeIndex, Metric, PalInverse, Palette},
+		stdoutRaw, PAL_LEN,
+	},
+	std::{
+		io::{self, BufWriter, Read, Write},
+		path::PathBuf,
 	},
-	d2sw_tiled_project::{stdoutRaw, DotExt, PAL_LEN, RGBCUBE_VOLUME, RGB_SIZE},
-	glam::IVec3,
-	rand::{thread_rng, Rng},
-	std::io::{self, Read, Write},
 };
 
+#[derive(Parser)]
+pub struct Args {
+	/// How the nearest index is told: `rgb`, `redmean`, `cie76`, `ciede2000` or `oklab`; recorded in the
+	///   palInverse's header.
+	#[clap(long, default_value_t)]
+	metric: Metric,
+
+	/// Where palInverses are kept by their palette's hash and metric: taken from there if this one has been
+	///   made before, left there for next time if not.
+	#[clap(long)]
+	cacheDir: Option<PathBuf>,
+}
+
 fn main() {
-	let (mut rng, pngPAL) = (thread_rng(), &mut Vec::<u8>::with_capacity(PAL_LEN));
-	io::stdin().read_to_end(pngPAL).unwrap();
-	assert_eq!(pngPAL.len(), PAL_LEN);
+	run(Args::parse(), io::stdin().lock(), stdoutRaw())
+}
 
-	#[inline(always)]
-	fn ivec3Iter(palBytes: &[u8]) -> Map<Chunks<'_, u8>, fn(&[u8]) -> IVec3> {
-		palBytes.chunks(3).map(|slice| -> IVec3 {
-			IVec3::from_array(unsafe {
-				<[u8; 3]>::try_from(slice).unwrap_unchecked().map(|colorComponent| colorComponent as _)
-			})
-		})
-	}
+pub fn run(Args { metric, cacheDir }: Args, mut stdin: impl Read, stdout: impl Write) {
+	let pngPAL = &mut Vec::<u8>::with_capacity(PAL_LEN);
+	stdin.read_to_end(pngPAL).unwrap();
+	assert_eq!(pngPAL.len(), PAL_LEN);
+	let palette = &Palette::fromPngPAL(pngPAL);
 
-	let (mut palInverse, mut squaredDistBuf) = (
-		Box::<[u8; RGBCUBE_VOLUME]>::try_from(vec![rng.gen::<u8>(); RGBCUBE_VOLUME].into_boxed_slice())
-			.unwrap(),
-		Box::<[i32; RGBCUBE_VOLUME]>::try_from(vec![i32::MAX; RGBCUBE_VOLUME].into_boxed_slice()).unwrap(),
-	);
-	// Based on S. W. Thomas' "Efficient Inverse Color Map Computation" (1991),
-	//   the "simple" version, that one with the complexity of O(PAL_LEN * 2.pow(3 * u8::BITS)) (in my case).
-	for (i, palColor) in ivec3Iter(pngPAL).enumerate() {
-		const X: i32 = 1;
-		const XSQR: i32 = X.pow(2);
-		const TXSQR: usize = (2 * XSQR) as _;
-		let (mut rdist, [rinc, ginc, binc], mut j) =
-			(palColor.lengthSquared(), (XSQR - 2 * X * palColor).to_array(), 0);
-		for (rxx, _) in iter::zip((rinc..).step_by(TXSQR), 0..=u8::MAX) {
-			let mut gdist = rdist;
-			for (gxx, _) in iter::zip((ginc..).step_by(TXSQR), 0..=u8::MAX) {
-				let mut bdist = gdist;
-				for (bxx, _) in iter::zip((binc..).step_by(TXSQR), 0..=u8::MAX) {
-					if squaredDistBuf[j] > bdist {
-						squaredDistBuf[j] = bdist;
-						palInverse[j] = i as _;
-					}
-					j += 1;
-					bdist += bxx;
-				}
-				gdist += gxx;
-			}
-			rdist += rxx;
+	let palInverse = match cacheDir {
+		Some(cacheDir) => {
+			PalInverse::cached(palette, metric, &cacheDir).unwrap_or_else(|err| panic!("{cacheDir:?}: {err}"))
 		}
-	}
+		None => PalInverse::ofPalette(palette, metric),
+	};
 
-	let (mut nearestSquaredDist, mut nearestI, testColor) = (
-		i32::MAX,
-		rng.gen::<u8>(),
-		IVec3::from_array(rng.gen::<[u8; RGB_SIZE]>().map(|colorComponent| colorComponent as _)),
-	);
-	eprintln!("testColor = {testColor}");
-	for (i, palColor) in ivec3Iter(pngPAL).enumerate() {
-		let squaredDist = (testColor - palColor).lengthSquared();
-		if squaredDist < nearestSquaredDist {
-			nearestSquaredDist = squaredDist;
-			nearestI = i as _;
+	// the colors 51 apart, every one against every palette color
+	for testColor in (0..6 * 6 * 6_u8).map(|i| [i / 36, i / 6 % 6, i % 6].map(|component| component * 51)) {
+		let testCoordinates = metric.coordinates(testColor);
+		let (mut nearestDist, mut nearestI) = (f64::INFINITY, 0);
+		for (i, &palColor) in palette.colors.iter().enumerate() {
+			let dist = metric.distance(testCoordinates, metric.coordinates(palColor));
+			if dist < nearestDist {
+				nearestDist = dist;
+				nearestI = i as _;
+			}
 		}
+		assert_eq!(palInverse.cube[cubeIndex(testColor)], nearestI, "testColor = {testColor:?}");
 	}
-	let j = ((testColor.x << (2 * u8::BITS)) | (testColor.y << u8::BITS) | testColor.z) as usize;
-	assert_eq!((palInverse[j], squaredDistBuf[j]), (nearestI, nearestSquaredDist));
 
-	stdoutRaw().write_all(&palInverse[..]).unwrap();
+	let stdout = &mut BufWriter::new(stdout);
+	palInverse.write(stdout).unwrap();
+	stdout.flush().unwrap();
 }
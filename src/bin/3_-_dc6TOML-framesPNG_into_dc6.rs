@@ -0,0 +1,29 @@
+#![warn(clippy::pedantic, elided_lifetimes_in_paths, explicit_outlives_requirements)]
+#![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
+
+use {
+	d2sw_tiled_project::{dc6, stdoutRaw, tar, Error, Image},
+	std::{
+		io::{self, Read, Write},
+		process::ExitCode,
+	},
+};
+
+fn main() -> Result<ExitCode, Error> {
+	run(io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(stdin: impl Read, mut stdout: impl Write) -> Result<ExitCode, Error> {
+	let entries = &tar::Entries::read(stdin)?;
+	// what a failed earlier stage of the pipeline leaves
+	if entries.0.is_empty() {
+		return Ok(ExitCode::FAILURE);
+	}
+	toml::from_slice::<dc6::Metadata>(&entries.only(tar::TOML)?.data)
+		.unwrap()
+		.writeWithFrameDataFromFramesImage(
+			&Image::fromPNG(&mut png::Decoder::new(&*entries.only(tar::PNG)?.data).read_info().unwrap()),
+			&mut stdout,
+		)?;
+	Ok(ExitCode::SUCCESS)
+}
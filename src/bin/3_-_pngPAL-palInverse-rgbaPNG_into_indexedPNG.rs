@@ -2,28 +2,173 @@
 #![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
 
 use {
-	core::iter,
+	clap::Parser,
 	d2sw_tiled_project::{
-		stdoutRaw, unlet, VecExt, BLACK, FULLY_TRANSPARENT, PAL_LEN, RGBA_SIZE, RGBCUBE_VOLUME,
+		palette::{cubeIndex, Color, Dither, Metric, PalInverse, Palette, NUM_COLORS},
+		stdoutRaw, VecExt, PAL_LEN, RGBA_SIZE, RGBCUBE_VOLUME,
 	},
 	png::ColorType,
-	std::io::{self, BufWriter, Read},
+	std::{
+		collections::HashMap,
+		fs,
+		io::{self, BufWriter, Read, Write},
+		path::PathBuf,
+		str::FromStr,
+	},
 };
 
+/// Palette indices as `1-31,64,172`.
+#[derive(Clone, Debug)]
+struct Indices(Vec<u8>);
+impl FromStr for Indices {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let index = |s: &str| s.trim().parse::<u8>().map_err(|err| format!("{s:?}: {err}"));
+		let mut indices = Vec::new();
+		for range in s.split(',') {
+			let (first, last) = match range.split_once('-') {
+				Some((first, last)) => (index(first)?, index(last)?),
+				None => (index(range)?, index(range)?),
+			};
+			if first > last {
+				return Err(format!("{range:?} runs backwards"));
+			}
+			indices.extend(first..=last);
+		}
+		Ok(Self(indices))
+	}
+}
+
+// the indices --only-indices and --only-indices-of leave, searched under the palInverse's metric
+struct Allowed {
+	metric: Metric,
+	indices: Vec<(u8, [f64; 3])>,
+	found: HashMap<Color, u8>,
+}
+
+// the palInverse for the whole palette, or a search of the allowed indices
+struct Nearest<'a> {
+	palInverse: &'a [u8; RGBCUBE_VOLUME],
+	transparent: u8,
+	black: u8,
+	allowed: Option<Allowed>,
+	madeBlack: usize,
+}
+impl Nearest<'_> {
+	fn of(&mut self, color: Color) -> u8 {
+		let Some(Allowed { metric, indices, found }) = &mut self.allowed else {
+			return match self.palInverse[cubeIndex(color)] {
+				index if index == self.transparent => {
+					self.madeBlack += 1;
+					self.black
+				}
+				index => index,
+			};
+		};
+		*found.entry(color).or_insert_with(|| {
+			let coordinates = metric.coordinates(color);
+			indices
+				.iter()
+				.min_by(|(_, a), (_, b)| {
+					metric.distance(coordinates, *a).total_cmp(&metric.distance(coordinates, *b))
+				})
+				.unwrap()
+				.0
+		})
+	}
+}
+
+#[derive(Parser)]
+pub struct Args {
+	/// The metric the palInverse is expected to have been made with, warned about if its header says
+	///   otherwise; `d2sw palette index` makes it with this one in a --pal-inverse cache directory.
+	#[clap(long)]
+	pub metric: Option<Metric>,
+
+	/// One of none, floyd-steinberg, atkinson, bayer2, bayer4, bayer8.
+	#[clap(long, default_value_t)]
+	dither: Dither,
+
+	/// How far, per color component, the bayer dithers push a pixel either way at most.
+	#[clap(long, default_value_t = 16.0)]
+	spread: f64,
+
+	/// Pixels this transparent or more become the transparent index.
+	#[clap(long, default_value_t = u8::MAX / 16)]
+	alphaThreshold: u8,
+
+	/// The only palette indices pixels become, as `1-31,64,172`; with --only-indices-of, either's.
+	#[clap(long)]
+	onlyIndices: Option<Indices>,
+
+	/// Indexed PNGs whose indices are the only ones pixels become, e.g. a tileset's original tile PNG.
+	#[clap(long)]
+	onlyIndicesOf: Vec<PathBuf>,
+}
+
 fn main() {
-	let stdin = &mut io::stdin().lock();
-	let boxedSlice = {
-		let mut vec = Vec::<u8>::with_capacity(PAL_LEN + RGBCUBE_VOLUME);
-		stdin.take(vec.capacity() as _).read_to_end(&mut vec).unwrap();
-		vec.into_boxed_slice()
-	};
-	let pngPAL;
-	let palInverse = {
-		let palInverse;
-		(pngPAL, palInverse) = boxedSlice.split_at(PAL_LEN);
-		<&[_; RGBCUBE_VOLUME]>::try_from(palInverse).unwrap()
+	run(Args::parse(), io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(
+	Args { metric, dither, spread, alphaThreshold, onlyIndices, onlyIndicesOf }: Args,
+	mut stdin: impl Read,
+	stdout: impl Write,
+) {
+	let stdin = &mut stdin;
+	let pngPAL = &mut [0; PAL_LEN];
+	stdin.read_exact(pngPAL).unwrap();
+	let palInverse = PalInverse::read(stdin).unwrap();
+	match (metric, palInverse.metric) {
+		(Some(expected), Some(recorded)) if expected != recorded => {
+			eprintln!("warning: the palInverse was made with --metric {recorded}, not {expected}");
+		}
+		(Some(expected), None) => {
+			eprintln!(
+				"warning: the palInverse has no header saying its metric, --metric {expected} can't be checked"
+			);
+		}
+		_ => {}
+	}
+	let palette = &Palette::fromPngPAL(pngPAL);
+	let allowed = (onlyIndices.is_some() || !onlyIndicesOf.is_empty()).then(|| {
+		let allowed = &mut [false; NUM_COLORS];
+		for index in onlyIndices.into_iter().flat_map(|Indices(indices)| indices) {
+			allowed[index as usize] = true;
+		}
+		for path in &onlyIndicesOf {
+			let bytes = &fs::read(path).unwrap_or_else(|err| panic!("{path:?}: {err}"));
+			let png =
+				&mut png::Decoder::new(&bytes[..]).read_info().unwrap_or_else(|err| panic!("{path:?}: {err}"));
+			assert_eq!(
+				(png.info().color_type, png.info().bit_depth),
+				(ColorType::Indexed, png::BitDepth::Eight),
+				"{path:?} isn't an 8-bit indexed PNG"
+			);
+			let mut vec = Vec::withLen(png.output_buffer_size());
+			let len = png.next_frame(&mut vec).unwrap_or_else(|err| panic!("{path:?}: {err}")).buffer_size();
+			vec.setLen(len);
+			for &index in &vec {
+				allowed[index as usize] = true;
+			}
+		}
+		allowed[usize::from(palette.transparent)] = false;
+		let metric = palInverse.metric.or(metric).unwrap_or_default();
+		let indices = Vec::from_iter(
+			(0..=u8::MAX)
+				.filter(|&index| allowed[index as usize])
+				.map(|index| (index, metric.coordinates(palette.colors[index as usize]))),
+		);
+		assert!(!indices.is_empty(), "no palette indices left to become, besides the transparent one");
+		Allowed { metric, indices, found: HashMap::new() }
+	});
+	let nearest = &mut Nearest {
+		palInverse: &palInverse.cube,
+		transparent: palette.transparent,
+		black: palette.black(),
+		allowed,
+		madeBlack: 0,
 	};
-	unlet!(boxedSlice);
 	let (rgbaData, width, height) = {
 		let png = &mut png::Decoder::new(stdin).read_info().unwrap();
 		let mut vec = Vec::withLen(png.output_buffer_size());
@@ -32,26 +177,54 @@ fn main() {
 		let &png::Info { width, height, .. } = png.info();
 		(vec, width, height)
 	};
-	let mut indexedColor_data = Vec::withLen((width * height) as _);
-	for (indexedColor_pixel, [red, green, blue, alpha]) in iter::zip(
-		indexedColor_data.iter_mut(),
-		rgbaData.as_slice().chunks(RGBA_SIZE).map(|slice| {
-			unsafe { <[u8; RGBA_SIZE]>::try_from(slice.get_unchecked(..RGBA_SIZE)).unwrap_unchecked() }
-				.map(|colorComponent| colorComponent as usize)
-		}),
-	) {
-		*indexedColor_pixel = if alpha <= (u8::MAX / 16) as _ {
-			FULLY_TRANSPARENT
-		} else {
-			match palInverse[(red << (2 * u8::BITS)) | (green << u8::BITS) | blue] {
-				0 => BLACK,
-				nonZero => nonZero,
+
+	// what each pixel is after the error diffused into it so far
+	let mut wanted = Vec::from_iter(
+		rgbaData.chunks_exact(RGBA_SIZE).map(|rgba| [rgba[0], rgba[1], rgba[2]].map(f64::from)),
+	);
+	let (width, height) = (width as usize, height as usize);
+	let mut indexedColor_data = Vec::withLen(width * height);
+	let (mut opaque, mut errorSum) = (0_usize, 0.0);
+	for y in 0..height {
+		for x in 0..width {
+			let i = y * width + x;
+			let rgba = &rgbaData[i * RGBA_SIZE..][..RGBA_SIZE];
+			if rgba[3] <= alphaThreshold {
+				indexedColor_data[i] = palette.transparent;
+				continue;
+			}
+			let offset = dither.threshold([x, y]) * 2.0 * spread;
+			let index =
+				nearest.of(wanted[i].map(|component| (component + offset).round().clamp(0.0, 255.0) as u8));
+			indexedColor_data[i] = index;
+			let chosen = palette.colors[index as usize].map(f64::from);
+			opaque += 1;
+			errorSum += (0..3).map(|c| (f64::from(rgba[c]) - chosen[c]).powi(2)).sum::<f64>().sqrt();
+			let error = [0, 1, 2].map(|c| wanted[i][c] - chosen[c]);
+			for &([Δx, Δy], share) in dither.diffusion() {
+				let (x, y) = (x.wrapping_add_signed(Δx), y.wrapping_add_signed(Δy));
+				if x < width && y < height {
+					for (component, error) in wanted[y * width + x].iter_mut().zip(error) {
+						*component += error * share;
+					}
+				}
 			}
 		}
 	}
-	let mut png = png::Encoder::new(BufWriter::new(stdoutRaw()), width, height);
+	eprintln!(
+		"{opaque} opaque pixels, {:.2} mean RGB distance from their palette colors",
+		if opaque == 0 { 0.0 } else { errorSum / opaque as f64 }
+	);
+	if nearest.madeBlack != 0 {
+		eprintln!(
+			"{} pixels the palInverse gave the transparent index made {} (black) instead",
+			nearest.madeBlack, nearest.black
+		);
+	}
+
+	let mut png = png::Encoder::new(BufWriter::new(stdout), width as _, height as _);
 	png.set_color(ColorType::Indexed);
-	png.set_palette(pngPAL);
-	png.set_trns(&[0][..]);
+	png.set_palette(&pngPAL[..]);
+	png.set_trns(palette.trns());
 	png.write_header().unwrap().write_image_data(&indexedColor_data).unwrap();
 }
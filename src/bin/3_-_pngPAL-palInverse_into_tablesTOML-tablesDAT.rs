@@ -0,0 +1,58 @@
+#![warn(clippy::pedantic, elided_lifetimes_in_paths, explicit_outlives_requirements)]
+#![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
+
+use {
+	clap::Parser,
+	d2sw_tiled_project::{
+		palette::{Metric, PalInverse, Palette, Tables, Tint},
+		stdoutRaw, tar, toml_toStringPretty, PAL_LEN,
+	},
+	std::io::{self, BufWriter, Read, Write},
+};
+
+#[derive(Parser)]
+pub struct Args {
+	/// The metric the palInverse is expected to have been made with, warned about if its header says
+	///   otherwise; `d2sw palette tables` makes it with this one in a --pal-inverse cache directory.
+	#[clap(long)]
+	pub metric: Option<Metric>,
+
+	/// How many light-level tables, from full brightness down to 1/N of it.
+	#[clap(long, default_value_t = 32)]
+	lightLevels: usize,
+
+	/// The tints to make tables for: `red`, `green`, `blue` or `name=RRGGBB`, what white becomes.
+	#[clap(long = "tint", default_values = &["red", "green", "blue"])]
+	tints: Vec<Tint>,
+
+	/// Leaves out the table of every index's nearest grey.
+	#[clap(long)]
+	noGrey: bool,
+}
+
+fn main() {
+	run(Args::parse(), io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(Args { metric, lightLevels, tints, noGrey }: Args, mut stdin: impl Read, stdout: impl Write) {
+	let pngPAL = &mut [0; PAL_LEN];
+	stdin.read_exact(pngPAL).unwrap();
+	let palInverse = PalInverse::read(&mut stdin).unwrap();
+	match (metric, palInverse.metric) {
+		(Some(expected), Some(recorded)) if expected != recorded => {
+			eprintln!("warning: the palInverse was made with --metric {recorded}, not {expected}");
+		}
+		(Some(expected), None) => {
+			eprintln!(
+				"warning: the palInverse has no header saying its metric, --metric {expected} can't be checked"
+			);
+		}
+		_ => {}
+	}
+	let (palette, palInverse) = (Palette::fromPngPAL(pngPAL), &palInverse.cube);
+	let tables = &Tables { lightLevels, grey: !noGrey, tints };
+	let mut stdout = tar::Writer::new(BufWriter::new(stdout));
+	stdout.append("tables.toml", tar::TOML, toml_toStringPretty(tables).unwrap().as_bytes()).unwrap();
+	stdout.append("tables.dat", tar::OCTET_STREAM, &palette.tables(palInverse, tables).concat()).unwrap();
+	stdout.finish().unwrap();
+}
@@ -0,0 +1,33 @@
+#![warn(clippy::pedantic, elided_lifetimes_in_paths, explicit_outlives_requirements)]
+#![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
+
+use {
+	clap::Parser,
+	d2sw_tiled_project::{ds1, io_readToString, stdoutRaw, tmx::TMXError, toml_toStringPretty},
+	std::{
+		fs,
+		io::{self, Read, Write},
+		path::PathBuf,
+	},
+};
+
+#[derive(Parser)]
+pub struct Args {
+	/// The directory external tilesets' `source` paths are relative to.
+	#[clap(long, default_value = ".")]
+	tmxDir: PathBuf,
+}
+
+fn main() -> Result<(), TMXError> {
+	run(Args::parse(), io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(Args { tmxDir }: Args, stdin: impl Read, mut stdout: impl Write) -> Result<(), TMXError> {
+	let ds1RootStruct = &ds1::RootStruct::fromTMX(&io_readToString(stdin).unwrap(), |source| {
+		fs::read_to_string(tmxDir.join(source))
+	})?;
+	stdout
+		.write_all(&toml_toStringPretty(ds1RootStruct).unwrap_or_else(|err| panic!("{err}")).into_bytes())
+		.unwrap();
+	Ok(())
+}
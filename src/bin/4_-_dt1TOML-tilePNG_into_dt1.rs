@@ -2,37 +2,25 @@
 #![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
 
 use {
-	core::str::{self, FromStr},
-	d2sw_tiled_project::{dt1, io_readToString, stdoutRaw, Image},
-	memchr::memchr,
+	d2sw_tiled_project::{dt1, stdoutRaw, tar, Error},
 	std::{
-		io::{self, BufRead, Read},
+		io::{self, Read, Write},
 		process::ExitCode,
 	},
 };
 
-fn main() -> ExitCode {
-	let stdin = &mut io::stdin().lock();
-	{
-		let (filesizeLine_len, filesize) = {
-			let buffer = stdin.fill_buf().unwrap();
-			let filesizeLine = str::from_utf8(
-				&buffer[..={
-					match memchr(b'\n', buffer) {
-						Some(index) => index,
-						None => return ExitCode::FAILURE,
-					}
-				}],
-			)
-			.unwrap();
-			(filesizeLine.len(), u64::from_str(filesizeLine.trim_end_matches(['\n', '\r'])).unwrap())
-		};
-		stdin.consume(filesizeLine_len);
-		toml::from_str::<dt1::Metadata>(&io_readToString(stdin.take(filesize)).unwrap()).unwrap()
+fn main() -> Result<ExitCode, Error> {
+	run(io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(stdin: impl Read, mut stdout: impl Write) -> Result<ExitCode, Error> {
+	let entries = &tar::Entries::read(stdin)?;
+	// what a failed earlier stage of the pipeline leaves
+	if entries.0.is_empty() {
+		return Ok(ExitCode::FAILURE);
 	}
-	.writeWithBlockDataFromTileImage(
-		&Image::fromPNG(&mut png::Decoder::new(stdin).read_info().unwrap()),
-		&mut stdoutRaw(),
-	);
-	ExitCode::SUCCESS
+	toml::from_slice::<dt1::Metadata>(&entries.only(tar::TOML)?.data)
+		.unwrap()
+		.writeFromTilePNG(&entries.only(tar::PNG)?.data, &mut stdout)?;
+	Ok(ExitCode::SUCCESS)
 }
@@ -2,47 +2,34 @@
 #![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
 
 use {
-	core::{
-		cmp::max,
-		mem,
-		str::{self, FromStr},
-	},
+	core::{cmp::max, mem},
 	d2sw_tiled_project::{
 		dt1::{
 			self, BLOCKWIDTH, FLOOR_ORIENTATION, FLOOR_ROOF_BLOCKHEIGHT, FLOOR_ROOF_TILEHEIGHT,
 			NUM_SUBTILES_PER_LINE, ROOF_ORIENTATION, TILEWIDTH,
 		},
-		io_readToString, stdoutRaw, Image, MinAssign_MaxAssign_Ext, TileColumns, TilesIterator, UsizeExt,
-		Vec2Ext, WIDTH,
+		stdoutRaw, tar, Error, Image, MinAssign_MaxAssign_Ext, TileColumns, TilesIterator, UsizeExt, Vec2Ext,
+		WIDTH,
 	},
-	memchr::memchr,
 	png::ColorType,
 	std::{
-		io::{self, BufRead, BufWriter, Read},
+		io::{self, BufWriter, Read, Write},
 		process::ExitCode,
 	},
 };
 
-fn main() -> ExitCode {
-	let stdin = &mut io::stdin().lock();
-	let mut dt1Metadata: dt1::Metadata = {
-		let (filesizeLine_len, filesize) = {
-			let buffer = stdin.fill_buf().unwrap();
-			let filesizeLine = str::from_utf8(
-				&buffer[..={
-					match memchr(b'\n', buffer) {
-						Some(index) => index,
-						None => return ExitCode::FAILURE,
-					}
-				}],
-			)
-			.unwrap();
-			(filesizeLine.len(), u64::from_str(filesizeLine.trim_end_matches(['\n', '\r'])).unwrap())
-		};
-		stdin.consume(filesizeLine_len);
-		toml::from_str(&io_readToString(stdin.take(filesize)).unwrap()).unwrap()
-	};
-	let png = &mut png::Decoder::new(stdin).read_info().unwrap();
+fn main() -> Result<ExitCode, Error> {
+	run(io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(stdin: impl Read, stdout: impl Write) -> Result<ExitCode, Error> {
+	let entries = &tar::Entries::read(stdin)?;
+	// what a failed earlier stage of the pipeline leaves
+	if entries.0.is_empty() {
+		return Ok(ExitCode::FAILURE);
+	}
+	let mut dt1Metadata: dt1::Metadata = toml::from_slice(&entries.only(tar::TOML)?.data).unwrap();
+	let png = &mut png::Decoder::new(&*entries.only(tar::PNG)?.data).read_info().unwrap();
 	let (srcImage, pngPAL) = (&mut Image::fromPNG(png), png.info().palette.as_ref().unwrap().as_ref());
 	dt1Metadata.tiles.retain_mut(|tile| {
 		if tile.blocks.len() == 0 {
@@ -103,7 +90,7 @@ fn main() -> ExitCode {
 	};
 	{
 		let destPoints = &mut TilesIterator::new(TILEWIDTH, destImage);
-		let srcPoints = &mut TilesIterator::new(TILEWIDTH, srcImage);
+		let srcPoints = &mut TilesIterator::fromPNG(TILEWIDTH, srcImage, png.info());
 		for tile in &dt1Metadata.tiles {
 			let tileHeight = tile.height as usize;
 			let [destPoint, srcPoint] = [destPoints.next(FLOOR_ROOF_TILEHEIGHT), srcPoints.next(tileHeight)];
@@ -192,11 +179,10 @@ fn main() -> ExitCode {
 		*/
 		];
 	}
-	let mut png =
-		png::Encoder::new(BufWriter::new(stdoutRaw()), destImage.width as _, destImage.height as _);
+	let mut png = png::Encoder::new(BufWriter::new(stdout), destImage.width as _, destImage.height as _);
 	png.set_color(ColorType::Indexed);
 	png.set_palette(pngPAL);
 	png.set_trns(&[0][..]);
 	png.write_header().unwrap().write_image_data(&destImage.data).unwrap();
-	ExitCode::SUCCESS
+	Ok(ExitCode::SUCCESS)
 }
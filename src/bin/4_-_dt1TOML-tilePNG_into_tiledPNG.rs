@@ -2,54 +2,70 @@
 #![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
 
 use {
-	core::str::{self, FromStr},
+	clap::Parser,
 	d2sw_tiled_project::{
 		applyMacro,
-		dt1::{self, Block, TILEWIDTH},
-		io_readToString, stdoutRaw, unlet, Image, MinAssign_MaxAssign_Ext, TilesIterator, X, Y,
+		dt1::{self, Block, FLOOR_ROOF_TILEHEIGHT, TILEWIDTH},
+		stdoutRaw, tar,
+		tmx::TilesetImage,
+		unlet, Error, Image, MinAssign_MaxAssign_Ext, TilesIterator, X, Y,
 	},
-	memchr::memchr,
 	png::ColorType,
 	std::{
-		io::{self, BufRead, BufWriter, Read},
+		fs::File,
+		io::{self, BufWriter, Read, Write},
+		path::PathBuf,
 		process::ExitCode,
 	},
 };
 
-fn main() -> ExitCode {
-	let stdin = &mut io::stdin().lock();
-	let tiles = &{
-		let (filesizeLine_len, filesize) = {
-			let buffer = stdin.fill_buf().unwrap();
-			let filesizeLine = str::from_utf8(
-				&buffer[..={
-					match memchr(b'\n', buffer) {
-						Some(index) => index,
-						None => return ExitCode::FAILURE,
-					}
-				}],
-			)
-			.unwrap();
-			(filesizeLine.len(), u64::from_str(filesizeLine.trim_end_matches(['\n', '\r'])).unwrap())
-		};
-		stdin.consume(filesizeLine_len);
-		toml::from_str::<dt1::Metadata>(&io_readToString(stdin.take(filesize)).unwrap()).unwrap()
+#[derive(Parser)]
+pub struct Args {
+	/// Also write a Tiled tileset for the output PNG, either a .tsx or a .tsj one.
+	#[clap(long)]
+	tileset: Option<PathBuf>,
+
+	/// The output PNG's path as the tileset should refer to it [default: <tileset stem>.tiled.png]
+	#[clap(long, requires = "tileset")]
+	image: Option<String>,
+}
+
+fn main() -> Result<ExitCode, Error> {
+	run(Args::parse(), io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(
+	Args { tileset, image }: Args,
+	stdin: impl Read,
+	stdout: impl Write,
+) -> Result<ExitCode, Error> {
+	let entries = &tar::Entries::read(stdin)?;
+	// what a failed earlier stage of the pipeline leaves
+	if entries.0.is_empty() {
+		return Ok(ExitCode::FAILURE);
 	}
-	.tiles;
-	let png = &mut png::Decoder::new(stdin).read_info().unwrap();
+	let dt1Metadata = &toml::from_slice::<dt1::Metadata>(&entries.only(tar::TOML)?.data).unwrap();
+	let tiles = &dt1Metadata.tiles;
+	let png = &mut png::Decoder::new(&*entries.only(tar::PNG)?.data).read_info().unwrap();
 	let (srcImage, pngPAL) = (&Image::fromPNG(png), png.info().palette.as_ref().unwrap().as_ref());
-	applyMacro!(unlet; (png), (stdin));
-	let (mut destTileHeight, srcTileHeights) = (0_usize, &mut Vec::with_capacity(tiles.len()));
+	let srcPoints = &mut TilesIterator::fromPNG(TILEWIDTH, srcImage, png.info());
+	applyMacro!(unlet; (png), (entries));
+	// every tile goes as far down its cell as it's drawn below the top corner of its floor diamond, so that all
+	//   of them stand on the same spot and one tileoffset fits the whole tileset
+	let ([mut cellTop, mut cellBottom], srcTileSpans) =
+		([i32::MAX, i32::MIN], &mut Vec::with_capacity(tiles.len()));
 	for tile in tiles {
 		let [mut startY, mut endY, blockHeight] = [i16::MAX, i16::MIN, tile.blockHeight() as _];
 		for &Block { y, .. } in &tile.blocks {
 			startY.minAssign(y);
 			endY.maxAssign(y + blockHeight);
 		}
-		let srcTileHeight = (endY - startY) as usize;
-		srcTileHeights.push(srcTileHeight);
-		destTileHeight.maxAssign(srcTileHeight);
+		let (top, srcTileHeight) = (tile.drawnY() + i32::from(startY), (endY - startY) as usize);
+		srcTileSpans.push((top, srcTileHeight));
+		cellTop.minAssign(top);
+		cellBottom.maxAssign(top + srcTileHeight as i32);
 	}
+	let destTileHeight = (cellBottom - cellTop) as usize;
 	{
 		let destColumnCount =
 			(tiles.len() as f32 * (destTileHeight as f32 / TILEWIDTH as f32)).sqrt().ceil() as usize;
@@ -57,13 +73,12 @@ fn main() -> ExitCode {
 		let destImage =
 			&mut Image::fromWidthHeight(destColumnCount * TILEWIDTH, destRowCount * destTileHeight);
 		{
-			let (mut destPoint, srcTileHeights, srcPoints) =
-				([0, 0], &mut srcTileHeights.iter(), &mut TilesIterator::new(TILEWIDTH, srcImage));
+			let (mut destPoint, srcTileSpans) = ([0, 0], &mut srcTileSpans.iter());
 			'outer: for _ in 0..destRowCount {
 				for _ in 0..destColumnCount {
-					if let Some(&srcTileHeight) = srcTileHeights.next() {
+					if let Some(&(top, srcTileHeight)) = srcTileSpans.next() {
 						destImage.blitPixelsRectangle(
-							destPoint,
+							[destPoint[X], destPoint[Y] + (top - cellTop) as usize],
 							[TILEWIDTH, srcTileHeight],
 							srcImage,
 							srcPoints.next(srcTileHeight),
@@ -76,13 +91,34 @@ fn main() -> ExitCode {
 				destPoint = [0, destPoint[Y] + destTileHeight];
 			}
 		}
-		let mut png =
-			png::Encoder::new(BufWriter::new(stdoutRaw()), destImage.width as _, destImage.height as _);
+		let mut png = png::Encoder::new(BufWriter::new(stdout), destImage.width as _, destImage.height as _);
 		png.set_color(ColorType::Indexed);
 		png.set_palette(pngPAL);
 		png.set_trns(&[0][..]);
 		png.write_header().unwrap().write_image_data(&destImage.data).unwrap();
+
+		if let Some(tileset) = tileset {
+			let (name, to) = (
+				tileset.file_stem().unwrap().to_string_lossy(),
+				&mut BufWriter::new(
+					File::create(&tileset).unwrap_or_else(|err| panic!("{:?}: {err}", tileset.display())),
+				),
+			);
+			let image = image.unwrap_or_else(|| format!("{name}.tiled.png"));
+			let tilesetImage = &TilesetImage {
+				source: &image,
+				dimensions: [destImage.width, destImage.height],
+				tileheight: destTileHeight,
+				columns: destColumnCount,
+				tileoffsetY: cellBottom - FLOOR_ROOF_TILEHEIGHT as i32,
+			};
+			if tileset.extension().map_or(false, |extension| extension == "tsj") {
+				dt1Metadata.writeTSJTo(to, &name, tilesetImage);
+			} else {
+				dt1Metadata.writeTSXTo(to, &name, tilesetImage);
+			}
+		}
 	}
 	eprintln!("\"tileheight\":{destTileHeight}");
-	ExitCode::SUCCESS
+	Ok(ExitCode::SUCCESS)
 }
@@ -7,11 +7,14 @@ use {
 		stdoutRaw, Image, TilesIterator, X,
 	},
 	png::ColorType,
-	std::io::{self, BufWriter},
+	std::io::{self, BufWriter, Read, Write},
 };
 
 fn main() {
-	let stdin = &mut io::stdin().lock();
+	run(io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(stdin: impl Read, stdout: impl Write) {
 	let png = &mut png::Decoder::new(stdin).read_info().unwrap();
 	let (srcImage, pngPAL) = (&mut Image::fromPNG(png), png.info().palette.as_ref().unwrap().as_ref());
 	let destImage = &mut Image::fromWidthHeight(
@@ -29,8 +32,7 @@ fn main() {
 			destImage.drawNoisySquareTile(destPoints.next(SQUARE_TILE_SIZE + 1), srcImage, srcPoint);
 		}
 	}
-	let mut png =
-		png::Encoder::new(BufWriter::new(stdoutRaw()), destImage.width as _, destImage.height as _);
+	let mut png = png::Encoder::new(BufWriter::new(stdout), destImage.width as _, destImage.height as _);
 	png.set_color(ColorType::Indexed);
 	png.set_palette(pngPAL);
 	png.set_trns(&[0][..]);
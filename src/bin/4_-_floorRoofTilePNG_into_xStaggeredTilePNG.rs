@@ -7,11 +7,14 @@ use {
 		stdoutRaw, Image, TilesIterator, X, Y,
 	},
 	png::ColorType,
-	std::io::{self, BufWriter},
+	std::io::{self, BufWriter, Read, Write},
 };
 
 fn main() {
-	let stdin = &mut io::stdin().lock();
+	run(io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(stdin: impl Read, stdout: impl Write) {
 	let png = &mut png::Decoder::new(stdin).read_info().unwrap();
 	let (srcImage, pngPAL) = (&mut Image::fromPNG(png), png.info().palette.as_ref().unwrap().as_ref());
 	let destImage = &mut Image::fromWidthHeight(
@@ -41,8 +44,7 @@ fn main() {
 			);
 		}
 	}
-	let mut png =
-		png::Encoder::new(BufWriter::new(stdoutRaw()), destImage.width as _, destImage.height as _);
+	let mut png = png::Encoder::new(BufWriter::new(stdout), destImage.width as _, destImage.height as _);
 	png.set_color(ColorType::Indexed);
 	png.set_palette(pngPAL);
 	png.set_trns(&[0][..]);
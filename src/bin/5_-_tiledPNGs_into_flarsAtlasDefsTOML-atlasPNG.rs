@@ -3,79 +3,212 @@
 
 use {
 	clap::Parser,
-	const_format::formatcp,
 	core::{
 		array, iter,
-		num::ParseIntError,
+		num::NonZeroUsize,
 		str::{self, FromStr},
 	},
 	d2sw_tiled_project::{
-		applyMacro, default, stdoutRaw, toml_toStringPretty, DivRemExt, Image, LenConst_Ext,
-		MinAssign_MaxAssign_Ext, Rectangle, Vec2, Vec2Ext, DIMENSIONS, HEIGHT, POINT, WIDTH, X, Y,
+		applyMacro, atlas, pack,
+		palette::{cubeIndex, Metric, PalInverse, Palette},
+		stdoutRaw, tar, DivRemExt, Image, MinAssign_MaxAssign_Ext, Vec2, Vec2Ext, DIMENSIONS,
+		FULLY_TRANSPARENT, HEIGHT, PAL_LEN, POINT, RGBA_SIZE, RGBCUBE_VOLUME, RGB_SIZE, WIDTH, X, Y,
 	},
 	png::ColorType,
-	serde::Serialize,
 	std::{
-		io::{self, BufRead, BufWriter, Read, Write},
+		collections::HashMap,
+		fs,
+		io::{self, BufWriter, Read, Write},
+		path::PathBuf,
 		process::Command,
 	},
 };
 
-fn main() {
-	#[derive(Parser, Debug)]
-	struct Args {
-		#[clap(long)]
-		firstgid: usize,
-		#[clap(required = true)]
-		tileDimensionPairs: Vec<DimensionPair>,
-		rectpackerPath: String,
+#[derive(Parser, Debug)]
+pub struct Args {
+	#[clap(long)]
+	firstgid: usize,
+	#[clap(required = true)]
+	tileDimensionPairs: Vec<DimensionPair>,
+
+	/// How the built-in packer lays the sprites out: `best-short-side-fit`, `best-area-fit`, `bottom-left` or
+	///   `skyline`.
+	#[clap(long, default_value_t = pack::Heuristic::BestShortSideFit)]
+	heuristic: pack::Heuristic,
+
+	/// An external rectangle packer to lay them out instead, given `width height` arguments per sprite and
+	///   printing an `x y` line per sprite.
+	#[clap(long, conflicts_with = "heuristic")]
+	rectpacker: Option<String>,
+
+	/// What to write the sprites' rectangles as: `flars` (atlasDefs.toml), `texturepacker-hash` or
+	///   `texturepacker-array` (atlas.json), `libgdx` (atlas.atlas), `godot` (a `<gid>.tres` per sprite) or
+	///   `tiled` (atlas.tsx, an image collection).
+	#[clap(long, default_value_t)]
+	format: atlas::Format,
+
+	/// The atlas PNG's path as the definitions should refer to it; Godot wants a `res://` one.
+	#[clap(long, default_value = "atlas.png")]
+	image: String,
+
+	/// Transparent pixels between sprites.
+	#[clap(long, default_value_t = 0)]
+	padding: usize,
+
+	/// How many times every sprite's edge pixels repeat around it, so that filtering doesn't bleed its
+	///   neighbours in.
+	#[clap(long, default_value_t = 0)]
+	extrude: usize,
+
+	/// Rounds every page's width and height up to powers of two.
+	#[clap(long)]
+	powerOfTwo: bool,
+
+	/// The biggest a page can be, `WIDTHxHEIGHT`, with the sprites that don't fit spilling onto more:
+	///   `atlas-0.png`, `atlas-1.png`…, each named in the definitions [rounded down to powers of two with
+	///   --power-of-two]
+	#[clap(long, conflicts_with = "rectpacker")]
+	maxPageSize: Option<DimensionPair>,
+
+	/// A palette, as written by `1_-_pal_into_pngPAL`, to remap every PNG into, so that PNGs with
+	///   different palettes can share an atlas.
+	#[clap(long, requires = "pal-inverse")]
+	pngPal: Option<PathBuf>,
+
+	/// The inverse of --png-pal, as written by `2_-_pngPAL_into_palInverse`, or a palInverse cache directory to
+	///   find or make it in.
+	#[clap(long, requires = "png-pal")]
+	palInverse: Option<PathBuf>,
+
+	/// The metric of the palInverse made in a --pal-inverse cache directory: `rgb`, `redmean`, `cie76`,
+	///   `ciede2000` or `oklab`.
+	#[clap(long, default_value_t, requires = "pal-inverse")]
+	metric: Metric,
+
+	/// Writes truecolor RGBA pages, every sprite in its own PNG's colors, instead of indexed ones.
+	#[clap(long, conflicts_with = "png-pal")]
+	rgba: bool,
+}
+#[derive(Debug)]
+struct DimensionPair([usize; 2]);
+impl FromStr for DimensionPair {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let Some((width, height)) = s.split_once('x') else {
+			return Err(format!("{s:?} isn't `WIDTHxHEIGHT`"));
+		};
+		let dimension = |s: &str| match s.parse::<NonZeroUsize>() {
+			Ok(dimension) => Ok(dimension.get()),
+			Err(err) => Err(format!("{s:?}: {err}")),
+		};
+		Ok(Self([dimension(width)?, dimension(height)?]))
 	}
-	#[derive(Debug)]
-	struct DimensionPair([usize; 2]);
-	impl FromStr for DimensionPair {
-		type Err = ParseIntError;
-		fn from_str(s: &str) -> Result<Self, Self::Err> {
-			let mut pair = [default(); 2];
-			for (i, s) in s.split('x').enumerate() {
-				pair[i] = s.parse()?;
-			}
-			Ok(Self(pair))
-		}
+}
+
+fn rectpackerPoints(rectpackerPath: &str, dimensions: &[Vec2]) -> Vec<Vec2> {
+	let args = Vec::from_iter(dimensions.iter().flatten().map(usize::to_string));
+	Vec::from_iter(
+		str::from_utf8(
+			&Command::new(rectpackerPath)
+				.args(args)
+				.output()
+				.unwrap_or_else(|err| panic!("{rectpackerPath:?}: {err}"))
+				.stdout,
+		)
+		.unwrap()
+		.lines()
+		.map(|line| {
+			array::from_fn({
+				let mut coords = line.split(' ');
+				move |_| usize::from_str(coords.next().unwrap()).unwrap()
+			})
+		}),
+	)
+}
+
+// the index every color of `srcPAL` has in `palette`, whose inverse `palInverse` is, as
+//   `3_-_pngPAL-palInverse-rgbaPNG_into_indexedPNG` looks them up; none at all if it is that palette,
+//   whose colors the palInverse may not all give back (duplicates, black)
+fn remapping(srcPAL: &[u8], palette: &Palette, palInverse: &[u8; RGBCUBE_VOLUME]) -> [u8; 256] {
+	if *srcPAL == palette.pngPAL() {
+		return array::from_fn(|i| i as _);
 	}
-	let Args { firstgid, tileDimensionPairs, rectpackerPath } = Args::parse();
-	let (pngPAL, srcImages, srcRects) = {
-		let (mut pngPAL, mut srcImages) = (None, Vec::with_capacity(tileDimensionPairs.len()));
-		{
-			type Filesize = usize;
-			const FILESIZE_LINE: &'static str = formatcp!("{}\r\n", Filesize::MAX);
-			let (mut i, stdin, filesizeLine) =
-				(0, &mut io::stdin().lock(), &mut String::with_capacity(FILESIZE_LINE.len()));
-			while i < tileDimensionPairs.len() && {
-				filesizeLine.clear();
-				stdin.read_line(filesizeLine).unwrap() != 0
-			} {
-				let png = &mut png::Decoder::new(
-					stdin.take(Filesize::from_str(filesizeLine.trim_end_matches(['\n', '\r'])).unwrap() as _),
-				)
-				.read_info()
-				.unwrap();
-				let pngInfo = png.info();
-				assert_eq!(pngInfo.color_type, ColorType::Indexed);
-				{
-					let cow = pngInfo.palette.as_ref().unwrap();
-					if let Some(pngPAL) = &pngPAL {
-						assert_eq!(pngPAL, cow.as_ref());
-					} else {
-						pngPAL = Some(cow.clone().into_owned());
-					}
+	let black = palette.black();
+	array::from_fn(|i| match srcPAL.get(i * RGB_SIZE..(i + 1) * RGB_SIZE) {
+		_ if i == FULLY_TRANSPARENT as usize => palette.transparent,
+		Some(&[red, green, blue]) => match palInverse[cubeIndex([red, green, blue])] {
+			index if index == palette.transparent => black,
+			index => index,
+		},
+		_ => palette.transparent,
+	})
+}
+
+fn main() {
+	run(Args::parse(), io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(
+	Args {
+		firstgid,
+		tileDimensionPairs,
+		heuristic,
+		rectpacker,
+		format,
+		image,
+		padding,
+		extrude,
+		powerOfTwo,
+		maxPageSize,
+		pngPal,
+		palInverse,
+		metric,
+		rgba,
+	}: Args,
+	stdin: impl Read,
+	stdout: impl Write,
+) {
+	// the distinct palettes, and which of them every PNG is in
+	let (pngPALs, srcPALIndices, srcImages, srcRects) = {
+		let (mut pngPALs, mut srcPALIndices, mut srcImages) =
+			(Vec::<Vec<u8>>::new(), Vec::new(), Vec::with_capacity(tileDimensionPairs.len()));
+		for entry in tar::Entries::read(stdin).unwrap().all(tar::PNG).take(tileDimensionPairs.len()) {
+			let png = &mut png::Decoder::new(&*entry.data).read_info().unwrap();
+			let pngInfo = png.info();
+			assert_eq!(pngInfo.color_type, ColorType::Indexed);
+			let palette = pngInfo.palette.as_deref().unwrap();
+			srcPALIndices.push(pngPALs.iter().position(|pngPAL| pngPAL == palette).unwrap_or_else(|| {
+				pngPALs.push(palette.to_owned());
+				pngPALs.len() - 1
+			}));
+			srcImages.push(Image::fromPNG(png));
+		}
+		if let (Some(pngPal), Some(palInverse)) = (pngPal, palInverse) {
+			let pngPAL = fs::read(&pngPal).unwrap_or_else(|err| panic!("{pngPal:?}: {err}"));
+			assert_eq!(pngPAL.len(), PAL_LEN);
+			let palette = &Palette::fromPngPAL(&pngPAL);
+			let palInverse = PalInverse::fromFileOrCache(&palInverse, palette, metric)
+				.unwrap_or_else(|err| panic!("{palInverse:?}: {err}"))
+				.cube;
+			let remappings =
+				Vec::from_iter(pngPALs.iter().map(|srcPAL| remapping(srcPAL, palette, &palInverse)));
+			for (srcImage, &srcPALIndex) in iter::zip(&mut srcImages, &srcPALIndices) {
+				for pixel in &mut *srcImage.data {
+					*pixel = remappings[srcPALIndex][*pixel as usize];
 				}
-				srcImages.push(Image::fromPNG(png));
-				i += 1;
 			}
-			assert_eq!(filesizeLine.capacity(), FILESIZE_LINE.len());
+			(pngPALs, srcPALIndices) = (vec![pngPAL], vec![0; srcImages.len()]);
+		} else if !rgba {
+			assert!(
+				pngPALs.len() <= 1,
+				"the PNGs come in {} different palettes; remap them into one with --png-pal and --pal-inverse, \
+				 or write --rgba pages",
+				pngPALs.len()
+			);
 		}
+		assert!(pngPALs.len() <= 256, "more palettes than an RGBA page's palette map can tell apart");
 		let (mut gid, mut srcRects, backgroundTileCenter) =
-			(firstgid, Vec::<(usize, usize, Rectangle, Vec2)>::new(), tileDimensionPairs[0].0.div(2));
+			(firstgid, Vec::<(usize, Vec2, atlas::Sprite)>::new(), tileDimensionPairs[0].0.div(2));
 		for (i, (srcImage, &DimensionPair(tileDimensions))) in
 			iter::zip(srcImages.iter(), tileDimensionPairs[tileDimensionPairs.len() - srcImages.len()..].iter())
 				.enumerate()
@@ -96,11 +229,17 @@ fn main() {
 			for _ in 0..rows {
 				for _ in 0..columns {
 					if let Some(boundingRectangle) = srcImage.boundingRectangle([point, tileDimensions]) {
+						let [srcPoint, dimensions] = boundingRectangle;
 						srcRects.push((
-							gid,
 							i,
-							boundingRectangle,
-							offset.add(point.add(boundingRectangle[POINT].neg())),
+							srcPoint,
+							atlas::Sprite {
+								gid,
+								rectangle: [[0; 2], dimensions],
+								trimmedPoint: srcPoint.add(point.neg()),
+								tileDimensions,
+								offset: offset.add(point.add(srcPoint.neg())).map(|coord| coord as _),
+							},
 						));
 					}
 					gid += 1;
@@ -110,68 +249,128 @@ fn main() {
 			}
 		}
 		eprintln!("srcRects.len() == {}", srcRects.len());
-		(pngPAL.unwrap(), &mut srcImages.into_boxed_slice(), &mut srcRects.into_boxed_slice())
+		(pngPALs, srcPALIndices, &mut srcImages.into_boxed_slice(), srcRects)
+	};
+	// sprites with the same trimmed pixels (rarity variants, repeated shadows) are packed once, their gids
+	//   all referring to that one region; with --rgba the same indices in another palette aren't the same
+	//   pixels
+	let (uniques, uniqueOf) = {
+		let (seen, mut uniques, mut uniqueOf) = (
+			&mut HashMap::<(Vec2, usize, Vec<u8>), usize>::new(),
+			Vec::new(),
+			Vec::with_capacity(srcRects.len()),
+		);
+		for (i, &(srcIndex, [x, y], ref sprite)) in srcRects.iter().enumerate() {
+			let (srcImage, dimensions @ [width, height]) = (&srcImages[srcIndex], sprite.rectangle[DIMENSIONS]);
+			let pixels = Vec::from_iter(
+				(y..y + height).flat_map(|row| &srcImage.data[row * srcImage.width + x..][..width]).copied(),
+			);
+			uniqueOf.push(*seen.entry((dimensions, srcPALIndices[srcIndex], pixels)).or_insert_with(|| {
+				uniques.push(i);
+				uniques.len() - 1
+			}));
+		}
+		(uniques, uniqueOf)
+	};
+	let saved = iter::zip(0.., &uniqueOf)
+		.filter(|&(i, &unique)| uniques[unique] != i)
+		.map(|(i, _)| srcRects[i].2.rectangle[DIMENSIONS].iter().product::<usize>())
+		.sum::<usize>();
+	eprintln!("{} unique of {} sprites; {saved} bytes of pixels saved", uniques.len(), srcRects.len());
+	// what every sprite takes up: itself, its extruded edges around it, and the padding to the next one
+	let margin = 2 * extrude + padding;
+	let reservedDimensions =
+		&Vec::from_iter(uniques.iter().map(|&i| srcRects[i].2.rectangle[DIMENSIONS].add([margin; 2])));
+	let pagePoints = match (rectpacker, maxPageSize) {
+		(Some(rectpackerPath), _) => {
+			vec![(rectpackerPoints(&rectpackerPath, reservedDimensions), Vec::from_iter(0..uniques.len()))]
+		}
+		(None, None) => {
+			vec![(pack::pack(reservedDimensions, heuristic).points, Vec::from_iter(0..uniques.len()))]
+		}
+		(None, Some(DimensionPair(maxDimensions))) => Vec::from_iter(
+			pack::packPages(
+				reservedDimensions,
+				heuristic,
+				if powerOfTwo { maxDimensions.map(|dimension| 1 << dimension.ilog2()) } else { maxDimensions },
+			)
+			.into_iter()
+			.map(|(packing, page)| (packing.points, page)),
+		),
 	};
-	let mut destPoints = Vec::with_capacity(srcRects.len());
-	let destImage = &mut {
-		let (mut destImageDimensions, srcRectDimensions) =
-			([0_usize; 2], &mut Vec::with_capacity(srcRects.len() * Vec2::LEN));
-		for (_, _, srcRect, _) in srcRects.iter() {
-			for dimension in srcRect[DIMENSIONS] {
-				srcRectDimensions.push(dimension.to_string());
+	let numPages = pagePoints.len();
+	let mut stdout = tar::Writer::new(BufWriter::new(stdout));
+	let pages = Vec::from_iter(pagePoints.into_iter().enumerate().map(|(pageIndex, (points, page))| {
+		let mut dimensions = [0_usize; 2];
+		for (&point, &unique) in iter::zip(&points, &page) {
+			for (j, dimension) in point
+				.add(srcRects[uniques[unique]].2.rectangle[DIMENSIONS].add([2 * extrude; 2]))
+				.into_iter()
+				.enumerate()
+			{
+				dimensions[j].maxAssign(dimension);
 			}
 		}
-		for (i, line) in str::from_utf8(
-			&Command::new(&rectpackerPath)
-				.args(srcRectDimensions)
-				.output()
-				.unwrap_or_else(|err| panic!("{:?}: {err}", rectpackerPath))
-				.stdout,
-		)
-		.unwrap()
-		.lines()
-		.enumerate()
+		if powerOfTwo {
+			dimensions = dimensions.map(usize::next_power_of_two);
+		}
+		let destImage = &mut Image::fromWidthHeight(dimensions[WIDTH], dimensions[HEIGHT]);
+		// which palette every pixel of `destImage` is in, for the RGBA page
+		let palIndexImage = &mut Image::fromWidthHeight(dimensions[WIDTH], dimensions[HEIGHT]);
+		let uniquePoints = &mut HashMap::with_capacity(page.len());
+		for (point, unique) in iter::zip(points, page) {
+			let (srcIndex, srcPoint, ref sprite) = srcRects[uniques[unique]];
+			let rectangle = [point.add([extrude; 2]), sprite.rectangle[DIMENSIONS]];
+			destImage.blitPixelsRectangle(
+				rectangle[POINT],
+				rectangle[DIMENSIONS],
+				&srcImages[srcIndex],
+				srcPoint,
+			);
+			destImage.extrude(rectangle, extrude);
+			let [x, y] = point;
+			for row in y..y + rectangle[DIMENSIONS][HEIGHT] + 2 * extrude {
+				palIndexImage.data[row * dimensions[WIDTH] + x..][..rectangle[DIMENSIONS][WIDTH] + 2 * extrude]
+					.fill(srcPALIndices[srcIndex] as _);
+			}
+			uniquePoints.insert(unique, rectangle[POINT]);
+		}
+		let sprites =
+			Vec::from_iter(iter::zip(&srcRects, &uniqueOf).filter_map(|((_, _, sprite), unique)| {
+				let mut sprite = sprite.clone();
+				sprite.rectangle[POINT] = *uniquePoints.get(unique)?;
+				Some(sprite)
+			}));
+		let atlasPNG = &mut Vec::new();
 		{
-			let destPoint: Vec2 = array::from_fn({
-				let mut coords = line.split(' ');
-				move |_| usize::from_str(coords.next().unwrap()).unwrap()
-			});
-			for (j, dimension) in destPoint.add(srcRects[i].2[DIMENSIONS]).into_iter().enumerate() {
-				destImageDimensions[j].maxAssign(dimension);
+			let mut png = png::Encoder::new(&mut *atlasPNG, destImage.width as _, destImage.height as _);
+			if rgba {
+				png.set_color(ColorType::Rgba);
+				let rgbaData = Vec::from_iter(iter::zip(&*destImage.data, &*palIndexImage.data).flat_map(
+					|(&pixel, &palIndex)| match pixel {
+						FULLY_TRANSPARENT => [0; RGBA_SIZE],
+						_ => {
+							let [red, green, blue] = <[u8; RGB_SIZE]>::try_from(
+								&pngPALs[palIndex as usize][pixel as usize * RGB_SIZE..][..RGB_SIZE],
+							)
+							.unwrap();
+							[red, green, blue, u8::MAX]
+						}
+					},
+				));
+				png.write_header().unwrap().write_image_data(&rgbaData).unwrap();
+			} else {
+				png.set_color(ColorType::Indexed);
+				png.set_palette(&pngPALs[0]);
+				png.set_trns(Palette::fromPngPAL(&pngPALs[0]).trns());
+				png.write_header().unwrap().write_image_data(&destImage.data).unwrap();
 			}
-			destPoints.push(destPoint);
 		}
-		Image::fromWidthHeight(destImageDimensions[WIDTH], destImageDimensions[HEIGHT])
-	};
-	type AtlasDefs = Vec<(usize, usize, usize, usize, usize, isize, isize)>;
-	let mut atlasDefs = AtlasDefs::with_capacity(srcRects.len());
-	for (&(gid, i, [srcPoint, rectDimensions], offset), destPoint) in
-		iter::zip(srcRects.into_iter(), destPoints.into_iter())
-	{
-		atlasDefs.push((
-			gid,
-			destPoint[X],
-			destPoint[Y],
-			rectDimensions[WIDTH],
-			rectDimensions[HEIGHT],
-			offset[X] as _,
-			offset[Y] as _,
-		));
-		destImage.blitPixelsRectangle(destPoint, rectDimensions, &srcImages[i], srcPoint);
-	}
-	#[derive(Serialize)]
-	struct AtlasDefsTOML {
-		#[serde(rename = "_.png")]
-		pngFilePath: AtlasDefs,
+		stdout.append(&atlas::pageImage("atlas.png", pageIndex, numPages), tar::PNG, atlasPNG).unwrap();
+		atlas::Page { image: atlas::pageImage(&image, pageIndex, numPages), dimensions, sprites }
+	}));
+	for (name, mediaType, data) in format.entries(&pages, firstgid) {
+		stdout.append(&name, mediaType, &data).unwrap();
 	}
-	let (stdout, tomlString) = (
-		&mut BufWriter::new(stdoutRaw()),
-		toml_toStringPretty(&AtlasDefsTOML { pngFilePath: atlasDefs }).unwrap_or_else(|err| panic!("{err}")),
-	);
-	write!(stdout, "{}\n{tomlString}", tomlString.len()).unwrap();
-	let mut png = png::Encoder::new(stdout, destImage.width as _, destImage.height as _);
-	png.set_color(ColorType::Indexed);
-	png.set_palette(pngPAL);
-	png.set_trns(&[0][..]);
-	png.write_header().unwrap().write_image_data(&destImage.data).unwrap();
+	stdout.finish().unwrap();
 }
@@ -9,21 +9,24 @@ use {
 		stdoutRaw, unlet, Image, TilesIterator, UsizeExt, X, Y,
 	},
 	png::ColorType,
-	std::io::{self, BufWriter},
+	std::io::{self, BufWriter, Read, Write},
 };
 
+#[derive(Parser)]
+pub struct Args {
+	#[clap(long, default_value_t = 0)]
+	tileDimensionsBitshiftLeftBy: usize,
+}
+
 fn main() {
-	#[derive(Parser)]
-	struct Args {
-		#[clap(long, default_value_t = 0)]
-		tileDimensionsBitshiftLeftBy: usize,
-	}
-	let Args { tileDimensionsBitshiftLeftBy } = Args::parse();
+	run(Args::parse(), io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(Args { tileDimensionsBitshiftLeftBy }: Args, stdin: impl Read, stdout: impl Write) {
 	let [tilewidth, floorRoofTileheight] =
 		[TILEWIDTH, FLOOR_ROOF_TILEHEIGHT].map(|dimension| dimension << tileDimensionsBitshiftLeftBy);
 	eprintln!("{:?}", [tilewidth, floorRoofTileheight],);
 
-	let stdin = &mut io::stdin().lock();
 	let png = &mut png::Decoder::new(stdin).read_info().unwrap();
 	let (srcImage, pngPAL) = (&mut Image::fromPNG(png), png.info().palette.as_ref().unwrap().as_ref());
 	let destImage = &mut Image::fromWidthHeight(
@@ -68,8 +71,7 @@ fn main() {
 			}
 		}
 	}
-	let mut png =
-		png::Encoder::new(BufWriter::new(stdoutRaw()), destImage.width as _, destImage.height as _);
+	let mut png = png::Encoder::new(BufWriter::new(stdout), destImage.width as _, destImage.height as _);
 	png.set_color(ColorType::Indexed);
 	png.set_palette(pngPAL);
 	png.set_trns(&[0][..]);
@@ -0,0 +1,193 @@
+#![warn(clippy::pedantic, elided_lifetimes_in_paths, explicit_outlives_requirements)]
+#![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
+
+// the stages the README's `find | while read` loops chain, run in-process
+#[path = "1_-_ds1_into_ds1TOML.rs"]
+#[allow(dead_code, unused_attributes)]
+mod ds1ToTOML;
+#[path = "2_-_pngPAL-dt1_into_dt1TOML-blockPNG.rs"]
+#[allow(dead_code, unused_attributes)]
+mod dt1Extract;
+#[path = "3_-_dt1TOML-blockPNG_into_tilePNG.rs"]
+#[allow(dead_code, unused_attributes)]
+mod dt1Tiles;
+
+use {
+	clap::Parser,
+	d2sw_tiled_project::{stdoutRaw, tar, Layout, PAL_LEN},
+	std::{
+		cell::RefCell,
+		ffi::OsStr,
+		fs,
+		io::{self, BufWriter, Read, Write},
+		num::NonZeroUsize,
+		panic::{self, AssertUnwindSafe},
+		path::{Path, PathBuf},
+		process::ExitCode,
+		sync::atomic::{AtomicUsize, Ordering},
+		thread,
+	},
+};
+
+fn collectFiles(dir: &Path, path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+	if path.is_dir() {
+		let mut entries = Vec::from_iter(fs::read_dir(path)?.map(|entry| entry.map(|entry| entry.path())));
+		entries.sort_by(|a, b| match (a, b) {
+			(Ok(a), Ok(b)) => a.cmp(b),
+			_ => core::cmp::Ordering::Equal,
+		});
+		for entry in entries {
+			collectFiles(dir, &entry?, files)?;
+		}
+	} else if path
+		.extension()
+		.map_or(false, |extension| ["ds1", "dt1"].iter().any(|&ext| extension.eq_ignore_ascii_case(ext)))
+	{
+		files.push(path.strip_prefix(dir).unwrap().to_owned());
+	}
+	Ok(())
+}
+
+// `Cave` + `.dt1.toml`; `Path::with_extension` would eat whatever dot the stem itself has
+fn withSuffix(stem: &Path, suffix: &str) -> PathBuf {
+	let mut path = stem.as_os_str().to_owned();
+	path.push(suffix);
+	path.into()
+}
+
+// nothing is written for a file that fails, so a rerun's output has no half-converted leftovers
+fn convertDT1(
+	pngPAL: &[u8],
+	dt1: &[u8],
+	tilesArgs: dt1Tiles::Args,
+	outputStem: &Path,
+) -> Result<(), String> {
+	let stream = &mut Vec::new();
+	// the block image doesn't outlive the conversion, columns are the quickest to lay out
+	dt1Extract::run(dt1Extract::Args { layout: Layout::Columns }, pngPAL.chain(dt1), &mut *stream)
+		.map_err(|err| err.to_string())?;
+	let entries = &tar::Entries::read(&stream[..]).map_err(|err| err.to_string())?;
+	let tilePNG = &mut Vec::new();
+	dt1Tiles::run(tilesArgs, &stream[..], &mut *tilePNG).map_err(|err| err.to_string())?;
+	write(
+		&withSuffix(outputStem, ".dt1.toml"),
+		&entries.only(tar::TOML).map_err(|err| err.to_string())?.data,
+	)?;
+	write(&withSuffix(outputStem, ".tile.png"), tilePNG)
+}
+
+fn convertDS1(ds1: &[u8], toTOMLArgs: ds1ToTOML::Args, outputStem: &Path) -> Result<(), String> {
+	let toml = &mut Vec::new();
+	ds1ToTOML::run(toTOMLArgs, ds1, &mut *toml).map_err(|err| err.to_string())?;
+	write(&withSuffix(outputStem, ".ds1.toml"), toml)
+}
+
+fn write(path: &Path, bytes: &[u8]) -> Result<(), String> {
+	fs::write(path, bytes).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+thread_local! {
+	// what the panic hook saw, for the file that panicked rather than for stderr
+	static PANIC_MESSAGE: RefCell<Option<String>> = RefCell::new(None);
+}
+
+#[derive(Parser)]
+pub struct Args {
+	/// The act palette as written by `1_-_pal_into_pngPAL`.
+	#[clap(long)]
+	pngPal: PathBuf,
+
+	/// Where `CAVES/Cave.dt1` under `dir` becomes `CAVES/Cave.dt1.toml` and `CAVES/Cave.tile.png`,
+	///   and `CAVES/Cave.ds1` becomes `CAVES/Cave.ds1.toml`.
+	#[clap(long)]
+	outputDir: PathBuf,
+
+	/// How many files are converted at once [default: one per core]
+	#[clap(long)]
+	jobs: Option<NonZeroUsize>,
+
+	#[clap(flatten)]
+	tilesArgs: dt1Tiles::Args,
+
+	#[clap(flatten)]
+	toTOMLArgs: ds1ToTOML::Args,
+
+	/// An act's tiles directory, `data/global/tiles/ACT1`, looked through recursively.
+	dir: PathBuf,
+}
+
+fn main() -> ExitCode {
+	run(Args::parse(), stdoutRaw())
+}
+
+pub fn run(
+	Args { pngPal, outputDir, jobs, tilesArgs, toTOMLArgs, dir }: Args,
+	stdout: impl Write,
+) -> ExitCode {
+	let pngPAL = &fs::read(&pngPal).unwrap_or_else(|err| panic!("{pngPal:?}: {err}"));
+	assert_eq!(pngPAL.len(), PAL_LEN);
+	let files = &mut Vec::new();
+	collectFiles(&dir, &dir, files).unwrap_or_else(|err| panic!("{dir:?}: {err}"));
+
+	let defaultHook = panic::take_hook();
+	panic::set_hook(Box::new(|info| {
+		let location = info.location().map_or_else(String::new, |location| format!(" at {location}"));
+		let message = info.payload_as_str().unwrap_or("Box<dyn Any>");
+		PANIC_MESSAGE
+			.with(|panicMessage| *panicMessage.borrow_mut() = Some(format!("panicked{location}: {message}")));
+	}));
+	let (next, jobs) =
+		(&AtomicUsize::new(0), jobs.unwrap_or_else(|| thread::available_parallelism().unwrap()));
+	let mut outcomes = thread::scope(|scope| {
+		let workers = Vec::from_iter((0..jobs.get()).map(|_| {
+			scope.spawn(|| {
+				let mut outcomes = Vec::new();
+				loop {
+					let i = next.fetch_add(1, Ordering::Relaxed);
+					let Some(file) = files.get(i) else {
+						break outcomes;
+					};
+					let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+						let path = &dir.join(file);
+						let bytes = fs::read(path).map_err(|err| format!("{}: {err}", path.display()))?;
+						let outputStem = &outputDir.join(file.with_extension(""));
+						let outputSubdir = outputStem.parent().unwrap();
+						fs::create_dir_all(outputSubdir)
+							.map_err(|err| format!("{}: {err}", outputSubdir.display()))?;
+						if file.extension().is_some_and(|extension| extension.eq_ignore_ascii_case(OsStr::new("ds1")))
+						{
+							convertDS1(&bytes, toTOMLArgs.clone(), outputStem)
+						} else {
+							convertDT1(pngPAL, &bytes, tilesArgs.clone(), outputStem)
+						}
+					}))
+					.unwrap_or_else(|_| Err(PANIC_MESSAGE.with(|panicMessage| panicMessage.take().unwrap())));
+					outcomes.push((i, outcome));
+				}
+			})
+		}));
+		Vec::from_iter(workers.into_iter().flat_map(|worker| worker.join().unwrap()))
+	});
+	panic::set_hook(defaultHook);
+
+	outcomes.sort_unstable_by_key(|&(i, _)| i);
+	let (stdout, mut failed) = (&mut BufWriter::new(stdout), 0);
+	for (i, outcome) in outcomes {
+		let file = files[i].display();
+		match outcome {
+			Ok(()) => writeln!(stdout, "OK\t{file}"),
+			Err(err) => {
+				failed += 1;
+				writeln!(stdout, "ERR\t{file}: {err}")
+			}
+		}
+		.unwrap();
+	}
+	stdout.flush().unwrap();
+	eprintln!("{} files: {} converted, {failed} failed", files.len(), files.len() - failed);
+	if failed == 0 {
+		ExitCode::SUCCESS
+	} else {
+		ExitCode::FAILURE
+	}
+}
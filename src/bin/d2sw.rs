@@ -0,0 +1,650 @@
+#![warn(clippy::pedantic, elided_lifetimes_in_paths, explicit_outlives_requirements)]
+#![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
+
+// every other binary, each its own module here, so that `d2sw` and the old pipelines run the same code;
+//   their crate-level `allow`s are already this crate's
+macro_rules! binaries {
+	($($module:ident = $path:literal,)*) => {$(
+		#[path = $path]
+		#[allow(dead_code, unused_attributes)]
+		mod $module;
+	)*};
+}
+binaries! {
+	ds1ToTOML = "1_-_ds1_into_ds1TOML.rs",
+	ds1FromTOML = "2_-_ds1TOML_into_ds1.rs",
+	ds1RuledFloor = "2_-_ds1TOML_into_ds1RuledFloorTOML.rs",
+	ds1SetFloor = "2_-_ds1TOML_into_ds1SettenFloorTOML.rs",
+	ds1HangulLayers = "2_-_ds1TOML_into_hangulLayersTXT.rs",
+	ds1ToTMX = "2_-_ds1TOML_into_tmx.rs",
+	ds1FromTMX = "3_-_tmx_into_ds1TOML.rs",
+	ds1Render = "ds1_render.rs",
+	dt1Extract = "2_-_pngPAL-dt1_into_dt1TOML-blockPNG.rs",
+	dt1Tiles = "3_-_dt1TOML-blockPNG_into_tilePNG.rs",
+	dt1Pack = "4_-_dt1TOML-tilePNG_into_dt1.rs",
+	dt1Roguelike = "4_-_dt1TOML-tilePNG_into_roguelikeTilePNG.rs",
+	dt1RoguelikeNoisySquare = "4_-_dt1TOML-tilePNG_into_roguelikeNoisySquareTilePNG.rs",
+	dt1Tiled = "4_-_dt1TOML-tilePNG_into_tiledPNG.rs",
+	dc6Extract = "2_-_pngPAL-dc6_into_dc6TOML-framesPNG.rs",
+	dc6Pack = "3_-_dc6TOML-framesPNG_into_dc6.rs",
+	dccSpriteSheet = "2_-_pngPAL-dcc_into_spriteSheetPNG.rs",
+	paletteToPngPAL = "1_-_pal_into_pngPAL.rs",
+	paletteInverse = "2_-_pngPAL_into_palInverse.rs",
+	paletteIndex = "3_-_pngPAL-palInverse-rgbaPNG_into_indexedPNG.rs",
+	paletteTables = "3_-_pngPAL-palInverse_into_tablesTOML-tablesDAT.rs",
+	tileNoisySquare = "4_-_floorRoofTilePNG_into_noisySquareTilePNG.rs",
+	tileXStaggered = "4_-_floorRoofTilePNG_into_xStaggeredTilePNG.rs",
+	tileFloorRoof = "5_-_xStaggeredTilePNG_into_floorRoofTilePNG.rs",
+	atlasBuild = "5_-_tiledPNGs_into_flarsAtlasDefsTOML-atlasPNG.rs",
+	statsTileIDs = "ds1s_into_usedTileIDsFrequency.rs",
+	statsCellComponents = "ds1s_into_usedCellComponentsFrequency.rs",
+	statsPALIndices = "dt1s_into_usedPALIndicesFrequency.rs",
+	tarCat = "dubcat.rs",
+	tarSplit = "dubsplit.rs",
+	mpqExtract = "mpq_extract.rs",
+	verify = "verify.rs",
+	batch = "batch.rs",
+	pngFixWaifu2x = "waifu2xPNG-originalIndexedPNG_into_fixedWaifu2xPNG.rs",
+}
+
+use {
+	clap::{Parser, Subcommand},
+	d2sw_tiled_project::{
+		palette::{Metric, PalInverse},
+		stdoutRaw,
+	},
+	std::{
+		fs::{self, File},
+		io::{self, BufReader, Read, Write},
+		path::PathBuf,
+		process::{ExitCode, Termination},
+	},
+};
+
+#[derive(clap::Args)]
+struct Input {
+	/// Read this file instead of stdin.
+	#[clap(short, long)]
+	input: Option<PathBuf>,
+}
+
+impl Input {
+	fn open(self) -> Box<dyn Read> {
+		match self.input {
+			Some(path) => {
+				Box::new(BufReader::new(File::open(&path).unwrap_or_else(|err| panic!("{path:?}: {err}"))))
+			}
+			None => Box::new(io::stdin().lock()),
+		}
+	}
+}
+
+#[derive(clap::Args)]
+struct Output {
+	/// Write this file instead of stdout.
+	#[clap(short, long)]
+	output: Option<PathBuf>,
+}
+
+impl Output {
+	fn open(self) -> Box<dyn Write> {
+		match self.output {
+			Some(path) => Box::new(File::create(&path).unwrap_or_else(|err| panic!("{path:?}: {err}"))),
+			None => Box::new(stdoutRaw()),
+		}
+	}
+}
+
+#[derive(clap::Args)]
+struct Palette {
+	/// The act palette as `palette to-png-pal` writes it, instead of in front of the input.
+	#[clap(long)]
+	palette: Option<PathBuf>,
+}
+
+impl Palette {
+	fn before(self, input: Box<dyn Read>) -> Box<dyn Read> {
+		chained([self.palette], input)
+	}
+}
+
+#[derive(clap::Args)]
+struct PaletteAndInverse {
+	/// The act palette as `palette to-png-pal` writes it, instead of in front of the input.
+	#[clap(long, requires = "pal-inverse")]
+	palette: Option<PathBuf>,
+
+	/// Its inverse as `palette inverse` writes it, instead of in front of the input; or a palInverse cache
+	///   directory to find or make it in.
+	#[clap(long, requires = "palette")]
+	palInverse: Option<PathBuf>,
+}
+
+impl PaletteAndInverse {
+	// a cache directory's palInverse is the one by `metric`
+	fn before(self, metric: Metric, input: Box<dyn Read>) -> Box<dyn Read> {
+		match self {
+			Self { palette: Some(pngPal), palInverse: Some(cacheDir) } if cacheDir.is_dir() => {
+				let mut pngPAL = fs::read(&pngPal).unwrap_or_else(|err| panic!("{pngPal:?}: {err}"));
+				PalInverse::fromFileOrCache(
+					&cacheDir,
+					&d2sw_tiled_project::palette::Palette::fromPngPAL(&pngPAL),
+					metric,
+				)
+				.unwrap_or_else(|err| panic!("{cacheDir:?}: {err}"))
+				.write(&mut pngPAL)
+				.unwrap();
+				Box::new(io::Cursor::new(pngPAL).chain(input))
+			}
+			Self { palette, palInverse } => chained([palette, palInverse], input),
+		}
+	}
+}
+
+// the files the old pipelines `cat` in front of what they pipe in
+fn chained<const N: usize>(paths: [Option<PathBuf>; N], input: Box<dyn Read>) -> Box<dyn Read> {
+	paths.into_iter().flatten().rev().fold(input, |input, path| {
+		Box::new(
+			BufReader::new(File::open(&path).unwrap_or_else(|err| panic!("{path:?}: {err}"))).chain(input),
+		)
+	})
+}
+
+#[derive(clap::Args)]
+struct TarInput {
+	/// Files, directories or quoted globs to read as the entries of a tar stream, as `tar cat` takes them,
+	///   instead of the tar stream on stdin; repeatable.
+	#[clap(short, long = "input")]
+	inputs: Vec<String>,
+}
+
+impl TarInput {
+	fn open(self) -> Box<dyn Read> {
+		if self.inputs.is_empty() {
+			return Box::new(io::stdin().lock());
+		}
+		let buffer = &mut Vec::new();
+		tarCat::run(tarCat::Args { paths: self.inputs }, io::empty(), &mut *buffer);
+		Box::new(io::Cursor::new(std::mem::take(buffer)))
+	}
+}
+
+#[derive(clap::Args)]
+struct TarOutput {
+	#[clap(flatten)]
+	output: Output,
+
+	/// Write every entry of the tar stream under its own name in this directory instead.
+	#[clap(long, conflicts_with = "output")]
+	outputDir: Option<PathBuf>,
+}
+
+impl TarOutput {
+	fn with<T>(self, run: impl FnOnce(&mut dyn Write) -> T) -> T {
+		let Some(outputDir) = self.outputDir else {
+			return run(&mut self.output.open());
+		};
+		let buffer = &mut Vec::new();
+		let result = run(buffer);
+		tarSplit::run(
+			tarSplit::Args { directory: outputDir, entryPaths: Vec::new() },
+			&buffer[..],
+			io::sink(),
+		)
+		.unwrap_or_else(|err| panic!("{err}"));
+		result
+	}
+}
+
+/// One binary for the whole toolchain; every command reads stdin and writes stdout unless given paths,
+///   so the old pipelines still work with it.
+#[derive(Parser)]
+#[clap(name = "d2sw")]
+enum Command {
+	/// DS1 maps.
+	#[clap(subcommand)]
+	Ds1(Ds1),
+	/// DT1 tilesets.
+	#[clap(subcommand)]
+	Dt1(Dt1),
+	/// DC6 sprites.
+	#[clap(subcommand)]
+	Dc6(Dc6),
+	/// DCC sprites.
+	#[clap(subcommand)]
+	Dcc(Dcc),
+	/// Act palettes and their inverses.
+	#[clap(subcommand)]
+	Palette(PaletteCommand),
+	/// Floor/roof tile PNGs.
+	#[clap(subcommand)]
+	Tile(Tile),
+	/// Texture atlases.
+	#[clap(subcommand)]
+	Atlas(Atlas),
+	/// Usage counts over many DS1s or DT1s.
+	#[clap(subcommand)]
+	Stats(Stats),
+	/// The tar streams the other commands pass around.
+	#[clap(subcommand)]
+	Tar(Tar),
+	/// MPQ archives.
+	#[clap(subcommand)]
+	Mpq(Mpq),
+	/// Indexed PNGs.
+	#[clap(subcommand)]
+	Png(Png),
+	/// Check that DS1s and DT1s survive the round trip through their TOML forms.
+	Verify {
+		#[clap(flatten)]
+		args: verify::Args,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// Convert every DT1 and DS1 under a directory, on every core.
+	Batch {
+		#[clap(flatten)]
+		args: batch::Args,
+		#[clap(flatten)]
+		output: Output,
+	},
+}
+
+#[derive(Subcommand)]
+enum Ds1 {
+	/// DS1 into DS1 TOML.
+	ToToml {
+		#[clap(flatten)]
+		args: ds1ToTOML::Args,
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// DS1 TOML into DS1.
+	FromToml {
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// DS1 TOML into DS1 TOML with its floors ruled by a main index.
+	RuledFloor {
+		#[clap(flatten)]
+		args: ds1RuledFloor::Args,
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// DS1 TOML into DS1 TOML with every floor cell set to one main and sub index.
+	SetFloor {
+		#[clap(flatten)]
+		args: ds1SetFloor::Args,
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// DS1 TOML into its layers drawn in hangul.
+	HangulLayers {
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// DS1 TOML into TMX.
+	ToTmx {
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// TMX into DS1 TOML.
+	FromTmx {
+		#[clap(flatten)]
+		args: ds1FromTMX::Args,
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// DS1 TOML into a PNG of the map.
+	Render {
+		#[clap(flatten)]
+		args: ds1Render::Args,
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+}
+
+#[derive(Subcommand)]
+enum Dt1 {
+	/// DT1 into a tar of its DT1 TOML and block PNG.
+	Extract {
+		#[clap(flatten)]
+		args: dt1Extract::Args,
+		#[clap(flatten)]
+		palette: Palette,
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: TarOutput,
+	},
+	/// DT1 TOML and block PNG into a tile PNG.
+	Tiles {
+		#[clap(flatten)]
+		args: dt1Tiles::Args,
+		#[clap(flatten)]
+		input: TarInput,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// DT1 TOML and tile PNG into DT1.
+	Pack {
+		#[clap(flatten)]
+		input: TarInput,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// DT1 TOML and tile PNG into a roguelike tile PNG.
+	Roguelike {
+		#[clap(flatten)]
+		input: TarInput,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// DT1 TOML and tile PNG into a noisy-square roguelike tile PNG.
+	RoguelikeNoisySquare {
+		#[clap(flatten)]
+		input: TarInput,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// DT1 TOML and tile PNG into a PNG for Tiled, and optionally its tileset.
+	Tiled {
+		#[clap(flatten)]
+		args: dt1Tiled::Args,
+		#[clap(flatten)]
+		input: TarInput,
+		#[clap(flatten)]
+		output: Output,
+	},
+}
+
+#[derive(Subcommand)]
+enum Dc6 {
+	/// DC6 into a tar of its DC6 TOML and frames PNG.
+	Extract {
+		#[clap(flatten)]
+		palette: Palette,
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: TarOutput,
+	},
+	/// DC6 TOML and frames PNG into DC6.
+	Pack {
+		#[clap(flatten)]
+		input: TarInput,
+		#[clap(flatten)]
+		output: Output,
+	},
+}
+
+#[derive(Subcommand)]
+enum Dcc {
+	/// DCC into a sprite sheet PNG, one row per direction.
+	SpriteSheet {
+		#[clap(flatten)]
+		palette: Palette,
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+}
+
+#[derive(Subcommand)]
+enum PaletteCommand {
+	/// An act's pal.dat into a palette in PNG (RGB) order, or any palette file into another format.
+	ToPngPal {
+		#[clap(flatten)]
+		args: paletteToPngPAL::Args,
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// A palette into its inverse, the nearest index of every RGB color.
+	Inverse {
+		#[clap(flatten)]
+		args: paletteInverse::Args,
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// An RGBA PNG into an indexed PNG through a palette and its inverse.
+	Index {
+		#[clap(flatten)]
+		args: paletteIndex::Args,
+		#[clap(flatten)]
+		palette: PaletteAndInverse,
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// A palette and its inverse into light-level, tint and grey lookup tables.
+	Tables {
+		#[clap(flatten)]
+		args: paletteTables::Args,
+		#[clap(flatten)]
+		palette: PaletteAndInverse,
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: TarOutput,
+	},
+}
+
+#[derive(Subcommand)]
+enum Tile {
+	/// Floor/roof tile PNG into a noisy-square tile PNG.
+	NoisySquare {
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// Floor/roof tile PNG into an x-staggered tile PNG.
+	XStaggered {
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// X-staggered tile PNG back into a floor/roof tile PNG.
+	FloorRoof {
+		#[clap(flatten)]
+		args: tileFloorRoof::Args,
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+}
+
+#[derive(Subcommand)]
+enum Atlas {
+	/// Tiled PNGs into a tar of flars atlas definitions and the atlas PNG.
+	Build {
+		#[clap(flatten)]
+		args: atlasBuild::Args,
+		#[clap(flatten)]
+		input: TarInput,
+		#[clap(flatten)]
+		output: TarOutput,
+	},
+}
+
+#[derive(Subcommand)]
+enum Stats {
+	/// How often each tile ID is used by the DS1s in a tar stream.
+	TileIds {
+		#[clap(flatten)]
+		args: statsTileIDs::Args,
+		#[clap(flatten)]
+		input: TarInput,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// How often each value of a cell component is used by the DS1s in a tar stream.
+	CellComponents {
+		#[clap(flatten)]
+		args: statsCellComponents::Args,
+		#[clap(flatten)]
+		input: TarInput,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// How often each palette index is used by the DT1s in a tar stream.
+	PalIndices {
+		#[clap(flatten)]
+		input: TarInput,
+		#[clap(flatten)]
+		output: Output,
+	},
+}
+
+#[derive(Subcommand)]
+enum Tar {
+	/// Files into a tar stream.
+	Cat {
+		#[clap(flatten)]
+		args: tarCat::Args,
+		#[clap(flatten)]
+		output: Output,
+	},
+	/// A tar stream into files.
+	Split {
+		#[clap(flatten)]
+		args: tarSplit::Args,
+		#[clap(flatten)]
+		input: Input,
+		#[clap(flatten)]
+		output: Output,
+	},
+}
+
+#[derive(Subcommand)]
+enum Mpq {
+	/// Files out of an MPQ archive.
+	Extract {
+		#[clap(flatten)]
+		args: mpqExtract::Args,
+		#[clap(flatten)]
+		output: Output,
+	},
+}
+
+#[derive(Subcommand)]
+enum Png {
+	/// A waifu2x-upscaled PNG and its indexed original into an indexed upscaled PNG.
+	FixWaifu2x {
+		#[clap(flatten)]
+		input: TarInput,
+		#[clap(flatten)]
+		output: Output,
+	},
+}
+
+fn main() -> ExitCode {
+	match Command::parse() {
+		Command::Ds1(command) => match command {
+			Ds1::ToToml { args, input, output } => ds1ToTOML::run(args, input.open(), output.open()).report(),
+			Ds1::FromToml { input, output } => ds1FromTOML::run(input.open(), output.open()).report(),
+			Ds1::RuledFloor { args, input, output } => {
+				ds1RuledFloor::run(args, input.open(), output.open()).report()
+			}
+			Ds1::SetFloor { args, input, output } => {
+				ds1SetFloor::run(args, input.open(), output.open()).report()
+			}
+			Ds1::HangulLayers { input, output } => ds1HangulLayers::run(input.open(), output.open()).report(),
+			Ds1::ToTmx { input, output } => ds1ToTMX::run(input.open(), output.open()).report(),
+			Ds1::FromTmx { args, input, output } => ds1FromTMX::run(args, input.open(), output.open()).report(),
+			Ds1::Render { args, input, output } => ds1Render::run(args, input.open(), output.open()).report(),
+		},
+		Command::Dt1(command) => match command {
+			Dt1::Extract { args, palette, input, output } => {
+				output.with(|stdout| dt1Extract::run(args, palette.before(input.open()), stdout)).report()
+			}
+			Dt1::Tiles { args, input, output } => dt1Tiles::run(args, input.open(), output.open()).report(),
+			Dt1::Pack { input, output } => dt1Pack::run(input.open(), output.open()).report(),
+			Dt1::Roguelike { input, output } => dt1Roguelike::run(input.open(), output.open()).report(),
+			Dt1::RoguelikeNoisySquare { input, output } => {
+				dt1RoguelikeNoisySquare::run(input.open(), output.open()).report()
+			}
+			Dt1::Tiled { args, input, output } => dt1Tiled::run(args, input.open(), output.open()).report(),
+		},
+		Command::Dc6(command) => match command {
+			Dc6::Extract { palette, input, output } => {
+				output.with(|stdout| dc6Extract::run(palette.before(input.open()), stdout)).report()
+			}
+			Dc6::Pack { input, output } => dc6Pack::run(input.open(), output.open()).report(),
+		},
+		Command::Dcc(Dcc::SpriteSheet { palette, input, output }) => {
+			dccSpriteSheet::run(palette.before(input.open()), output.open()).report()
+		}
+		Command::Palette(command) => match command {
+			PaletteCommand::ToPngPal { args, input, output } => {
+				paletteToPngPAL::run(args, input.open(), output.open()).report()
+			}
+			PaletteCommand::Inverse { args, input, output } => {
+				paletteInverse::run(args, input.open(), output.open()).report()
+			}
+			PaletteCommand::Index { args, palette, input, output } => {
+				let metric = args.metric.unwrap_or_default();
+				paletteIndex::run(args, palette.before(metric, input.open()), output.open()).report()
+			}
+			PaletteCommand::Tables { args, palette, input, output } => {
+				let metric = args.metric.unwrap_or_default();
+				output
+					.with(|stdout| paletteTables::run(args, palette.before(metric, input.open()), stdout))
+					.report()
+			}
+		},
+		Command::Tile(command) => match command {
+			Tile::NoisySquare { input, output } => tileNoisySquare::run(input.open(), output.open()).report(),
+			Tile::XStaggered { input, output } => tileXStaggered::run(input.open(), output.open()).report(),
+			Tile::FloorRoof { args, input, output } => {
+				tileFloorRoof::run(args, input.open(), output.open()).report()
+			}
+		},
+		Command::Atlas(Atlas::Build { args, input, output }) => {
+			output.with(|stdout| atlasBuild::run(args, input.open(), stdout)).report()
+		}
+		Command::Stats(command) => match command {
+			Stats::TileIds { args, input, output } => {
+				statsTileIDs::run(args, input.open(), output.open()).report()
+			}
+			Stats::CellComponents { args, input, output } => {
+				statsCellComponents::run(args, input.open(), output.open()).report()
+			}
+			Stats::PalIndices { input, output } => statsPALIndices::run(input.open(), output.open()).report(),
+		},
+		Command::Tar(command) => match command {
+			Tar::Cat { args, output } => tarCat::run(args, io::stdin().lock(), output.open()).report(),
+			Tar::Split { args, input, output } => tarSplit::run(args, input.open(), output.open()).report(),
+		},
+		Command::Mpq(Mpq::Extract { args, output }) => mpqExtract::run(args, output.open()).report(),
+		Command::Png(Png::FixWaifu2x { input, output }) => {
+			pngFixWaifu2x::run(input.open(), output.open()).report()
+		}
+		Command::Verify { args, output } => verify::run(args, output.open()),
+		Command::Batch { args, output } => batch::run(args, output.open()),
+	}
+}
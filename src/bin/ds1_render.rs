@@ -0,0 +1,249 @@
+#![warn(clippy::pedantic, elided_lifetimes_in_paths, explicit_outlives_requirements)]
+#![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
+
+use {
+	clap::Parser,
+	d2sw_tiled_project::{
+		ds1::{self, Cell},
+		dt1::{
+			self, DrawDestination, FLOOR_ORIENTATION, FLOOR_ROOF_TILEHEIGHT, ROOF_ORIENTATION,
+			SHADOW_ORIENTATION, TILEWIDTH,
+		},
+		io_readToString,
+		palette::{Blend, BlendTable, Format, Metric, PalInverse, Palette},
+		stdoutRaw, Blended, Image, MinAssign_MaxAssign_Ext,
+	},
+	png::ColorType,
+	std::{
+		collections::HashMap,
+		fs,
+		io::{self, BufWriter, Read, Write},
+		path::{Path, PathBuf},
+	},
+};
+
+// drawn along with the orientation-3 tile of the same main and sub indices
+const LEFT_PART_OF_NORTH_CORNER_WALL: i32 = 3;
+const RIGHT_PART_OF_NORTH_CORNER_WALL: i32 = 4;
+// warps and other invisible markers
+const SPECIAL_ORIENTATIONS: [i32; 2] = [10, 11];
+
+struct DrawCall<'a> {
+	point: [i32; 2],
+	tile: &'a dt1::Tile,
+	dt1: &'a [u8],
+	isShadow: bool,
+}
+
+// shadows darken what they cover, whatever their own pixels: black blended over it through --shadow-blend's
+//   table, or without one, every other pixel of it made the palette's black
+struct ShadowDestination<'a> {
+	image: &'a mut Image,
+	black: u8,
+	blendTable: Option<&'a BlendTable>,
+}
+impl DrawDestination for ShadowDestination<'_> {
+	#[inline(always)]
+	fn width(&self) -> usize {
+		self.image.width
+	}
+	#[inline(always)]
+	fn putpixel(&mut self, atIndex: usize, _: u8) {
+		if let Some(blendTable) = self.blendTable {
+			Blended { image: &mut *self.image, blendTable }.putpixel(atIndex, self.black);
+		} else if (atIndex % self.image.width + atIndex / self.image.width) % 2 == 0 {
+			self.image.data[atIndex] = self.black;
+		}
+	}
+}
+
+// DS1s refer to their DT1s as e.g. `C:\Diablo2\Data\Global\Tiles\Act1\Town\Floor.tg1`
+fn dt1Path(tilesDir: &Path, file: &str) -> Option<PathBuf> {
+	let file = file.replace('\\', "/");
+	let start = file.to_ascii_lowercase().find("tiles/")? + "tiles/".len();
+	let mut path = tilesDir.to_path_buf();
+	let components = Vec::from_iter(file[start..].split('/'));
+	for (i, component) in components.iter().enumerate() {
+		let component = match i + 1 == components.len() {
+			true => format!("{}.dt1", component.rsplit_once('.').map_or(*component, |(stem, _)| stem)),
+			false => (*component).to_owned(),
+		};
+		path = fs::read_dir(&path)
+			.ok()?
+			.filter_map(Result::ok)
+			.find(|entry| entry.file_name().to_string_lossy().eq_ignore_ascii_case(&component))?
+			.path();
+	}
+	Some(path)
+}
+
+#[derive(Parser)]
+pub struct Args {
+	/// The act palette as written by `1_-_pal_into_pngPAL`.
+	#[clap(long, required_unless_present = "palette-dir")]
+	pngPal: Option<PathBuf>,
+
+	/// Diablo II's `data/global/palette`, to take the palette of the DS1's act (its `actIndex`) from instead.
+	#[clap(long, conflicts_with = "png-pal")]
+	paletteDir: Option<PathBuf>,
+
+	/// The palette's inverse, as written by `2_-_pngPAL_into_palInverse`, or a palInverse cache directory to
+	///   find or make it in, to blend shadows over what they cover the way the game does instead of dithering them.
+	#[clap(long)]
+	palInverse: Option<PathBuf>,
+
+	/// How black is blended over what shadows cover, with --pal-inverse: `alpha25`, `alpha50`, `alpha75` or
+	///   `additive`.
+	#[clap(long, default_value_t = Blend::Alpha50, requires = "pal-inverse")]
+	shadowBlend: Blend,
+
+	/// The metric of the palInverse made in a --pal-inverse cache directory: `rgb`, `redmean`, `cie76`,
+	///   `ciede2000` or `oklab`.
+	#[clap(long, default_value_t, requires = "pal-inverse")]
+	metric: Metric,
+
+	/// The directory the DS1's `files` are looked up in (case-insensitively), from their `Tiles\` on.
+	#[clap(long)]
+	tilesDir: PathBuf,
+}
+
+fn main() {
+	run(Args::parse(), io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(
+	Args { pngPal, paletteDir, palInverse, shadowBlend, metric, tilesDir }: Args,
+	stdin: impl Read,
+	stdout: impl Write,
+) {
+	let ds1RootStruct = &toml::from_str::<ds1::RootStruct>(&io_readToString(stdin).unwrap()).unwrap();
+	let palette = match (pngPal, paletteDir) {
+		(Some(pngPal), _) => fs::read(&pngPal)
+			.map_err(Into::into)
+			.and_then(|pngPAL| Palette::read(Format::PngPAL, &pngPAL))
+			.unwrap_or_else(|err| panic!("{pngPal:?}: {err}")),
+		(None, Some(paletteDir)) => Palette::ofAct(&paletteDir, ds1RootStruct.actIndex)
+			.unwrap_or_else(|err| panic!("{paletteDir:?}: {err}")),
+		(None, None) => panic!("neither --png-pal nor --palette-dir"),
+	};
+	let shadowBlendTable = palInverse.map(|path| {
+		let palInverse =
+			PalInverse::fromFileOrCache(&path, &palette, metric).unwrap_or_else(|err| panic!("{path:?}: {err}"));
+		palette.blendTable(&palInverse.cube, shadowBlend)
+	});
+	let black = palette.black();
+	let dt1s = Vec::from_iter(ds1RootStruct.files.iter().filter_map(|file| {
+		let path = dt1Path(&tilesDir, file).or_else(|| {
+			eprintln!("{file:?}: not found under {tilesDir:?}");
+			None
+		})?;
+		let dt1 = fs::read(&path).unwrap_or_else(|err| panic!("{path:?}: {err}"));
+		match dt1::Metadata::new(&dt1) {
+			Ok(dt1Metadata) => Some((dt1Metadata, dt1)),
+			Err(err) => {
+				eprintln!("{path:?}: {err:?}");
+				None
+			}
+		}
+	}));
+	// the first of the DT1s' tiles with a given [orientation, mainIndex, subIndex] wins
+	let tiles = &mut HashMap::new();
+	for (dt1Metadata, dt1) in &dt1s {
+		for tile in &dt1Metadata.tiles {
+			tiles.entry([tile.orientation, tile.mainIndex, tile.subIndex]).or_insert((tile, dt1.as_slice()));
+		}
+	}
+
+	let [numWallLayers, numFloors] =
+		[ds1RootStruct.numWallLayers as usize, ds1RootStruct.numFloors as usize];
+	let (floors, shadows, lowerWalls, upperWalls, roofs) =
+		(&mut Vec::new(), &mut Vec::new(), &mut Vec::new(), &mut Vec::new(), &mut Vec::new());
+	for y in 0..ds1RootStruct.height() {
+		for x in 0..ds1RootStruct.width() {
+			let point =
+				[(x as i32 - y as i32) * TILEWIDTH as i32 / 2, (x + y) as i32 * FLOOR_ROOF_TILEHEIGHT as i32 / 2];
+			let push = |drawCalls: &mut Vec<_>, orientation: i32, cell: Cell, Δy: i32| {
+				if cell.isEmpty() || cell.isHidden() {
+					return false;
+				}
+				if let Some(&(tile, dt1)) = tiles.get(&[orientation, cell.mainIndex() as _, cell.subIndex() as _])
+				{
+					drawCalls.push(DrawCall {
+						point: [point[0], point[1] + Δy],
+						tile,
+						dt1,
+						isShadow: orientation == SHADOW_ORIENTATION,
+					});
+					return true;
+				}
+				false
+			};
+			for i in 0..numFloors {
+				_ = push(floors, FLOOR_ORIENTATION, ds1RootStruct.floorLayer(i)[(x, y)], 0);
+			}
+			_ =
+				push(shadows, SHADOW_ORIENTATION, ds1RootStruct.shadowLayer()[(x, y)], FLOOR_ROOF_TILEHEIGHT as _);
+			for i in 0..numWallLayers {
+				let (cell, orientation) =
+					(ds1RootStruct.wallLayer(i)[(x, y)], ds1RootStruct.orientationLayer(i)[(x, y)].orientation());
+				match orientation {
+					ROOF_ORIENTATION => {
+						if push(roofs, orientation, cell, 0) {
+							let roof = roofs.last_mut().unwrap();
+							roof.point[1] -= i32::from(roof.tile.roofHeight);
+						}
+					}
+					_ if SPECIAL_ORIENTATIONS.contains(&orientation) => {}
+					_ if orientation > ROOF_ORIENTATION => {
+						_ = push(lowerWalls, orientation, cell, FLOOR_ROOF_TILEHEIGHT as _);
+					}
+					_ => {
+						_ = push(upperWalls, orientation, cell, FLOOR_ROOF_TILEHEIGHT as _);
+						if orientation == LEFT_PART_OF_NORTH_CORNER_WALL {
+							_ = push(upperWalls, RIGHT_PART_OF_NORTH_CORNER_WALL, cell, FLOOR_ROOF_TILEHEIGHT as _);
+						}
+					}
+				}
+			}
+		}
+	}
+
+	let drawCalls = Vec::from_iter(
+		[floors, shadows, lowerWalls, upperWalls, roofs].into_iter().flat_map(|drawCalls| drawCalls.drain(..)),
+	);
+	let [mut left, mut top, mut right, mut bottom] = [i32::MAX, i32::MAX, i32::MIN, i32::MIN];
+	for &DrawCall { point: [x, y], tile, .. } in &drawCalls {
+		for block in &tile.blocks {
+			let [x, y] = [x + i32::from(block.x), y + i32::from(block.y)];
+			left.minAssign(x);
+			top.minAssign(y);
+			right.maxAssign(x + dt1::BLOCKWIDTH as i32);
+			bottom.maxAssign(y + tile.blockHeight() as i32);
+		}
+	}
+	if drawCalls.is_empty() {
+		[left, top, right, bottom] = [0, 0, 1, 1];
+	}
+	let image = &mut Image::fromWidthHeight((right - left) as _, (bottom - top) as _);
+	for DrawCall { point: [x, y], tile, dt1, isShadow } in drawCalls {
+		for block in &tile.blocks {
+			let ([x0, y0], data) = (
+				[x + i32::from(block.x) - left, y + i32::from(block.y) - top].map(|coord| coord as usize),
+				&dt1[(tile.blockHeadersPointer + block.fileOffset) as _..][..block.length as _],
+			);
+			if isShadow {
+				let shadowDestination =
+					&mut ShadowDestination { image, black, blendTable: shadowBlendTable.as_deref() };
+				block.drawFn()(shadowDestination, x0, y0, data);
+			} else {
+				block.drawFn()(image, x0, y0, data);
+			}
+		}
+	}
+
+	let mut png = png::Encoder::new(BufWriter::new(stdout), image.width as _, image.height as _);
+	png.set_color(ColorType::Indexed);
+	png.set_palette(palette.pngPAL());
+	png.set_trns(palette.trns());
+	png.write_header().unwrap().write_image_data(&image.data).unwrap();
+}
@@ -3,37 +3,32 @@
 
 use {
 	array_macro::array,
-	const_format::formatcp,
-	core::str::FromStr,
 	d2sw_tiled_project::{
 		dt1::{self, DrawDestination},
-		stdoutRaw, VecExt,
+		stdoutRaw, tar,
 	},
-	std::io::{self, BufRead, Read, Write},
+	std::io::{self, Read, Write},
 };
 
 fn main() {
-	type Filesize = usize;
-	const FILESIZE_LINE: &'static str = formatcp!("{}\r\n", Filesize::MAX);
-	let (stdin, stdout, filesizeLine, dt1, counts) = &mut (
-		io::stdin().lock(),
-		io::BufWriter::new(stdoutRaw()),
-		String::with_capacity(FILESIZE_LINE.len()),
-		Vec::new(),
-		[0; UsedPALIndicesFrequency::LEN],
-	);
-	'outer: while {
-		filesizeLine.clear();
-		stdin.read_line(filesizeLine).unwrap() != 0
-	} {
+	run(io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(stdin: impl Read, stdout: impl Write) {
+	let (stdout, counts) = &mut (io::BufWriter::new(stdout), [0; UsedPALIndicesFrequency::LEN]);
+	'outer: for entry in tar::Reader::new(stdin) {
+		let entry = &entry.unwrap_or_else(|err| panic!("{err}"));
+		// a directory's worth of `dubcat` has more than dt1s in it
+		if !entry.hasExtension("dt1") {
+			continue;
+		}
+		let tar::Entry { name, data: dt1, .. } = entry;
 		for tile in {
-			let filesize = Filesize::from_str(filesizeLine.trim_end_matches(['\n', '\r'])).unwrap();
-			dt1.clear();
-			dt1.reserve(filesize);
-			dt1.setLen(filesize);
-			stdin.read_exact(dt1).unwrap();
 			match dt1::Metadata::new(dt1) {
-				Err(_) => continue 'outer,
+				Err(err) => {
+					eprintln!("{name}: {err}");
+					continue 'outer;
+				}
 				Ok(ok) => ok,
 			}
 		}
@@ -49,7 +44,6 @@ fn main() {
 			}
 		}
 	}
-	assert_eq!(filesizeLine.capacity(), FILESIZE_LINE.len());
 	let mut arrayIndices = array![j => j as u8; UsedPALIndicesFrequency::LEN];
 	arrayIndices.sort_by_key(|&i| counts[i as usize]);
 	for i in arrayIndices {
@@ -2,27 +2,116 @@
 #![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
 
 use {
-	d2sw_tiled_project::stdoutRaw,
+	clap::Parser,
+	d2sw_tiled_project::{stdoutRaw, tar},
 	std::{
-		fs::File,
-		io::{self, BufRead, Write},
+		fs::{self, File},
+		io::{self, BufRead, BufWriter, Write},
+		path::{Component, Path, PathBuf},
 	},
 };
 
+// `*` matches any run of characters, `?` any one character
+fn wildcardMatches(pattern: &[u8], name: &[u8]) -> bool {
+	match (pattern.split_first(), name.split_first()) {
+		(None, _) => name.is_empty(),
+		(Some((b'*', rest)), _) => {
+			wildcardMatches(rest, name) || !name.is_empty() && wildcardMatches(pattern, &name[1..])
+		}
+		(Some((_, _)), None) => false,
+		(Some((&p, patternRest)), Some((&c, nameRest))) => {
+			(p == b'?' || p == c) && wildcardMatches(patternRest, nameRest)
+		}
+	}
+}
+
+fn sortedDirEntries(dir: &Path) -> Vec<PathBuf> {
+	let mut entries = Vec::from_iter(
+		fs::read_dir(if dir.as_os_str().is_empty() { Path::new(".") } else { dir })
+			.unwrap_or_else(|err| panic!("{dir:?}: {err}"))
+			.map(|entry| dir.join(entry.unwrap().file_name())),
+	);
+	entries.sort_unstable();
+	entries
+}
+
+// wildcards are matched one path component at a time, so they never cross a `/`
+fn expandGlob(pattern: &str) -> Vec<PathBuf> {
+	let mut paths = vec![PathBuf::from(if pattern.starts_with('/') { "/" } else { "" })];
+	for component in pattern.split('/').filter(|component| !component.is_empty()) {
+		if component.contains(['*', '?']) {
+			paths = paths
+				.iter()
+				.filter(|path| path.as_os_str().is_empty() || path.is_dir())
+				.flat_map(|dir| sortedDirEntries(dir))
+				.filter(|path| {
+					wildcardMatches(component.as_bytes(), path.file_name().unwrap().to_string_lossy().as_bytes())
+				})
+				.collect();
+		} else {
+			for path in &mut paths {
+				path.push(component);
+			}
+		}
+	}
+	paths.retain(|path| path.exists());
+	paths
+}
+
+fn collectFiles(path: &Path, files: &mut Vec<PathBuf>) {
+	if path.is_dir() {
+		for entry in sortedDirEntries(path) {
+			collectFiles(&entry, files);
+		}
+	} else {
+		files.push(path.to_owned());
+	}
+}
+
+// relative and `/`-separated, like tar makes member names
+fn entryName(path: &Path) -> String {
+	let components = Vec::from_iter(path.components().filter_map(|component| match component {
+		Component::Normal(component) => Some(component.to_string_lossy()),
+		_ => None,
+	}));
+	components.join("/")
+}
+
+#[derive(Parser)]
+pub struct Args {
+	/// Files, directories to take every file under, recursively, or quoted `*`/`?` globs;
+	///   one per stdin line if there are none.
+	pub paths: Vec<String>,
+}
+
 fn main() {
-	let (stdin, stdout, filepathLine) =
-		&mut (io::stdin().lock(), io::BufWriter::new(stdoutRaw()), String::new());
-	while {
-		filepathLine.clear();
-		stdin.read_line(filepathLine).unwrap() != 0
-	} {
-		let file = &mut {
-			let filepath = filepathLine.trim_end_matches(['\n', '\r']);
-			File::open(filepath).unwrap_or_else(|err| panic!("{filepath:?}: {err}"))
-		};
-		writeln!(stdout, "{}", file.metadata().unwrap().len()).unwrap();
-
-		// cast BufWriter into generic Write in order to avoid unnecessary flushes
-		io::copy(file, stdout as &mut dyn Write).unwrap();
+	run(Args::parse(), io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(Args { mut paths }: Args, stdin: impl BufRead, stdout: impl Write) {
+	if paths.is_empty() {
+		paths = Result::from_iter(stdin.lines()).unwrap();
+	}
+
+	let files = &mut Vec::new();
+	for path in paths {
+		if path.contains(['*', '?']) && !Path::new(&path).exists() {
+			let expanded = expandGlob(&path);
+			assert!(!expanded.is_empty(), "{path:?}: no matches");
+			for path in expanded {
+				collectFiles(&path, files);
+			}
+		} else {
+			collectFiles(Path::new(&path), files);
+		}
+	}
+	let mut stdout = tar::Writer::new(BufWriter::new(stdout));
+	for filepath in files.iter() {
+		let file = &mut File::open(filepath).unwrap_or_else(|err| panic!("{filepath:?}: {err}"));
+		let name = entryName(filepath);
+		stdout
+			.appendFrom(&name, tar::mediaTypeFromName(&name), file.metadata().unwrap().len(), file)
+			.unwrap_or_else(|err| panic!("{filepath:?}: {err}"));
 	}
+	stdout.finish().unwrap();
 }
@@ -2,30 +2,82 @@
 #![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
 
 use {
-	const_format::formatcp,
+	clap::Parser,
 	core::str::FromStr,
-	d2sw_tiled_project::stdoutRaw,
+	d2sw_tiled_project::{stdoutRaw, tar, Error},
 	std::{
-		env,
-		fs::File,
-		io::{self, BufRead, Read},
+		fs,
+		io::{self, Read, Write},
+		path::{Component, Path, PathBuf},
+		process::ExitCode,
 	},
 };
 
-fn main() {
-	const FILESIZE_LINE: &'static str = formatcp!("{}\r\n", u64::MAX);
-	let (stdin, filesizeLine) = &mut (io::stdin().lock(), String::with_capacity(FILESIZE_LINE.len()));
-	for filepath in env::args().skip(1) {
-		filesizeLine.clear();
-		if stdin.read_line(filesizeLine).unwrap() == 0 {
-			break;
+#[derive(Parser)]
+pub struct Args {
+	/// Where every entry is written under its own name when no `NAME=PATH` is given.
+	#[clap(short = 'C', long, default_value = ".", conflicts_with = "entry-paths")]
+	pub directory: PathBuf,
+
+	/// Write only these entries, each to its path, `-` being stdout; `dt1.toml=Floor.dt1.toml`.
+	pub entryPaths: Vec<EntryPath>,
+}
+pub struct EntryPath {
+	name: String,
+	path: String,
+	isWritten: bool,
+}
+impl FromStr for EntryPath {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (name, path) = s.split_once('=').ok_or_else(|| format!("{s:?} isn't `NAME=PATH`"))?;
+		Ok(Self { name: name.to_owned(), path: path.to_owned(), isWritten: false })
+	}
+}
+
+fn main() -> Result<ExitCode, Error> {
+	run(Args::parse(), io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(
+	Args { directory, mut entryPaths }: Args,
+	stdin: impl Read,
+	mut stdout: impl Write,
+) -> Result<ExitCode, Error> {
+	let mut exitCode = ExitCode::SUCCESS;
+	for entry in tar::Reader::new(stdin) {
+		let tar::Entry { name, data, .. } = entry?;
+		if entryPaths.is_empty() {
+			// no escaping the directory through absolute names or `..`
+			if !Path::new(&name).components().all(|component| matches!(component, Component::Normal(_))) {
+				eprintln!("{name:?}: not a plain relative name, skipped");
+				exitCode = ExitCode::FAILURE;
+				continue;
+			}
+			let filepath = directory.join(&name);
+			fs::create_dir_all(filepath.parent().unwrap())?;
+			write(&filepath, &data)?;
+			continue;
+		}
+		for EntryPath { path, isWritten, .. } in
+			entryPaths.iter_mut().filter(|entryPath| entryPath.name == name)
+		{
+			if path == "-" {
+				stdout.write_all(&data)?;
+			} else {
+				write(Path::new(path), &data)?;
+			}
+			*isWritten = true;
 		}
-		io::copy(
-			&mut stdin.take(u64::from_str(filesizeLine.trim_end_matches(['\n', '\r'])).unwrap()),
-			&mut File::create(filepath).unwrap(),
-		)
-		.unwrap();
 	}
-	assert_eq!(filesizeLine.capacity(), FILESIZE_LINE.len());
-	io::copy(stdin, &mut stdoutRaw()).unwrap();
+	for EntryPath { name, .. } in entryPaths.iter().filter(|entryPath| !entryPath.isWritten) {
+		eprintln!("no {name:?} entry in the stream");
+		exitCode = ExitCode::FAILURE;
+	}
+	Ok(exitCode)
+}
+
+fn write(path: &Path, data: &[u8]) -> Result<(), Error> {
+	fs::write(path, data)
+		.map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())).into())
 }
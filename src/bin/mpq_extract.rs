@@ -0,0 +1,131 @@
+#![warn(clippy::pedantic, elided_lifetimes_in_paths, explicit_outlives_requirements)]
+#![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
+
+use {
+	clap::Parser,
+	d2sw_tiled_project::{
+		mpq::{self, MPQError},
+		stdoutRaw, tar,
+	},
+	std::{
+		fs,
+		io::{BufWriter, Write},
+		path::{Component, Path, PathBuf},
+	},
+};
+
+// case-insensitive, `*` matches any run of characters (path separators included), `?` any one character
+fn wildcardMatches(pattern: &[u8], path: &[u8]) -> bool {
+	match (pattern.split_first(), path.split_first()) {
+		(None, _) => path.is_empty(),
+		(Some((b'*', rest)), _) => {
+			wildcardMatches(rest, path) || !path.is_empty() && wildcardMatches(pattern, &path[1..])
+		}
+		(Some((_, _)), None) => false,
+		(Some((&p, patternRest)), Some((&c, pathRest))) => {
+			(p == b'?' || normalized(p) == normalized(c)) && wildcardMatches(patternRest, pathRest)
+		}
+	}
+}
+fn normalized(byte: u8) -> u8 {
+	match byte {
+		b'/' => b'\\',
+		_ => byte.to_ascii_lowercase(),
+	}
+}
+
+#[derive(Parser)]
+pub struct Args {
+	mpq: PathBuf,
+	/// Archive paths, `data\global\tiles\ACT1\Town\floor.dt1` or `data/global/tiles/act1/town/*.dt1`;
+	///   wildcards are matched against the listfiles.
+	paths: Vec<String>,
+	/// Write every file under this directory instead of to stdout, where one path without wildcards is
+	///   written as it is and anything else as a tar stream.
+	#[clap(long)]
+	outputDir: Option<PathBuf>,
+	/// An extra listfile for archives whose own `(listfile)` is missing or incomplete.
+	#[clap(long)]
+	listfile: Option<PathBuf>,
+	/// Print the known paths that exist in the archive (and match `paths`, if any) instead of extracting.
+	#[clap(long)]
+	list: bool,
+}
+
+fn main() -> Result<(), MPQError> {
+	run(Args::parse(), stdoutRaw())
+}
+
+pub fn run(
+	Args { mpq, paths, outputDir, listfile, list }: Args,
+	stdout: impl Write,
+) -> Result<(), MPQError> {
+	let archive =
+		mpq::Archive::new(fs::read(&mpq).unwrap_or_else(|err| panic!("{mpq:?}: {err}")).into_boxed_slice())?;
+	let knownPaths = {
+		let mut knownPaths = archive.listfile().unwrap_or_default();
+		if let Some(listfile) = &listfile {
+			knownPaths.extend(mpq::parseListfile(
+				&fs::read_to_string(listfile).unwrap_or_else(|err| panic!("{listfile:?}: {err}")),
+			));
+		}
+		knownPaths.sort_unstable_by_key(|path| path.to_ascii_lowercase());
+		knownPaths.dedup_by_key(|path| path.to_ascii_lowercase());
+		knownPaths.retain(|path| archive.contains(path));
+		knownPaths
+	};
+	// however many files a wildcard matches, what's written doesn't change with it
+	let isOnePlainPath = matches!(paths.as_slice(), [path] if !path.contains(['*', '?']));
+	let selectedPaths = if paths.is_empty() {
+		knownPaths
+	} else {
+		let mut selectedPaths = Vec::new();
+		for path in paths {
+			if path.contains(['*', '?']) {
+				selectedPaths.extend(
+					knownPaths
+						.iter()
+						.filter(|knownPath| wildcardMatches(path.as_bytes(), knownPath.as_bytes()))
+						.cloned(),
+				);
+			} else {
+				selectedPaths.push(path);
+			}
+		}
+		selectedPaths
+	};
+
+	let stdout = &mut BufWriter::new(stdout);
+	if list {
+		for path in selectedPaths {
+			writeln!(stdout, "{path}").unwrap();
+		}
+		return Ok(());
+	}
+	match (&outputDir, selectedPaths.as_slice()) {
+		(Some(outputDir), _) => {
+			for path in &selectedPaths {
+				let name = path.replace('\\', "/");
+				// the listfile is the archive's say, no escaping the directory through absolute names or `..`
+				if !Path::new(&name).components().all(|component| matches!(component, Component::Normal(_))) {
+					eprintln!("{path:?}: not a plain relative name, skipped");
+					continue;
+				}
+				let filepath = outputDir.join(name);
+				fs::create_dir_all(filepath.parent().unwrap()).unwrap();
+				fs::write(&filepath, archive.read(path)?).unwrap_or_else(|err| panic!("{filepath:?}: {err}"));
+			}
+		}
+		(None, [path]) if isOnePlainPath => stdout.write_all(&archive.read(path)?).unwrap(),
+		// a tar stream, same as dubcat's, so the output can be dubsplit
+		(None, _) => {
+			let mut stdout = tar::Writer::new(stdout);
+			for path in &selectedPaths {
+				let name = path.replace('\\', "/");
+				stdout.append(&name, tar::mediaTypeFromName(&name), &archive.read(path)?).unwrap();
+			}
+			stdout.finish().unwrap();
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,252 @@
+#![warn(clippy::pedantic, elided_lifetimes_in_paths, explicit_outlives_requirements)]
+#![allow(non_snake_case, confusable_idents, mixed_script_confusables, uncommon_codepoints)]
+
+use {
+	clap::Parser,
+	core::{iter, mem::size_of},
+	d2sw_tiled_project::{
+		ds1,
+		dt1::{self, BLOCKHEADER_SIZE, BLOCKWIDTH, FILEHEADER_SIZE, TILEHEADER_SIZE},
+		pack, stdoutRaw, toml_toStringPretty, Image, Layout, PAL_LEN,
+	},
+	std::{
+		fs,
+		io::{self, BufWriter, Write},
+		panic,
+		path::{Path, PathBuf},
+		process::ExitCode,
+	},
+};
+
+enum Outcome {
+	Same,
+	SameBut(String),
+	Differs(String),
+}
+
+// ds1 -> ds1TOML (both the packed and the named-layers forms) -> ds1, as `1_-_ds1_into_ds1TOML` and
+//   `2_-_ds1TOML_into_ds1` do it
+fn verifyDS1(ds1: &[u8]) -> Result<Outcome, String> {
+	let cursor = &mut io::Cursor::new(ds1);
+	let ds1RootStruct = &ds1::RootStruct::new(cursor).map_err(|err| err.to_string())?;
+	let end = cursor.position() as usize;
+	for (form, toml) in [
+		("packed", toml_toStringPretty(ds1RootStruct)),
+		("named-layers", toml_toStringPretty(&ds1RootStruct.withNamedLayers())),
+	] {
+		let toml = toml.map_err(|err| format!("{form} TOML: {err}"))?;
+		let written = &mut Vec::new();
+		toml::from_str::<ds1::RootStruct>(&toml)
+			.map_err(|err| format!("{form} TOML: {err}"))?
+			.writeTo(written)
+			.map_err(|err| err.to_string())?;
+		if let Some(offset) = firstDifference(&ds1[..end], written) {
+			return Ok(Outcome::Differs(format!(
+				"{form} TOML: first difference at byte {offset} ({})",
+				ds1Location(ds1RootStruct, offset)
+			)));
+		}
+	}
+	Ok(match ds1.len() - end {
+		0 => Outcome::Same,
+		trailing => Outcome::SameBut(format!("the {trailing} bytes after byte {end} aren't kept")),
+	})
+}
+
+fn ds1Location(ds1RootStruct: &ds1::RootStruct, offset: usize) -> String {
+	let &ds1::RootStruct { version, ref files, ref layers, .. } = ds1RootStruct;
+	let layersStart = size_of::<i32>()
+		* (4
+			+ usize::from(version >= 8)
+			+ usize::from(version >= 10)
+			+ usize::from(matches!(version, 9..=13)) * 2
+			+ usize::from(version >= 16))
+		+ files.iter().map(|file| file.len() + 1).sum::<usize>();
+	let layerSize = ds1RootStruct.width() * ds1RootStruct.height() * size_of::<u32>();
+	match offset.checked_sub(layersStart) {
+		None => "header".to_owned(),
+		Some(Δ) if Δ < layers.len() * layerSize => {
+			let cellIndex = Δ % layerSize / size_of::<u32>();
+			format!(
+				"layer {} cell ({}, {})",
+				ds1RootStruct.layerNames()[Δ / layerSize],
+				cellIndex % ds1RootStruct.width(),
+				cellIndex / ds1RootStruct.width()
+			)
+		}
+		Some(_) => "objects, groups or paths".to_owned(),
+	}
+}
+
+// how `verifyDT1` lays the block and tile PNGs out, one round trip each: named, the blocks' and the tiles'
+//   layout, and --zealous-vertical-packing
+const DT1_PASSES: [(&str, Layout, bool); 4] = [
+	("columns", Layout::Columns, false),
+	("zealous", Layout::Columns, true),
+	("packed", Layout::Packed(pack::Heuristic::BestShortSideFit), false),
+	("zealous packed", Layout::Packed(pack::Heuristic::BestShortSideFit), true),
+];
+
+// dt1 -> dt1TOML + block PNG -> tile PNG -> dt1, as `2_-_pngPAL-dt1_into_dt1TOML-blockPNG`,
+//   `3_-_dt1TOML-blockPNG_into_tilePNG` and `4_-_dt1TOML-tilePNG_into_dt1` do it, in every one of `DT1_PASSES`
+fn verifyDT1(dt1: &[u8]) -> Result<Outcome, String> {
+	let dt1Metadata = &dt1::Metadata::new(dt1).map_err(|err| err.to_string())?;
+	for (pass, layout, zealousVerticalPacking) in DT1_PASSES {
+		let (blockMetadata, blockPNG) = &dt1::Metadata::newWithBlockPNG(&[0; PAL_LEN], dt1, layout)
+			.map_err(|err| format!("{pass}: {err}"))?;
+		let reparsedMetadata = &toml::from_str::<dt1::Metadata>(
+			&toml::to_string(blockMetadata).map_err(|err| format!("{pass}: TOML: {err}"))?,
+		)
+		.map_err(|err| format!("{pass}: TOML: {err}"))?;
+		let tilePNG = &reparsedMetadata
+			.tilePNG(blockPNG, zealousVerticalPacking, layout)
+			.map_err(|err| format!("{pass}: {err}"))?;
+		let written = &mut Vec::new();
+		reparsedMetadata.writeFromTilePNG(tilePNG, written).map_err(|err| format!("{pass}: {err}"))?;
+		if let Some(offset) = firstDifference(dt1, written) {
+			return dt1Difference(dt1Metadata, dt1, written, offset)
+				.map(|difference| Outcome::Differs(format!("{pass}: {difference}")))
+				.map_err(|err| format!("{pass}: {err}"));
+		}
+	}
+	Ok(Outcome::Same)
+}
+
+fn dt1Difference(
+	dt1Metadata: &dt1::Metadata,
+	dt1: &[u8],
+	written: &[u8],
+	offset: usize,
+) -> Result<String, String> {
+	let location = dt1Location(dt1Metadata, offset);
+	let writtenMetadata = &dt1::Metadata::new(written)
+		.map_err(|err| format!("byte {offset} ({location}); unreadable back: {err}"))?;
+	for (i, (tile, writtenTile)) in iter::zip(&dt1Metadata.tiles, &writtenMetadata.tiles).enumerate() {
+		for (j, (block, writtenBlock)) in iter::zip(&tile.blocks, &writtenTile.blocks).enumerate() {
+			if blockImage(tile, block, dt1).data != blockImage(writtenTile, writtenBlock, written).data {
+				return Ok(format!(
+					"first difference at byte {offset} ({location}); pixel-visible from tile {i} block {j} on"
+				));
+			}
+		}
+	}
+	Ok(format!("first difference at byte {offset} ({location}); encoding-only, same pixels"))
+}
+
+fn dt1Location(dt1Metadata: &dt1::Metadata, offset: usize) -> String {
+	let (offset, tileHeadersPointer) = (offset as i32, dt1Metadata.fileHeader.tileHeadersPointer);
+	if offset < FILEHEADER_SIZE {
+		return "file header".to_owned();
+	}
+	let Δ = offset - tileHeadersPointer;
+	if (0..dt1Metadata.tiles.len() as i32 * TILEHEADER_SIZE).contains(&Δ) {
+		return format!("tile {} header", Δ / TILEHEADER_SIZE);
+	}
+	for (i, tile) in dt1Metadata.tiles.iter().enumerate() {
+		let Δ = offset - tile.blockHeadersPointer;
+		if (0..tile.blocks.len() as i32 * BLOCKHEADER_SIZE).contains(&Δ) {
+			return format!("tile {i} block {} header", Δ / BLOCKHEADER_SIZE);
+		}
+		for (j, block) in tile.blocks.iter().enumerate() {
+			if (block.fileOffset..block.fileOffset + block.length).contains(&Δ) {
+				return format!("tile {i} block {j} data");
+			}
+		}
+	}
+	"past the last block".to_owned()
+}
+
+fn blockImage(tile: &dt1::Tile, block: &dt1::Block, dt1: &[u8]) -> Image {
+	let mut image = Image::fromWidthHeight(BLOCKWIDTH, tile.blockHeight());
+	block.drawFn()(
+		&mut image,
+		0,
+		0,
+		&dt1[(tile.blockHeadersPointer + block.fileOffset) as _..][..block.length as _],
+	);
+	image
+}
+
+fn firstDifference(expected: &[u8], actual: &[u8]) -> Option<usize> {
+	iter::zip(expected, actual)
+		.position(|(expected, actual)| expected != actual)
+		.or_else(|| (expected.len() != actual.len()).then(|| expected.len().min(actual.len())))
+}
+
+fn collectFiles(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+	if path.is_dir() {
+		let mut entries = Vec::from_iter(fs::read_dir(path)?.map(|entry| entry.map(|entry| entry.path())));
+		entries.sort_by(|a, b| match (a, b) {
+			(Ok(a), Ok(b)) => a.cmp(b),
+			_ => core::cmp::Ordering::Equal,
+		});
+		for entry in entries {
+			collectFiles(&entry?, files)?;
+		}
+	} else if path
+		.extension()
+		.map_or(false, |extension| ["ds1", "dt1"].iter().any(|&ext| extension.eq_ignore_ascii_case(ext)))
+	{
+		files.push(path.to_owned());
+	}
+	Ok(())
+}
+
+#[derive(Parser)]
+pub struct Args {
+	/// DS1 and DT1 files, or directories to look for them in, recursively.
+	#[clap(required = true)]
+	paths: Vec<PathBuf>,
+}
+
+fn main() -> ExitCode {
+	run(Args::parse(), stdoutRaw())
+}
+
+pub fn run(Args { paths }: Args, stdout: impl Write) -> ExitCode {
+	let files = &mut Vec::new();
+	for path in &paths {
+		collectFiles(path, files).unwrap_or_else(|err| panic!("{path:?}: {err}"));
+	}
+	let (stdout, [mut same, mut different, mut unreadable]) = (&mut BufWriter::new(stdout), [0; 3]);
+	for path in files.iter() {
+		let outcome = fs::read(path).map_err(|err| err.to_string()).and_then(|bytes| {
+			// block data the drawing code can't make sense of panics; that's one bad file, not a bad run
+			panic::catch_unwind(|| {
+				if path.extension().unwrap().eq_ignore_ascii_case("ds1") {
+					verifyDS1(&bytes)
+				} else {
+					verifyDT1(&bytes)
+				}
+			})
+			.unwrap_or_else(|_| Err("panicked, see above".to_owned()))
+		});
+		let path = path.display();
+		match outcome {
+			Ok(Outcome::Same) => {
+				same += 1;
+				writeln!(stdout, "OK\t{path}")
+			}
+			Ok(Outcome::SameBut(note)) => {
+				same += 1;
+				writeln!(stdout, "OK\t{path}: {note}")
+			}
+			Ok(Outcome::Differs(difference)) => {
+				different += 1;
+				writeln!(stdout, "DIFF\t{path}: {difference}")
+			}
+			Err(err) => {
+				unreadable += 1;
+				writeln!(stdout, "ERR\t{path}: {err}")
+			}
+		}
+		.unwrap();
+	}
+	stdout.flush().unwrap();
+	eprintln!("{} files: {same} round-tripped, {different} differ, {unreadable} unreadable", files.len());
+	if different + unreadable == 0 {
+		ExitCode::SUCCESS
+	} else {
+		ExitCode::FAILURE
+	}
+}
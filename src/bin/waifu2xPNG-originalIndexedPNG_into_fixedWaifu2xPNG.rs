@@ -3,30 +3,37 @@
 
 use {
 	array_macro::array,
-	core::str::{self, FromStr},
-	d2sw_tiled_project::{stdoutRaw, DotExt, Image, VecExt, FULLY_TRANSPARENT, RGBA_SIZE, RGB_SIZE},
+	d2sw_tiled_project::{
+		stdoutRaw, tar, DotExt, Error, Image, VecExt, FULLY_TRANSPARENT, RGBA_SIZE, RGB_SIZE,
+	},
 	glam::{IVec2, IVec3, IVec4},
-	memchr::memchr,
 	png::ColorType,
-	std::io::{self, BufRead, BufWriter, Read},
+	std::io::{self, BufWriter, Read, Write},
 };
 
-fn main() {
-	let stdin = &mut io::stdin().lock();
+fn main() -> Result<(), Error> {
+	run(io::stdin().lock(), stdoutRaw())
+}
+
+pub fn run(stdin: impl Read, stdout: impl Write) -> Result<(), Error> {
+	let entries = &tar::Entries::read(stdin)?;
+	// told apart by what they are rather than by their order: the original is the indexed one
+	let (origs, waifu2xs): (Vec<_>, Vec<_>) = entries
+		.all(tar::PNG)
+		.map(|entry| png::Decoder::new(&*entry.data).read_info().unwrap())
+		.partition(|png| png.info().color_type == ColorType::Indexed);
+	let (Ok([ref mut png]), Ok([mut waifu2x])) = (<[_; 1]>::try_from(origs), <[_; 1]>::try_from(waifu2xs))
+	else {
+		return Err(Error::Role(
+			"one indexed PNG (the original) and one other (waifu2x's) expected".to_owned(),
+		));
+	};
 	let (ref waifu2x, width) = {
-		let (filesizeLine_len, filesize) = {
-			let buffer = stdin.fill_buf().unwrap();
-			let filesizeLine = str::from_utf8(&buffer[..=memchr(b'\n', buffer).unwrap()]).unwrap();
-			(filesizeLine.len(), u64::from_str(filesizeLine.trim_end_matches(['\n', '\r'])).unwrap())
-		};
-		stdin.consume(filesizeLine_len);
-		let png = &mut png::Decoder::new(stdin.take(filesize)).read_info().unwrap();
-		let mut vec = Vec::withLen(png.output_buffer_size());
-		let len = png.next_frame(&mut vec).unwrap().buffer_size();
+		let mut vec = Vec::withLen(waifu2x.output_buffer_size());
+		let len = waifu2x.next_frame(&mut vec).unwrap().buffer_size();
 		vec.setLen(len);
-		(vec.into_boxed_slice(), png.info().width as i32)
+		(vec.into_boxed_slice(), waifu2x.info().width as i32)
 	};
-	let png = &mut png::Decoder::new(stdin).read_info().unwrap();
 	let ([origWidth, origHeight], ref mut orig) = {
 		let image = Image::fromPNG(png);
 		([image.width as i32, image.height as _], image.data)
@@ -98,9 +105,10 @@ fn main() {
 			js = js.map(|j| j + Δj);
 		}
 	}
-	let mut png = png::Encoder::new(BufWriter::new(stdoutRaw()), width as _, (origHeight * 2) as _);
+	let mut png = png::Encoder::new(BufWriter::new(stdout), width as _, (origHeight * 2) as _);
 	png.set_color(ColorType::Indexed);
 	png.set_palette(pngPAL);
 	png.set_trns(&[0][..]);
 	png.write_header().unwrap().write_image_data(fixedWaifu2x).unwrap();
+	Ok(())
 }
@@ -3,19 +3,27 @@
 
 pub mod ds1 {
 	use {
-		super::{ReadExt, VecExt, WriteExt},
+		super::{Error, ReadExt, VecExt, WriteExt},
 		byteorder::{ReadBytesExt, WriteBytesExt, LE},
 		core::{
-			array, fmt,
+			fmt,
 			mem::{size_of, size_of_val},
-			slice,
+			ops, slice,
 		},
 		memchr::memchr,
-		serde::{Deserialize, Serialize},
-		std::io::{self, BufRead, Seek, SeekFrom, Write},
+		serde::{
+			de::{self, value::MapAccessDeserializer, value::SeqAccessDeserializer},
+			ser::SerializeMap,
+			Deserialize, Deserializer, Serialize, Serializer,
+		},
+		std::{
+			collections::HashMap,
+			io::{self, BufRead, Seek, SeekFrom, Write},
+		},
 	};
 
 	#[derive(Serialize, Deserialize)]
+	#[serde(try_from = "UncheckedRootStruct")]
 	pub struct RootStruct {
 		pub version: i32,
 		pub xMax: i32,
@@ -26,6 +34,8 @@ pub mod ds1 {
 		pub unknown: Option<[u8; 2 * size_of::<i32>()]>,
 		pub numWallLayers: i32,
 		pub numFloors: i32,
+
+		/// Either the packed `[[u32]]` form or a table of named layers (see `NamedLayers`).
 		pub layers: Vec<Box<[u32]>>,
 
 		#[serde(rename = "object")]
@@ -85,22 +95,632 @@ pub mod ds1 {
 	pub const ONE_SHADOW_LAYER: usize = 1;
 	const MINIMUM_VERSION: i32 = 7;
 
-	pub struct VersionMismatchError {
-		version: i32,
+	#[inline(always)]
+	pub const fn existsTagLayer(tagType: i32) -> bool {
+		matches!(tagType, 1 | 2)
+	}
+
+	pub const HIDDEN_MASK: u32 = 1 << (u32::BITS - 1);
+	pub const UNKNOWN_BITS_MASK: u32 = !(PROP1_MASK | SUB_INDEX_MASK | MAIN_INDEX_MASK | HIDDEN_MASK);
+
+	/// One of `layers`' elements: prop1 (the drawing priority of a wall, floor or shadow cell, or the orientation of
+	///   an orientation cell), subIndex, mainIndex, the hidden flag, and the unknown bits in between.
+	#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+	#[repr(transparent)]
+	pub struct Cell(pub u32);
+
+	impl Cell {
+		#[inline(always)]
+		pub const fn prop1(self) -> u32 {
+			self.0 & PROP1_MASK
+		}
+		#[inline(always)]
+		pub const fn drawingPriority(self) -> u32 {
+			self.0 & LAYER_DRAWING_PRIORITY_MASK
+		}
+		#[inline(always)]
+		pub const fn orientation(self) -> i32 {
+			(self.0 & ORIENTATION_MASK) as _
+		}
+		#[inline(always)]
+		pub const fn mainIndex(self) -> u32 {
+			self.0 >> MAIN_INDEX_OFFSET & MAIN_INDEX_MAX
+		}
+		#[inline(always)]
+		pub const fn subIndex(self) -> u32 {
+			self.0 >> SUB_INDEX_OFFSET & SUB_INDEX_MAX
+		}
+		#[inline(always)]
+		pub const fn isHidden(self) -> bool {
+			self.0 & HIDDEN_MASK != 0
+		}
+		#[inline(always)]
+		pub const fn unknownBits(self) -> u32 {
+			self.0 & UNKNOWN_BITS_MASK
+		}
+		/// Wall, floor and shadow cells without a drawing priority have no tile.
+		#[inline(always)]
+		pub const fn isEmpty(self) -> bool {
+			self.drawingPriority() == 0
+		}
+
+		#[inline(always)]
+		pub fn setProp1(&mut self, prop1: u32) {
+			debug_assert_eq!(prop1 & !PROP1_MASK, 0);
+			self.0 = self.0 & !PROP1_MASK | prop1;
+		}
+		#[inline(always)]
+		pub fn setMainIndex(&mut self, mainIndex: u32) {
+			debug_assert!(mainIndex <= MAIN_INDEX_MAX);
+			self.0 = self.0 & !MAIN_INDEX_MASK | mainIndex << MAIN_INDEX_OFFSET;
+		}
+		#[inline(always)]
+		pub fn setSubIndex(&mut self, subIndex: u32) {
+			debug_assert!(subIndex <= SUB_INDEX_MAX);
+			self.0 = self.0 & !SUB_INDEX_MASK | subIndex << SUB_INDEX_OFFSET;
+		}
+		#[inline(always)]
+		pub fn setHidden(&mut self, isHidden: bool) {
+			self.0 = self.0 & !HIDDEN_MASK | if isHidden { HIDDEN_MASK } else { 0 };
+		}
+		#[inline(always)]
+		pub fn setUnknownBits(&mut self, unknownBits: u32) {
+			debug_assert_eq!(unknownBits & !UNKNOWN_BITS_MASK, 0);
+			self.0 = self.0 & !UNKNOWN_BITS_MASK | unknownBits;
+		}
 	}
-	impl fmt::Debug for VersionMismatchError {
+
+	/// The compact form of a cell: `-` when all of its bits are clear, else `main:sub:prop`, followed by `!` when
+	///   hidden and by `+0x...` when any of the unknown bits are set.
+	impl fmt::Display for Cell {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			if self.0 == 0 {
+				return f.pad("-");
+			}
+			let mut string = format!("{}:{}:{}", self.mainIndex(), self.subIndex(), self.prop1());
+			if self.isHidden() {
+				string.push('!');
+			}
+			if self.unknownBits() != 0 {
+				string += &format!("+{:#x}", self.unknownBits());
+			}
+			f.pad(&string)
+		}
+	}
+
+	pub struct ParseCellError(String);
+	impl fmt::Debug for ParseCellError {
 		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-			write!(f, "ds1.version == {:?} < {MINIMUM_VERSION:?}", self.version)
+			write!(f, "ds1 cell {:?} is neither `-` nor an in-range `main:sub:prop[!][+0x...]`", self.0)
+		}
+	}
+
+	impl core::str::FromStr for Cell {
+		type Err = ParseCellError;
+		fn from_str(string: &str) -> Result<Self, Self::Err> {
+			let err = || ParseCellError(string.to_owned());
+			if string == "-" {
+				return Ok(Self(0));
+			}
+			let (rest, unknownBits) = match string.split_once('+') {
+				None => (string, 0),
+				Some((rest, unknownBits)) => (
+					rest,
+					u32::from_str_radix(unknownBits.strip_prefix("0x").ok_or_else(err)?, 16).map_err(|_| err())?,
+				),
+			};
+			let (rest, isHidden) = rest.strip_suffix('!').map_or((rest, false), |rest| (rest, true));
+			let mut indices = rest.split(':').map(str::parse::<u32>);
+			let (Some(Ok(mainIndex)), Some(Ok(subIndex)), Some(Ok(prop1)), None) =
+				(indices.next(), indices.next(), indices.next(), indices.next())
+			else {
+				return Err(err());
+			};
+			Cell::fromFields(mainIndex, subIndex, prop1, isHidden, unknownBits).ok_or_else(err)
+		}
+	}
+
+	impl Cell {
+		/// `None` when any of the fields doesn't fit its bits.
+		pub fn fromFields(
+			mainIndex: u32,
+			subIndex: u32,
+			prop1: u32,
+			isHidden: bool,
+			unknownBits: u32,
+		) -> Option<Self> {
+			if mainIndex > MAIN_INDEX_MAX
+				|| subIndex > SUB_INDEX_MAX
+				|| prop1 & !PROP1_MASK != 0
+				|| unknownBits & !UNKNOWN_BITS_MASK != 0
+			{
+				return None;
+			}
+			let mut cell = Self(unknownBits);
+			cell.setMainIndex(mainIndex);
+			cell.setSubIndex(subIndex);
+			cell.setProp1(prop1);
+			cell.setHidden(isHidden);
+			Some(cell)
+		}
+	}
+
+	impl Serialize for Cell {
+		fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+			serializer.collect_str(self)
+		}
+	}
+
+	/// A cell is read from its packed integer, its compact string or a `{ main, sub, prop, hidden, unknown }` table.
+	impl<'de> Deserialize<'de> for Cell {
+		fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+			#[derive(Deserialize)]
+			#[serde(deny_unknown_fields)]
+			struct CellFields {
+				main: u32,
+				sub: u32,
+				prop: u32,
+				#[serde(default)]
+				hidden: bool,
+				#[serde(default)]
+				unknown: u32,
+			}
+
+			struct CellVisitor;
+			impl<'de> de::Visitor<'de> for CellVisitor {
+				type Value = Cell;
+				fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+					f.write_str("a packed u32, a `main:sub:prop` string or a `{ main, sub, prop }` table")
+				}
+				fn visit_i64<E: de::Error>(self, packed: i64) -> Result<Cell, E> {
+					u32::try_from(packed)
+						.map(Cell)
+						.map_err(|_| E::invalid_value(de::Unexpected::Signed(packed), &self))
+				}
+				fn visit_u64<E: de::Error>(self, packed: u64) -> Result<Cell, E> {
+					u32::try_from(packed)
+						.map(Cell)
+						.map_err(|_| E::invalid_value(de::Unexpected::Unsigned(packed), &self))
+				}
+				fn visit_str<E: de::Error>(self, string: &str) -> Result<Cell, E> {
+					string.parse().map_err(|err| E::custom(format!("{err:?}")))
+				}
+				fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Cell, A::Error> {
+					let CellFields { main, sub, prop, hidden, unknown } =
+						CellFields::deserialize(MapAccessDeserializer::new(map))?;
+					Cell::fromFields(main, sub, prop, hidden, unknown).ok_or_else(|| {
+						de::Error::custom(format!(
+							"ds1 cell {{ main = {main}, sub = {sub}, prop = {prop} }} is out of range"
+						))
+					})
+				}
+			}
+
+			deserializer.deserialize_any(CellVisitor)
+		}
+	}
+
+	/// A layer as `width` columns of cells, indexed by `(x, y)`.
+	pub struct Grid<T> {
+		width: usize,
+		cells: T,
+	}
+
+	impl<T: AsRef<[Cell]>> Grid<T> {
+		#[inline(always)]
+		pub fn width(&self) -> usize {
+			self.width
+		}
+		#[inline(always)]
+		pub fn height(&self) -> usize {
+			self.cells.as_ref().len() / self.width
+		}
+		#[inline(always)]
+		pub fn cells(&self) -> &[Cell] {
+			self.cells.as_ref()
+		}
+		#[inline(always)]
+		pub fn get(&self, (x, y): (usize, usize)) -> Option<Cell> {
+			if x < self.width {
+				self.cells.as_ref().get(x + y * self.width).copied()
+			} else {
+				None
+			}
+		}
+		pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), Cell)> + '_ {
+			let width = self.width;
+			self.cells.as_ref().iter().enumerate().map(move |(i, &cell)| ((i % width, i / width), cell))
+		}
+	}
+
+	impl<T: AsMut<[Cell]>> Grid<T> {
+		#[inline(always)]
+		pub fn cellsMut(&mut self) -> &mut [Cell] {
+			self.cells.as_mut()
+		}
+	}
+
+	impl<T: AsRef<[Cell]>> ops::Index<(usize, usize)> for Grid<T> {
+		type Output = Cell;
+		#[inline(always)]
+		fn index(&self, (x, y): (usize, usize)) -> &Cell {
+			assert!(x < self.width);
+			&self.cells.as_ref()[x + y * self.width]
+		}
+	}
+
+	impl<T: AsRef<[Cell]> + AsMut<[Cell]>> ops::IndexMut<(usize, usize)> for Grid<T> {
+		#[inline(always)]
+		fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Cell {
+			assert!(x < self.width);
+			&mut self.cells.as_mut()[x + y * self.width]
 		}
 	}
 
 	#[inline(always)]
-	pub const fn existsTagLayer(tagType: i32) -> bool {
-		matches!(tagType, 1 | 2)
+	pub fn asCells(layer: &[u32]) -> &[Cell] {
+		unsafe { slice::from_raw_parts(layer.as_ptr().cast(), layer.len()) }
+	}
+	#[inline(always)]
+	pub fn asCellsMut(layer: &mut [u32]) -> &mut [Cell] {
+		unsafe { slice::from_raw_parts_mut(layer.as_mut_ptr().cast(), layer.len()) }
+	}
+
+	// `layers` go wall 1, orientation 1, ..., wall n, orientation n, floor 1, ..., floor m, shadow, [tag]
+	impl RootStruct {
+		#[inline(always)]
+		pub const fn width(&self) -> usize {
+			(self.xMax + 1) as _
+		}
+		#[inline(always)]
+		pub const fn height(&self) -> usize {
+			(self.yMax + 1) as _
+		}
+
+		#[inline(always)]
+		fn grid(&self, layerIndex: usize) -> Grid<&[Cell]> {
+			Grid { width: self.width(), cells: asCells(&self.layers[layerIndex]) }
+		}
+		#[inline(always)]
+		fn gridMut(&mut self, layerIndex: usize) -> Grid<&mut [Cell]> {
+			Grid { width: self.width(), cells: asCellsMut(&mut self.layers[layerIndex]) }
+		}
+		#[inline(always)]
+		const fn floorLayersStart(&self) -> usize {
+			self.numWallLayers as usize * 2
+		}
+		#[inline(always)]
+		const fn shadowLayerIndex(&self) -> usize {
+			self.floorLayersStart() + self.numFloors as usize
+		}
+
+		pub fn wallLayer(&self, i: usize) -> Grid<&[Cell]> {
+			assert!(i < self.numWallLayers as _);
+			self.grid(i * 2)
+		}
+		pub fn wallLayerMut(&mut self, i: usize) -> Grid<&mut [Cell]> {
+			assert!(i < self.numWallLayers as _);
+			self.gridMut(i * 2)
+		}
+		pub fn orientationLayer(&self, i: usize) -> Grid<&[Cell]> {
+			assert!(i < self.numWallLayers as _);
+			self.grid(i * 2 + 1)
+		}
+		pub fn orientationLayerMut(&mut self, i: usize) -> Grid<&mut [Cell]> {
+			assert!(i < self.numWallLayers as _);
+			self.gridMut(i * 2 + 1)
+		}
+		pub fn floorLayer(&self, i: usize) -> Grid<&[Cell]> {
+			assert!(i < self.numFloors as _);
+			self.grid(self.floorLayersStart() + i)
+		}
+		pub fn floorLayerMut(&mut self, i: usize) -> Grid<&mut [Cell]> {
+			assert!(i < self.numFloors as _);
+			self.gridMut(self.floorLayersStart() + i)
+		}
+		pub fn shadowLayer(&self) -> Grid<&[Cell]> {
+			self.grid(self.shadowLayerIndex())
+		}
+		pub fn shadowLayerMut(&mut self) -> Grid<&mut [Cell]> {
+			self.gridMut(self.shadowLayerIndex())
+		}
+		pub fn tagLayer(&self) -> Option<Grid<&[Cell]>> {
+			existsTagLayer(self.tagType).then(|| self.grid(self.shadowLayerIndex() + ONE_SHADOW_LAYER))
+		}
+		pub fn tagLayerMut(&mut self) -> Option<Grid<&mut [Cell]>> {
+			existsTagLayer(self.tagType).then(|| self.gridMut(self.shadowLayerIndex() + ONE_SHADOW_LAYER))
+		}
+
+		/// `layers`' names, as `NamedLayers` writes them: `wall1`, `orientation1`, ..., `floor1`, ..., `shadow`, `tag`.
+		pub fn layerNames(&self) -> Vec<String> {
+			let mut names = Vec::with_capacity(self.layers.len());
+			for i in 1..=self.numWallLayers {
+				names.extend([format!("wall{i}"), format!("orientation{i}")]);
+			}
+			names.extend((1..=self.numFloors).map(|i| format!("floor{i}")));
+			names.push("shadow".to_owned());
+			if existsTagLayer(self.tagType) {
+				names.push("tag".to_owned());
+			}
+			names
+		}
+	}
+
+	/// `layers` as a table of `wall1`, `orientation1`, ..., `floor1`, ..., `shadow` and `tag`, each written as one line
+	///   of compact cells (see `Cell`'s `Display`) per row. Serialize `RootStruct::withNamedLayers` to get them.
+	pub struct NamedLayers<'a>(&'a RootStruct);
+
+	impl Serialize for NamedLayers<'_> {
+		fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+			let ds1RootStruct = self.0;
+			let mut map = serializer.serialize_map(Some(ds1RootStruct.layers.len()))?;
+			for (i, name) in ds1RootStruct.layerNames().into_iter().enumerate() {
+				let layer = ds1RootStruct.grid(i);
+				let width = layer.iter().map(|(_, cell)| cell.to_string().len()).max().unwrap_or(0);
+				let mut rows = String::new();
+				for row in layer.cells().chunks(layer.width()) {
+					let row = Vec::from_iter(row.iter().map(|cell| format!("{cell:>width$}")));
+					rows += &row.join(" ");
+					rows.push('\n');
+				}
+				map.serialize_entry(&name, &rows)?;
+			}
+			map.end()
+		}
+	}
+
+	/// `RootStruct`'s fields in its order, save for `layers` that come last as `NamedLayers` (TOML can't have a table
+	///   before `object`, `group` and `path`).
+	#[derive(Serialize)]
+	pub struct WithNamedLayers<'a> {
+		version: i32,
+		xMax: i32,
+		yMax: i32,
+		actIndex: i32,
+		tagType: i32,
+		files: &'a [String],
+		unknown: Option<[u8; 2 * size_of::<i32>()]>,
+		numWallLayers: i32,
+		numFloors: i32,
+
+		#[serde(rename = "object")]
+		objects: &'a Option<Vec<Object>>,
+
+		#[serde(rename = "group")]
+		groups: &'a Option<Vec<Group>>,
+
+		#[serde(rename = "path")]
+		paths: &'a Option<Vec<Path>>,
+
+		layers: NamedLayers<'a>,
+	}
+
+	impl RootStruct {
+		pub fn withNamedLayers(&self) -> WithNamedLayers<'_> {
+			WithNamedLayers {
+				version: self.version,
+				xMax: self.xMax,
+				yMax: self.yMax,
+				actIndex: self.actIndex,
+				tagType: self.tagType,
+				files: &self.files,
+				unknown: self.unknown,
+				numWallLayers: self.numWallLayers,
+				numFloors: self.numFloors,
+				objects: &self.objects,
+				groups: &self.groups,
+				paths: &self.paths,
+				layers: NamedLayers(self),
+			}
+		}
+	}
+
+	/// `layers` as deserialized: with the counts and row lengths of named layers, for `RootStruct`'s other fields to
+	///   be checked against.
+	struct Layers {
+		layers: Vec<Box<[u32]>>,
+		named: Option<NamedShape>,
+	}
+	struct NamedShape {
+		numWallLayers: usize,
+		numFloors: usize,
+		hasTag: bool,
+		rowLens: Vec<(String, Vec<usize>)>,
+	}
+
+	fn deserializeLayers<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Layers, D::Error> {
+		// a named layer is either the multi-line string `NamedLayers` writes, or an array of rows of `Cell`s
+		// the cells, and how many there are in each row
+		struct NamedLayer(Vec<u32>, Vec<usize>);
+		impl<'de> Deserialize<'de> for NamedLayer {
+			fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+				struct NamedLayerVisitor;
+				impl<'de> de::Visitor<'de> for NamedLayerVisitor {
+					type Value = NamedLayer;
+					fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+						f.write_str("a string of whitespace-separated cells, one row per line, or an array of rows")
+					}
+					fn visit_str<E: de::Error>(self, rows: &str) -> Result<NamedLayer, E> {
+						let rows = Vec::from_iter(rows.lines().filter(|row| !row.trim().is_empty()).map(|row| {
+							Result::<Vec<_>, _>::from_iter(
+								row
+									.split_whitespace()
+									.map(|cell| cell.parse::<Cell>().map_err(|err| E::custom(format!("{err:?}")))),
+							)
+						}));
+						NamedLayer::fromRows(Result::<Vec<_>, _>::from_iter(rows)?)
+					}
+					fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<NamedLayer, A::Error> {
+						NamedLayer::fromRows(Vec::<Vec<Cell>>::deserialize(SeqAccessDeserializer::new(seq))?)
+					}
+				}
+				deserializer.deserialize_any(NamedLayerVisitor)
+			}
+		}
+		impl NamedLayer {
+			fn fromRows<E: de::Error>(rows: Vec<Vec<Cell>>) -> Result<Self, E> {
+				let rowLens = Vec::from_iter(rows.iter().map(Vec::len));
+				Ok(Self(Vec::from_iter(rows.into_iter().flatten().map(|Cell(cell)| cell)), rowLens))
+			}
+		}
+
+		struct LayersVisitor;
+		impl<'de> de::Visitor<'de> for LayersVisitor {
+			type Value = Layers;
+			fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+				f.write_str("an array of packed layers or a table of named layers")
+			}
+			fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+				Ok(Layers { layers: Vec::deserialize(SeqAccessDeserializer::new(seq))?, named: None })
+			}
+			fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+				let mut namedLayers = HashMap::<String, NamedLayer>::deserialize(MapAccessDeserializer::new(map))?;
+				let (mut layers, mut rowLens) = (Vec::new(), Vec::new());
+				let mut take = |name: &str, isRequired: bool| match namedLayers.remove(name) {
+					Some(NamedLayer(layer, layerRowLens)) => {
+						layers.push(layer.into_boxed_slice());
+						rowLens.push((name.to_owned(), layerRowLens));
+						Ok(true)
+					}
+					None if isRequired => Err(de::Error::custom(format!("missing ds1 layer {name:?}"))),
+					None => Ok(false),
+				};
+				let (mut numWallLayers, mut numFloors) = (0, 0);
+				while take(&format!("wall{}", numWallLayers + 1), false)? {
+					numWallLayers += 1;
+					take(&format!("orientation{numWallLayers}"), true)?;
+				}
+				while take(&format!("floor{}", numFloors + 1), false)? {
+					numFloors += 1;
+				}
+				take("shadow", true)?;
+				let hasTag = take("tag", false)?;
+				match namedLayers.keys().next() {
+					Some(name) => Err(de::Error::custom(format!("unexpected ds1 layer {name:?}"))),
+					None => {
+						Ok(Layers { layers, named: Some(NamedShape { numWallLayers, numFloors, hasTag, rowLens }) })
+					}
+				}
+			}
+		}
+
+		deserializer.deserialize_any(LayersVisitor)
+	}
+
+	/// `RootStruct` as it's deserialized, before its layers are checked against its dimensions and layer counts.
+	#[derive(Deserialize)]
+	struct UncheckedRootStruct {
+		version: i32,
+		xMax: i32,
+		yMax: i32,
+		actIndex: i32,
+		tagType: i32,
+		files: Vec<String>,
+		unknown: Option<[u8; 2 * size_of::<i32>()]>,
+		numWallLayers: i32,
+		numFloors: i32,
+
+		#[serde(deserialize_with = "deserializeLayers")]
+		layers: Layers,
+
+		#[serde(rename = "object")]
+		objects: Option<Vec<Object>>,
+
+		#[serde(rename = "group")]
+		groups: Option<Vec<Group>>,
+
+		#[serde(rename = "path")]
+		paths: Option<Vec<Path>>,
+	}
+
+	impl TryFrom<UncheckedRootStruct> for RootStruct {
+		type Error = String;
+		fn try_from(unchecked: UncheckedRootStruct) -> Result<Self, Self::Error> {
+			let UncheckedRootStruct {
+				version,
+				xMax,
+				yMax,
+				actIndex,
+				tagType,
+				files,
+				unknown,
+				numWallLayers,
+				numFloors,
+				layers: Layers { layers, named },
+				objects,
+				groups,
+				paths,
+			} = unchecked;
+			for (name, value) in
+				[("xMax", xMax), ("yMax", yMax), ("numWallLayers", numWallLayers), ("numFloors", numFloors)]
+			{
+				if value < 0 {
+					return Err(format!("ds1.{name} == {value} < 0"));
+				}
+			}
+			let [width, height] = [xMax, yMax].map(|coordMax| coordMax as usize + 1);
+			match named {
+				Some(NamedShape { numWallLayers: namedWallLayers, numFloors: namedFloors, hasTag, rowLens }) => {
+					if namedWallLayers != numWallLayers as usize {
+						return Err(format!("{namedWallLayers} wall layers != numWallLayers == {numWallLayers}"));
+					}
+					if namedFloors != numFloors as usize {
+						return Err(format!("{namedFloors} floor layers != numFloors == {numFloors}"));
+					}
+					if hasTag != existsTagLayer(tagType) {
+						return Err(format!(
+							"tagType == {tagType} {} a tag layer",
+							if hasTag { "doesn't have" } else { "has" }
+						));
+					}
+					for (name, rowLens) in rowLens {
+						if rowLens.len() != height {
+							return Err(format!("ds1 layer {name:?}: {} rows != yMax + 1 == {height}", rowLens.len()));
+						}
+						if let Some((row, len)) = rowLens.into_iter().enumerate().find(|&(_, len)| len != width) {
+							return Err(format!("ds1 layer {name:?}, row {row}: {len} cells != xMax + 1 == {width}"));
+						}
+					}
+				}
+				None => {
+					let numLayers = numWallLayers as usize * 2
+						+ numFloors as usize
+						+ ONE_SHADOW_LAYER
+						+ existsTagLayer(tagType) as usize;
+					if layers.len() != numLayers {
+						return Err(format!("{} ds1.layers != {numLayers}", layers.len()));
+					}
+					if let Some((i, layer)) =
+						layers.iter().enumerate().find(|(_, layer)| layer.len() != width.saturating_mul(height))
+					{
+						return Err(format!(
+							"ds1.layers[{i}]: {} cells != (xMax + 1) * (yMax + 1) == {}",
+							layer.len(),
+							width.saturating_mul(height)
+						));
+					}
+				}
+			}
+			Ok(Self {
+				version,
+				xMax,
+				yMax,
+				actIndex,
+				tagType,
+				files,
+				unknown,
+				numWallLayers,
+				numFloors,
+				layers,
+				objects,
+				groups,
+				paths,
+			})
+		}
 	}
 
 	impl RootStruct {
-		pub fn writeTo(&self, to: &mut impl Write) {
+		pub fn writeTo(&self, to: &mut impl Write) -> Result<(), Error> {
 			let &RootStruct {
 				version,
 				xMax,
@@ -116,72 +736,87 @@ pub mod ds1 {
 				ref groups,
 				ref paths,
 			} = self;
-			to.write_i32::<LE>(version).unwrap();
-			[xMax, yMax].iter().for_each(|&coordMax| to.write_i32::<LE>(coordMax).unwrap());
+			let numLayers =
+				numWallLayers * 2 + numFloors + ONE_SHADOW_LAYER as i32 + existsTagLayer(tagType) as i32;
+			if layers.len() != numLayers.max(0) as usize {
+				return Err(Error::Inconsistent(format!("{} ds1.layers != {numLayers}", layers.len())));
+			}
+			if let Some(layer) = layers.iter().find(|layer| layer.len() != self.width() * self.height()) {
+				return Err(Error::Inconsistent(format!(
+					"a ds1 layer of {} cells != ({xMax} + 1) * ({yMax} + 1)",
+					layer.len()
+				)));
+			}
+			to.write_i32::<LE>(version)?;
+			[xMax, yMax].iter().try_for_each(|&coordMax| to.write_i32::<LE>(coordMax))?;
 			if version >= 8 {
-				to.write_i32::<LE>(actIndex).unwrap();
+				to.write_i32::<LE>(actIndex)?;
 				if version >= 10 {
-					to.write_i32::<LE>(tagType).unwrap();
+					to.write_i32::<LE>(tagType)?;
 				}
 			}
-			to.write_i32::<LE>(files.len() as _).unwrap();
+			to.write_i32::<LE>(files.len() as _)?;
 			for file in files {
-				to.write_all(file.as_bytes()).unwrap();
-				to.write_u8(b'\0').unwrap();
+				to.write_all(file.as_bytes())?;
+				to.write_u8(b'\0')?;
 			}
 			if matches!(version, 9..=13) {
-				to.write_all(&unknown.unwrap()).unwrap();
+				to.write_all(
+					unknown
+						.as_ref()
+						.ok_or_else(|| Error::Inconsistent(format!("ds1.version == {version} needs ds1.unknown")))?,
+				)?;
 			}
-			to.write_i32::<LE>(numWallLayers).unwrap();
+			to.write_i32::<LE>(numWallLayers)?;
 			if version >= 16 {
-				to.write_i32::<LE>(numFloors).unwrap();
+				to.write_i32::<LE>(numFloors)?;
 			}
 			for layer in layers {
 				if cfg!(target_endian = "little") {
-					to.write_all(unsafe { slice::from_raw_parts(layer.as_ptr() as _, size_of_val(layer as &[_])) })
-						.unwrap();
+					to.write_all(unsafe { slice::from_raw_parts(layer.as_ptr() as _, size_of_val(layer as &[_])) })?;
 				} else {
 					for &cell in layer.iter() {
-						_ = to.write_u32::<LE>(cell);
+						to.write_u32::<LE>(cell)?;
 					}
 				}
 			}
 			let (objects, groups, paths) = (objects.asVec(), groups.asVec(), paths.asVec());
-			to.write_i32::<LE>(objects.len() as _).unwrap();
+			to.write_i32::<LE>(objects.len() as _)?;
 			for &Object { r#type, id, x, y, flags } in objects {
-				to.write_i32::<LE>(r#type).unwrap();
-				to.write_i32::<LE>(id).unwrap();
-				[x, y].iter().for_each(|&coord| to.write_i32::<LE>(coord).unwrap());
-				to.write_i32::<LE>(flags).unwrap();
+				to.write_i32::<LE>(r#type)?;
+				to.write_i32::<LE>(id)?;
+				[x, y].iter().try_for_each(|&coord| to.write_i32::<LE>(coord))?;
+				to.write_i32::<LE>(flags)?;
 			}
 			if version >= 12 {
 				if existsTagLayer(tagType) {
 					if version >= 18 {
-						to.writeZeros(size_of::<i32>());
+						to.writeZeros(size_of::<i32>())?;
 					}
-					to.write_i32::<LE>(groups.len() as _).unwrap();
+					to.write_i32::<LE>(groups.len() as _)?;
 					for &Group { x, y, width, height, unknown } in groups {
-						[x, y].iter().for_each(|&coord| to.write_i32::<LE>(coord).unwrap());
-						[width, height].iter().for_each(|&dimension| to.write_i32::<LE>(dimension).unwrap());
+						[x, y].iter().try_for_each(|&coord| to.write_i32::<LE>(coord))?;
+						[width, height].iter().try_for_each(|&dimension| to.write_i32::<LE>(dimension))?;
 						if version >= 13 {
-							to.write_i32::<LE>(unknown).unwrap();
+							to.write_i32::<LE>(unknown)?;
 						}
 					}
 				}
 				if version >= 14 {
-					to.write_i32::<LE>(paths.len() as _).unwrap();
+					to.write_i32::<LE>(paths.len() as _)?;
 					for &Path { x, y, ref nodes } in paths {
-						to.write_i32::<LE>(nodes.len() as _).unwrap();
-						[x, y].iter().for_each(|&coord| to.write_i32::<LE>(coord).unwrap());
+						to.write_i32::<LE>(nodes.len() as _)?;
+						[x, y].iter().try_for_each(|&coord| to.write_i32::<LE>(coord))?;
 						for &Node { x, y, action } in nodes {
-							[x, y].iter().for_each(|&coord| to.write_i32::<LE>(coord).unwrap());
+							[x, y].iter().try_for_each(|&coord| to.write_i32::<LE>(coord))?;
 							if version >= 15 {
-								to.write_i32::<LE>(action).unwrap()
+								to.write_i32::<LE>(action)?;
 							}
 						}
 					}
 				}
 			}
+			return Ok(());
 
 			trait OptionVecExt<T: 'static> {
 				fn asVec(&self) -> &Vec<T>;
@@ -194,79 +829,103 @@ pub mod ds1 {
 			}
 		}
 
-		pub fn new(cursor: &mut io::Cursor<impl AsRef<[u8]>>) -> Result<Self, VersionMismatchError> {
-			let version = cursor.read_i32::<LE>().unwrap();
+		pub fn new(cursor: &mut io::Cursor<impl AsRef<[u8]>>) -> Result<Self, Error> {
+			let version = cursor.readLE::<i32>()?;
 			if version < MINIMUM_VERSION {
-				return Err(VersionMismatchError { version });
-			}
-			let [xMax, yMax] = array::from_fn(|_| cursor.read_i32::<LE>().unwrap());
-			let actIndex = if version < 8 { 0 } else { cursor.read_i32::<LE>().unwrap() };
-			let tagType = if version < 10 { 0 } else { cursor.read_i32::<LE>().unwrap() };
-			let mut files = Vec::with_capacity(cursor.read_i32::<LE>().unwrap() as _);
-			for _ in 0..files.capacity() {
-				let ds1 = cursor.get_ref().as_ref();
-				let unreadDS1 = &ds1[cursor.position() as _..];
-				let nulPosition = memchr(b'\0', unreadDS1).unwrap();
-				files.push(String::from_utf8((&unreadDS1[..nulPosition]).to_vec()).unwrap());
+				return Err(Error::UnsupportedVersion(format!(
+					"ds1.version == {version:?} < {MINIMUM_VERSION:?}"
+				)));
+			}
+			let [xMax, yMax] = [cursor.readCount("ds1.xMax")?, cursor.readCount("ds1.yMax")?];
+			let actIndex = if version < 8 { 0 } else { cursor.readLE()? };
+			let tagType = if version < 10 { 0 } else { cursor.readLE()? };
+			let numFiles = cursor.readCount("ds1.numFiles")?;
+			let mut files = Vec::new();
+			for _ in 0..numFiles {
+				let offset = cursor.position();
+				let unreadDS1 = &cursor.get_ref().as_ref()[offset as _..];
+				let nulPosition = memchr(b'\0', unreadDS1).ok_or(Error::Truncated { offset, needed: 1 })?;
+				files.push(String::from_utf8(unreadDS1[..nulPosition].to_vec()).map_err(|err| {
+					Error::InvalidData { offset, reason: format!("ds1.files[{}]: {err}", files.len()) }
+				})?);
 				cursor.consume(nulPosition + 1);
 			}
-			let unknown = if matches!(version, 9..=13) { Some(cursor.read_u8_array()) } else { None };
-			let numWallLayers = cursor.read_i32::<LE>().unwrap();
-			let numFloors = if version < 16 { 1 } else { cursor.read_i32::<LE>().unwrap() };
+			let unknown = if matches!(version, 9..=13) { Some(cursor.read_u8_array()?) } else { None };
+			let layersOffset = cursor.position();
+			let numWallLayers = cursor.readCount("ds1.numWallLayers")?;
+			let numFloors = if version < 16 { 1 } else { cursor.readCount("ds1.numFloors")? };
+			let overflowing =
+				|| Error::InvalidData { offset: layersOffset, reason: "ds1.layers overflow".to_owned() };
+			let numLayers = numWallLayers
+				.checked_mul(2)
+				.and_then(|len| len.checked_add(numFloors + ONE_SHADOW_LAYER + existsTagLayer(tagType) as usize))
+				.ok_or_else(overflowing)?;
+			let layerLen = (xMax + 1).checked_mul(yMax + 1).ok_or_else(overflowing)?;
+			cursor.ensureRemaining(
+				numLayers
+					.checked_mul(layerLen)
+					.and_then(|len| len.checked_mul(size_of::<u32>()))
+					.ok_or_else(overflowing)?,
+			)?;
 			let mut layers = Vec::new();
-			for _ in 0..numWallLayers * 2 + numFloors + ONE_SHADOW_LAYER as i32 + existsTagLayer(tagType) as i32
-			{
-				let mut layer = Vec::withLen(((xMax + 1) * (yMax + 1)) as _).into_boxed_slice();
-				cursor.read_u32_into::<LE>(&mut layer).unwrap();
+			for _ in 0..numLayers {
+				let mut layer = Vec::withLen(layerLen).into_boxed_slice();
+				cursor.read_u32_into::<LE>(&mut layer)?;
 				layers.push(layer);
 			}
-			let mut objects = Vec::with_capacity(cursor.read_i32::<LE>().unwrap() as _);
-			for _ in 0..objects.capacity() {
+			let numObjects = cursor.readCount("ds1.numObjects")?;
+			let mut objects = Vec::new();
+			for _ in 0..numObjects {
 				objects.push(Object {
-					r#type: cursor.read_i32::<LE>().unwrap(),
-					id: cursor.read_i32::<LE>().unwrap(),
-					x: cursor.read_i32::<LE>().unwrap(),
-					y: cursor.read_i32::<LE>().unwrap(),
-					flags: cursor.read_i32::<LE>().unwrap(),
+					r#type: cursor.readLE()?,
+					id: cursor.readLE()?,
+					x: cursor.readLE()?,
+					y: cursor.readLE()?,
+					flags: cursor.readLE()?,
 				});
 			}
-			let mut groups = Vec::with_capacity(if version >= 12 && existsTagLayer(tagType) {
+			let numGroups = if version >= 12 && existsTagLayer(tagType) {
 				if version >= 18 {
-					cursor.consumeZeros(size_of::<i32>());
+					cursor.consumeZeros(size_of::<i32>())?;
 				}
-				cursor.read_i32::<LE>().unwrap() as _
+				cursor.readCount("ds1.numGroups")?
 			} else {
 				0
-			});
-			for _ in 0..groups.capacity() {
-				let x = cursor.read_i32::<LE>().unwrap();
+			};
+			let mut groups = Vec::new();
+			for _ in 0..numGroups {
+				let x = cursor.readLE::<i32>()?;
 				groups.push(Group {
 					y: if cursor.remaining() == 0 {
-						assert_eq!(x, 0);
-						cursor.seek(SeekFrom::Current(-(size_of_val(&x) as i64))).unwrap();
+						if x != 0 {
+							return Err(Error::NonZeroPadding {
+								offset: cursor.position() - size_of_val(&x) as u64,
+								len: size_of_val(&x),
+							});
+						}
+						cursor.seek(SeekFrom::Current(-(size_of_val(&x) as i64)))?;
 						break;
 					} else {
-						cursor.read_i32::<LE>().unwrap()
+						cursor.readLE()?
 					},
 					x,
-					width: cursor.read_i32::<LE>().unwrap(),
-					height: cursor.read_i32::<LE>().unwrap(),
-					unknown: if version < 13 { 0 } else { cursor.read_i32::<LE>().unwrap() },
+					width: cursor.readLE()?,
+					height: cursor.readLE()?,
+					unknown: if version < 13 { 0 } else { cursor.readLE()? },
 				});
 			}
-			let mut paths = Vec::with_capacity(if version < 14 || cursor.remaining() == 0 {
-				0
-			} else {
-				cursor.read_i32::<LE>().unwrap() as _
-			});
-			for _ in 0..paths.capacity() {
-				let mut nodes = Vec::with_capacity(cursor.read_i32::<LE>().unwrap() as _);
-				let [x, y] = array::from_fn(|_| cursor.read_i32::<LE>().unwrap());
-				for _ in 0..nodes.capacity() {
+			let numPaths =
+				if version < 14 || cursor.remaining() == 0 { 0 } else { cursor.readCount("ds1.numPaths")? };
+			let mut paths = Vec::new();
+			for _ in 0..numPaths {
+				let numNodes = cursor.readCount(&format!("ds1.paths[{}].numNodes", paths.len()))?;
+				let [x, y] = [cursor.readLE()?, cursor.readLE()?];
+				let mut nodes = Vec::new();
+				for _ in 0..numNodes {
 					nodes.push(Node {
-						x: cursor.read_i32::<LE>().unwrap(),
-						y: cursor.read_i32::<LE>().unwrap(),
-						action: if version < 15 { 1 } else { cursor.read_i32::<LE>().unwrap() },
+						x: cursor.readLE()?,
+						y: cursor.readLE()?,
+						action: if version < 15 { 1 } else { cursor.readLE()? },
 					});
 				}
 				paths.push(Path { x, y, nodes });
@@ -290,14 +949,14 @@ pub mod ds1 {
 
 			Ok(Self {
 				version,
-				xMax,
-				yMax,
+				xMax: xMax as _,
+				yMax: yMax as _,
 				actIndex,
 				tagType,
 				files,
 				unknown,
-				numWallLayers,
-				numFloors,
+				numWallLayers: numWallLayers as _,
+				numFloors: numFloors as _,
 				layers,
 				objects: objects.intо(),
 				groups: groups.intо(),
@@ -312,549 +971,5385 @@ pub const PAL_LEN: usize = 256 * RGB_SIZE;
 pub const RGBCUBE_VOLUME: usize = 2_usize.pow(RGB_SIZE as u32 * u8::BITS);
 pub const RGBA_SIZE: usize = RGB_SIZE + 1;
 
-pub mod dt1 {
+pub mod palette {
 	use {
-		super::{
-			CopyExt, Image, MinAssign_MaxAssign_Ext, ReadExt, TileColumns, TilesIterator, UsizeExt, Vec2,
-			Vec2Ext, WriteExt, FULLY_TRANSPARENT, WIDTH, X, Y,
-		},
-		byteorder::{ReadBytesExt, WriteBytesExt, LE},
-		core::{cmp::max, fmt, iter, mem, ops},
+		super::{Error, FULLY_TRANSPARENT, PAL_LEN, RGBCUBE_VOLUME, RGB_SIZE},
+		core::{array, fmt, mem::size_of, num::NonZeroUsize, str::FromStr},
 		serde::{Deserialize, Serialize},
 		std::{
-			fs::File,
-			io::{self, BufRead, Cursor, Write},
+			fs,
+			io::{self, Read, Write},
+			path::{Path, PathBuf},
+			sync::Mutex,
+			thread,
 		},
 	};
 
-	#[derive(Serialize, Deserialize)]
-	pub struct Metadata {
-		pub fileHeader: FileHeader,
+	pub const NUM_COLORS: usize = PAL_LEN / RGB_SIZE;
+	pub type Color = [u8; RGB_SIZE];
+	/// The index every palette index becomes.
+	pub type Table = [u8; NUM_COLORS];
+	/// The index every pair becomes, `over` drawn on top of `under` at `(under << 8) | over`.
+	pub type BlendTable = [u8; NUM_COLORS * NUM_COLORS];
 
-		#[serde(rename = "tile")]
-		pub tiles: Vec<Tile>,
+	/// Where `color` is in a palInverse, `2_-_pngPAL_into_palInverse`'s nearest index of every RGB color.
+	pub fn cubeIndex([red, green, blue]: Color) -> usize {
+		(usize::from(red) << (2 * u8::BITS)) | (usize::from(green) << u8::BITS) | usize::from(blue)
 	}
 
-	#[derive(Serialize, Deserialize)]
-	pub struct FileHeader {
-		pub version: [i32; 2],
-		pub tileHeadersPointer: i32,
+	// Rec. 601, as most everything of the game's era weighs them
+	fn luma([red, green, blue]: Color) -> f64 {
+		0.299 * f64::from(red) + 0.587 * f64::from(green) + 0.114 * f64::from(blue)
 	}
 
-	const EXPECTED_VERSION: [i32; 2] = [7, 6];
-	pub struct VersionMismatchError {
-		version: [i32; 2],
+	/// The files a palette can be read from and written to.
+	#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+	pub enum Format {
+		/// Diablo II's `pal.dat`: 256 colors in BGR order.
+		#[default]
+		PalDat,
+		/// A PNG's PLTE chunk as is, 256 colors in RGB order; what every `--png-pal` takes.
+		PngPAL,
+		/// An indexed PNG's PLTE; written as a 16x16 swatch of every index.
+		Png,
+		/// JASC (Paint Shop Pro) `.pal`.
+		Jasc,
+		/// GIMP `.gpl`.
+		Gimp,
+		/// Photoshop `.act`: a `PngPAL` plus the number of colors and the transparent index.
+		Act,
+		/// An `RRGGBB` line per color, as Lospec has them.
+		Hex,
+	}
+	impl Format {
+		pub const ALL: [Self; 7] =
+			[Self::PalDat, Self::PngPAL, Self::Png, Self::Jasc, Self::Gimp, Self::Act, Self::Hex];
+
+		pub const fn name(self) -> &'static str {
+			match self {
+				Self::PalDat => "pal-dat",
+				Self::PngPAL => "png-pal",
+				Self::Png => "png",
+				Self::Jasc => "jasc",
+				Self::Gimp => "gimp",
+				Self::Act => "act",
+				Self::Hex => "hex",
+			}
+		}
+
+		// `.dat` is both Diablo II's and this project's own pngPAL; the extension can't tell them apart
+		pub fn fromPath(path: &Path) -> Option<Self> {
+			let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+			Some(match &*extension {
+				"png" => Self::Png,
+				"pal" => Self::Jasc,
+				"gpl" => Self::Gimp,
+				"act" => Self::Act,
+				"hex" => Self::Hex,
+				_ => return None,
+			})
+		}
 	}
-	impl fmt::Debug for VersionMismatchError {
+	impl fmt::Display for Format {
 		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-			write!(f, "dt1.fileHeader.version == {:?} != {EXPECTED_VERSION:?}", self.version)
+			f.write_str(self.name())
 		}
 	}
-
-	pub const NUM_SUBTILES_PER_LINE: usize = 5;
-	const NUM_SUBTILES: usize = NUM_SUBTILES_PER_LINE.pow(2);
-	const FILEHEADER_SIZE: i32 = 276;
-	const TILEHEADER_SIZE: i32 = 96;
-	const BLOCKHEADER_SIZE: i32 = 20;
-	const ISOMETRIC: [u8; 2] = [1, 0];
-	const RLE_ISOMETRIC: [u8; 2] = [5, 32];
-	const RLE: [u8; 2] = [1, 16];
-
-	#[derive(Serialize, Deserialize)]
-	pub struct Tile {
-		pub direction: i32,
-		pub roofHeight: i16,
-		pub materialFlags: [u8; 2],
-		pub height: i32,
-		pub width: i32,
-		pub orientation: i32,
-		pub mainIndex: i32,
-		pub subIndex: i32,
-		pub rarityOrFrameIndex: i32,
-		pub unknown: [u8; 4],
-		pub subtileFlags: [u8; NUM_SUBTILES],
-		pub blockHeadersPointer: i32,
-		pub blocksDataLength: i32,
-		pub usuallyZeros: [u8; 4],
-
-		#[serde(rename = "block")]
-		pub blocks: Vec<Block>,
+	impl FromStr for Format {
+		type Err = String;
+		fn from_str(s: &str) -> Result<Self, Self::Err> {
+			Self::ALL.into_iter().find(|format| format.name() == s).ok_or_else(|| {
+				format!("{s:?} isn't one of {}", Vec::from_iter(Self::ALL.map(Self::name)).join(", "))
+			})
+		}
 	}
 
-	#[derive(Serialize, Deserialize)]
-	pub struct Block {
-		pub x: i16,
-		pub y: i16,
-		pub gridX: u8,
-		pub gridY: u8,
-		pub format: [u8; 2],
-		pub length: i32,
-		pub fileOffset: i32,
+	/// 256 colors in RGB order, and which of them is drawn as nothing at all.
+	#[derive(Clone, Debug, PartialEq, Eq)]
+	pub struct Palette {
+		pub colors: [Color; NUM_COLORS],
+		pub transparent: u8,
 	}
-
-	impl Block {
-		#[inline(always)]
-		pub fn drawFn<T: DrawDestination>(&self) -> DrawFn<T> {
-			if self.format == ISOMETRIC {
-				DrawDestination::drawBlockIsometric
-			} else {
-				DrawDestination::drawBlockRLE
+	impl Palette {
+		/// A PLTE's colors, the ones past its end black.
+		pub fn fromPngPAL(pngPAL: &[u8]) -> Self {
+			let mut colors = [[0; RGB_SIZE]; NUM_COLORS];
+			for (color, chunk) in colors.iter_mut().zip(pngPAL.chunks_exact(RGB_SIZE)) {
+				color.copy_from_slice(chunk);
 			}
+			Self { colors, transparent: FULLY_TRANSPARENT }
 		}
-	}
 
-	impl Tile {
-		#[inline(always)]
-		pub fn blockHeight(&self) -> usize {
-			match self.orientation {
-				FLOOR_ORIENTATION | ROOF_ORIENTATION => FLOOR_ROOF_BLOCKHEIGHT,
-				_ => MAX_BLOCKHEIGHT,
-			}
+		pub fn pngPAL(&self) -> Vec<u8> {
+			self.colors.concat()
 		}
-	}
 
-	impl Metadata {
-		pub fn new(dt1: &[u8]) -> Result<Self, VersionMismatchError> {
-			let mut cursor = io::Cursor::new(dt1);
-			let version = [cursor.read_i32::<LE>().unwrap(), cursor.read_i32::<LE>().unwrap()];
-			if version != EXPECTED_VERSION {
-				return Err(VersionMismatchError { version });
-			}
-			cursor.consumeZeros(260);
-			let mut tiles = Vec::with_capacity(cursor.read_i32::<LE>().unwrap() as _);
-			let tileHeadersPointer = cursor.read_i32::<LE>().unwrap();
-			assert_eq!(cursor.position(), tileHeadersPointer as _);
-			for _ in 0..tiles.capacity() {
-				tiles.push(Tile {
-					direction: cursor.read_i32::<LE>().unwrap(),
-					roofHeight: cursor.read_i16::<LE>().unwrap(),
-					materialFlags: cursor.read_u8_array(),
-					height: cursor.read_i32::<LE>().unwrap(),
-					width: cursor.read_i32::<LE>().unwrap(),
-					orientation: {
-						cursor.consumeZeros(4);
-						cursor.read_i32::<LE>().unwrap()
-					},
-					mainIndex: cursor.read_i32::<LE>().unwrap(),
-					subIndex: cursor.read_i32::<LE>().unwrap(),
-					rarityOrFrameIndex: cursor.read_i32::<LE>().unwrap(),
-					unknown: cursor.read_u8_array(),
-					subtileFlags: cursor.read_u8_array(),
-					blockHeadersPointer: {
-						cursor.consumeZeros(7);
-						cursor.read_i32::<LE>().unwrap()
-					},
-					blocksDataLength: cursor.read_i32::<LE>().unwrap(),
-					blocks: Vec::with_capacity(cursor.read_i32::<LE>().unwrap() as _),
-					usuallyZeros: {
-						cursor.consumeZeros(4);
-						cursor.read_u8_array()
-					},
-				});
-				cursor.consumeZeros(4);
-			}
-			assert_eq!(tiles.len(), tiles.capacity());
-			for tile in &mut tiles {
-				assert_eq!(cursor.position(), tile.blockHeadersPointer as _);
-				let mut totalLength = 0;
-				let blocks = &mut tile.blocks;
-				for _ in 0..blocks.capacity() {
-					blocks.push(Block {
-						x: cursor.read_i16::<LE>().unwrap(),
-						y: cursor.read_i16::<LE>().unwrap(),
-						gridX: {
-							cursor.consumeZeros(2);
-							cursor.read_u8().unwrap()
-						},
-						gridY: cursor.read_u8().unwrap(),
-						format: cursor.read_u8_array(),
-						length: cursor.read_i32::<LE>().unwrap().alsoAddTo(&mut totalLength),
-						fileOffset: {
-							cursor.consumeZeros(2);
-							cursor.read_i32::<LE>().unwrap()
-						},
-					});
-					#[allow(non_camel_case_types)]
-					trait Copy_AddAssign_Ext {
-						fn alsoAddTo(self, to: &mut Self) -> Self;
-					}
-					impl<T: Copy + CopyExt + ops::AddAssign> Copy_AddAssign_Ext for T {
-						#[inline(always)]
-						fn alsoAddTo(self, to: &mut Self) -> Self {
-							self.also(|&Δ| *to += Δ)
-						}
+		/// A tRNS chunk's alphas: every index up to `transparent` opaque, `transparent` itself not at all, the ones
+		///   past it left opaque by omission.
+		pub fn trns(&self) -> Vec<u8> {
+			let mut alphas = vec![u8::MAX; usize::from(self.transparent)];
+			alphas.push(0);
+			alphas
+		}
+
+		/// FNV-1a of the colors, what a palInverse's header and the palInverse cache know a palette by.
+		pub fn hash(&self) -> u64 {
+			self
+				.colors
+				.iter()
+				.flatten()
+				.fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+		}
+
+		pub fn read(format: Format, bytes: &[u8]) -> Result<Self, Error> {
+			match format {
+				Format::PalDat => {
+					let mut palette = Self::fromPngPAL(exactly(bytes, PAL_LEN)?);
+					for color in &mut palette.colors {
+						color.reverse();
+					}
+					Ok(palette)
+				}
+				Format::PngPAL => Ok(Self::fromPngPAL(exactly(bytes, PAL_LEN)?)),
+				Format::Png => {
+					let png = png::Decoder::new(bytes)
+						.read_info()
+						.map_err(|err| Error::InvalidData { offset: 0, reason: err.to_string() })?;
+					let plte = png.info().palette.as_deref().ok_or_else(|| Error::InvalidData {
+						offset: 0,
+						reason: "a PNG without a PLTE chunk".to_owned(),
+					})?;
+					Ok(Self::fromPngPAL(plte))
+				}
+				Format::Act => {
+					let palette =
+						Self::fromPngPAL(bytes.get(..PAL_LEN).ok_or(Error::Truncated {
+							offset: bytes.len() as _,
+							needed: (PAL_LEN - bytes.len()) as _,
+						})?);
+					// the 772-byte form adds a big-endian count of the colors, then the transparent index or 0xFFFF
+					match bytes[PAL_LEN..] {
+						[] | [_, _, u8::MAX, u8::MAX] => Ok(palette),
+						[_, _, 0, transparent] => Ok(Self { transparent, ..palette }),
+						ref trailing => Err(Error::TrailingBytes { offset: PAL_LEN as _, len: trailing.len() }),
 					}
 				}
-				assert_eq!(blocks.len(), blocks.capacity());
-				cursor.consume(totalLength as _);
+				Format::Jasc | Format::Gimp | Format::Hex => {
+					let text = std::str::from_utf8(bytes).map_err(|err| Error::InvalidData {
+						offset: err.valid_up_to() as _,
+						reason: err.to_string(),
+					})?;
+					Self::fromLines(format, text)
+				}
 			}
-			assert_eq!(cursor.position(), dt1.len() as _);
-			Ok(Self { fileHeader: FileHeader { version, tileHeadersPointer }, tiles })
 		}
 
-		pub fn writeWithBlockDataFromTileImage(&self, tileImage: &Image, to: &mut File) {
-			let (Self { fileHeader, tiles }, cursor) =
-				(self, &mut Cursor::new(Vec::with_capacity(6 * 1024 * 1024)));
-			fileHeader.version.iter().for_each(|&versionElem| cursor.write_i32::<LE>(versionElem).unwrap());
-			cursor.writeZeros(260);
-			cursor.write_i32::<LE>(tiles.len() as _).unwrap();
-			cursor.write_i32::<LE>(fileHeader.tileHeadersPointer).unwrap();
-			let (points, mut blockHeadersPointer) = (
-				&mut TilesIterator::new(TILEWIDTH, tileImage),
-				FILEHEADER_SIZE + tiles.len() as i32 * TILEHEADER_SIZE,
-			);
-			for tile in tiles {
-				let &Tile {
-					direction,
-					roofHeight,
-					ref materialFlags,
-					height,
-					width,
-					orientation,
-					mainIndex,
-					subIndex,
-					rarityOrFrameIndex,
-					ref unknown,
-					ref subtileFlags,
-					blockHeadersPointer: _,
-					blocksDataLength: _,
-					ref usuallyZeros,
-					ref blocks,
-				} = tile;
-				let blocksDataLength = {
-					let [mut startY, mut endY, blockHeight] = [i16::MAX, i16::MIN, tile.blockHeight() as _];
-					for &Block { y, .. } in blocks {
-						startY.minAssign(y);
-						endY.maxAssign(y + blockHeight);
+		fn fromLines(format: Format, text: &str) -> Result<Self, Error> {
+			let (pngPAL, mut offset) = (&mut Vec::with_capacity(PAL_LEN), 0);
+			let expectedHeader: &[&str] = match format {
+				Format::Jasc => &["JASC-PAL", "0100"],
+				Format::Gimp => &["GIMP Palette"],
+				_ => &[],
+			};
+			for (i, line) in text.split_inclusive('\n').enumerate() {
+				let (lineOffset, trimmed) = (offset, line.trim());
+				offset += line.len();
+				let invalid = |reason: String| Error::InvalidData { offset: lineOffset as _, reason };
+				if let Some(&header) = expectedHeader.get(i) {
+					if trimmed != header {
+						return Err(invalid(format!("{trimmed:?} where {format} has {header:?}")));
 					}
-					let (mut fileOffset, position, point) = (
-						blocks.len() as i32 * BLOCKHEADER_SIZE,
-						cursor.position(),
-						points
-							.next(((endY - startY) as usize).nextMultipleOf(FLOOR_ROOF_BLOCKHEIGHT))
-							.add([0, 0_usize.wrapping_sub(startY as _)]),
-					);
-					cursor.set_position(blockHeadersPointer as _);
-					for &Block { x, y, gridX, gridY, format, length: _, fileOffset: _ } in blocks {
-						let (position, point) = (cursor.position(), point.add([x as _, y as _]));
-						cursor.set_position((blockHeadersPointer + fileOffset) as _);
-						let length = {
-							let position = cursor.position();
-							if format == ISOMETRIC {
-								cursor.writeBlockDataIsometric(point, tileImage);
-							} else {
-								cursor.writeBlockDataRLE(
-									point,
-									match format {
-										RLE => MAX_BLOCKHEIGHT,
-										RLE_ISOMETRIC => NBPIX.len(),
-										_ => unreachable!(),
-									},
-									tileImage,
-								);
+					continue;
+				}
+				let color = match format {
+					// the number of colors; there's no trusting it over the lines that follow
+					Format::Jasc if i == expectedHeader.len() => continue,
+					Format::Jasc => {
+						let components = Vec::from_iter(trimmed.split_whitespace().map(u8::from_str));
+						match &*components {
+							[Ok(red), Ok(green), Ok(blue)] => [*red, *green, *blue],
+							_ => return Err(invalid(format!("{trimmed:?} isn't `R G B`"))),
+						}
+					}
+					Format::Gimp => {
+						if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.contains(':') {
+							continue;
+						}
+						// what follows the components is the color's name
+						let components = Vec::from_iter(trimmed.split_whitespace().take(RGB_SIZE).map(u8::from_str));
+						match &*components {
+							[Ok(red), Ok(green), Ok(blue)] => [*red, *green, *blue],
+							_ => return Err(invalid(format!("{trimmed:?} isn't `R G B name`"))),
+						}
+					}
+					_ => {
+						if trimmed.is_empty() {
+							continue;
+						}
+						let hex = trimmed.trim_start_matches('#');
+						match u32::from_str_radix(hex, 16) {
+							Ok(rgb) if hex.len() == 2 * RGB_SIZE => {
+								let [_, red, green, blue] = rgb.to_be_bytes();
+								[red, green, blue]
 							}
-							(cursor.position() - position) as i32
-						};
-						cursor.set_position(position);
-						cursor.write_i16::<LE>(x).unwrap();
-						cursor.write_i16::<LE>(y).unwrap();
-						cursor.writeZeros(2);
-						cursor.write_u8(gridX).unwrap();
-						cursor.write_u8(gridY).unwrap();
-						cursor.write_all(&format).unwrap();
-						cursor.write_i32::<LE>(length).unwrap();
-						cursor.writeZeros(2);
-						cursor.write_i32::<LE>(fileOffset).unwrap();
-						fileOffset += length;
+							_ => return Err(invalid(format!("{trimmed:?} isn't `RRGGBB`"))),
+						}
 					}
-					cursor.set_position(position);
-					fileOffset
 				};
-				cursor.write_i32::<LE>(direction).unwrap();
-				cursor.write_i16::<LE>(roofHeight).unwrap();
-				cursor.write_all(materialFlags).unwrap();
-				cursor.write_i32::<LE>(height).unwrap();
-				cursor.write_i32::<LE>(width).unwrap();
-				cursor.writeZeros(4);
-				cursor.write_i32::<LE>(orientation).unwrap();
-				cursor.write_i32::<LE>(mainIndex).unwrap();
-				cursor.write_i32::<LE>(subIndex).unwrap();
-				cursor.write_i32::<LE>(rarityOrFrameIndex).unwrap();
-				cursor.write_all(unknown).unwrap();
-				cursor.write_all(subtileFlags).unwrap();
-				cursor.writeZeros(7);
-				cursor.write_i32::<LE>(blockHeadersPointer).unwrap();
-				cursor.write_i32::<LE>(blocksDataLength).unwrap();
-				cursor.write_i32::<LE>(blocks.len() as _).unwrap();
-				cursor.writeZeros(4);
-				cursor.write_all(usuallyZeros).unwrap();
-				cursor.writeZeros(4);
-				blockHeadersPointer += blocksDataLength;
+				if pngPAL.len() == PAL_LEN {
+					return Err(invalid(format!("more than {NUM_COLORS} colors")));
+				}
+				pngPAL.extend(color);
 			}
-			to.write_all(cursor.get_ref()).unwrap();
+			Ok(Self::fromPngPAL(pngPAL))
+		}
 
-			trait WriteBlockDataExt {
-				fn writeBlockDataIsometric(&mut self, point: Vec2, tileImage: &Image);
-				fn writeBlockDataRLE(&mut self, point: Vec2, blockHeight: usize, tileImage: &Image);
-			}
-			impl<T: Write> WriteBlockDataExt for T {
-				#[inline(always)]
-				fn writeBlockDataIsometric(&mut self, point: Vec2, tileImage: &Image) {
-					let mut i = point[Y] * tileImage.width + point[X];
-					for (&xjump, &nbpix) in iter::zip(XJUMP, NBPIX) {
-						_ = self.write_all(&tileImage.data[i + xjump..][..nbpix]);
-						i += tileImage.width;
+		pub fn write(&self, format: Format, to: &mut impl Write) -> io::Result<()> {
+			match format {
+				Format::PalDat => to.write_all(&Vec::from_iter(
+					self.colors.iter().flat_map(|&[red, green, blue]| [blue, green, red]),
+				)),
+				Format::PngPAL => to.write_all(&self.pngPAL()),
+				Format::Png => {
+					const SIDE: u32 = 16;
+					let mut png = png::Encoder::new(to, SIDE, SIDE);
+					png.set_color(png::ColorType::Indexed);
+					png.set_palette(self.pngPAL());
+					png.set_trns(self.trns());
+					png.write_header()?.write_image_data(&Vec::from_iter(0..=u8::MAX))?;
+					Ok(())
+				}
+				Format::Act => {
+					to.write_all(&self.pngPAL())?;
+					to.write_all(&[(NUM_COLORS >> u8::BITS) as _, NUM_COLORS as _, 0, self.transparent])
+				}
+				Format::Jasc => {
+					write!(to, "JASC-PAL\r\n0100\r\n{NUM_COLORS}\r\n")?;
+					for [red, green, blue] in self.colors {
+						write!(to, "{red} {green} {blue}\r\n")?;
 					}
+					Ok(())
 				}
-				#[inline(always)]
-				fn writeBlockDataRLE(&mut self, point: Vec2, blockHeight: usize, tileImage: &Image) {
-					let (mut i, imageData) = ((point[Y] - 1) * tileImage.width + point[X], &tileImage.data);
-					for Δy in 0..blockHeight {
-						let ([mut xjump, nbpix], mut xsolid) = (
-							if blockHeight == MAX_BLOCKHEIGHT {
-								[0, BLOCKWIDTH as u8]
-							} else {
-								[XJUMP[Δy] as _, NBPIX[Δy] as _]
-							},
-							0,
-						);
-						i += tileImage.width;
-						let mut i = i + xjump as usize;
-						for Δx in 1..=nbpix {
-							let [mut nextXJump, mut nextXSolid] = [xjump, xsolid];
-							if imageData[i] == FULLY_TRANSPARENT {
-								nextXJump = xjump + 1;
-							} else {
-								nextXSolid = xsolid + 1;
-							};
-							if xsolid != 0 && nextXJump != xjump && {
-								xsolid = nextXSolid;
-								true
-							} || Δx == nbpix && nextXSolid != 0 && {
-								[xjump, xsolid] = [nextXJump, nextXSolid - 1];
-								true
-							} {
-								_ = self.write_all(&[xjump, nextXSolid]);
-								_ = self.write_all(&imageData[i - xsolid as usize..][..nextXSolid as _]);
-								nextXJump -= xjump;
-								nextXSolid = 0;
-							}
-							[xjump, xsolid] = [nextXJump, nextXSolid];
-							i += 1;
-						}
-						self.writeZeros(2);
+				Format::Gimp => {
+					writeln!(to, "GIMP Palette\nName: d2sw\nColumns: 16\n#")?;
+					for (i, [red, green, blue]) in self.colors.into_iter().enumerate() {
+						writeln!(to, "{red:3} {green:3} {blue:3}\tIndex {i}")?;
 					}
+					Ok(())
+				}
+				Format::Hex => {
+					for [red, green, blue] in self.colors {
+						writeln!(to, "{red:02x}{green:02x}{blue:02x}")?;
+					}
+					Ok(())
 				}
 			}
 		}
-	}
 
-	pub const XJUMP: &[usize] = &[14, 12, 10, 8, 6, 4, 2, 0, 2, 4, 6, 8, 10, 12, 14];
-	pub const NBPIX: &[usize] = &[4, 8, 12, 16, 20, 24, 28, 32, 28, 24, 20, 16, 12, 8, 4];
+		/// The `pal.dat` of the act a DS1's `actIndex` names, under `data/global/palette` (looked up
+		///   case-insensitively).
+		pub fn ofAct(paletteDir: &Path, actIndex: i32) -> Result<Self, Error> {
+			let path = actPalDatPath(paletteDir, actIndex).ok_or_else(|| {
+				Error::Role(format!("no ACT{}/pal.dat under {}", actIndex + 1, paletteDir.display()))
+			})?;
+			Self::read(Format::PalDat, &fs::read(&path)?)
+		}
 
-	type DrawFn<ImplDrawDestination> = fn(&mut ImplDrawDestination, x0: usize, y0: usize, data: &[u8]);
+		/// The index of the color nearest to `color`, the transparent one aside.
+		pub fn nearest(&self, color: Color) -> u8 {
+			let squaredDist = |other: &Color| -> u32 {
+				(0..RGB_SIZE).map(|i| u32::from(color[i].abs_diff(other[i])).pow(2)).sum()
+			};
+			self
+				.colors
+				.iter()
+				.enumerate()
+				.filter(|&(i, _)| i != usize::from(self.transparent))
+				.min_by_key(|(_, other)| squaredDist(other))
+				.map_or(self.transparent, |(i, _)| i as _)
+		}
 
-	pub trait DrawDestination {
-		fn width(&self) -> usize;
-		fn putpixel(&mut self, atIndex: usize, value: u8);
+		/// What's drawn for shadows, and for colors the transparent index would otherwise get.
+		pub fn black(&self) -> u8 {
+			self.nearest([0; RGB_SIZE])
+		}
 
-		/*
-			3D-isometric Block :
+		pub fn red(&self) -> u8 {
+			self.nearest([u8::MAX, 0, 0])
+		}
 
-			1st line : draw a line of 4 pixels
-			2nd line : draw a line of 8 pixels
-			3rd line : draw a line of 12 pixels
-			and so on...
-		*/
-		fn drawBlockIsometric(&mut self, x0: usize, y0: usize, data: &[u8]) {
-			let mut length = data.len();
+		/// Every index through `f` and back through `palInverse`; the transparent index stays, and what would
+		///   become it becomes black.
+		pub fn mapped(&self, palInverse: &[u8; RGBCUBE_VOLUME], f: impl Fn(Color) -> Color) -> Table {
+			let black = self.black();
+			array::from_fn(|i| {
+				if i == usize::from(self.transparent) {
+					return self.transparent;
+				}
+				self.inverse(palInverse, black, f(self.colors[i]))
+			})
+		}
 
-			// 3d-isometric subtile is 256 bytes, no more, no less
-			assert_eq!(length, 256);
+		fn inverse(&self, palInverse: &[u8; RGBCUBE_VOLUME], black: u8, color: Color) -> u8 {
+			match palInverse[cubeIndex(color)] {
+				index if index == self.transparent => black,
+				index => index,
+			}
+		}
 
-			// draw
-			let [mut Δy, width] = [0, self.width()];
-			let [mut i, mut yMulWidthAddX0] = [0, y0 * width + x0];
-			while length > 0 {
-				let [mut j, mut n] = [yMulWidthAddX0 + XJUMP[Δy], NBPIX[Δy]];
-				length -= n;
-				while n != 0 {
-					self.putpixel(j, data[i]);
-					i += 1;
-					j += 1;
-					n -= 1;
+		/// Every pair of indices blended. A transparent `over` leaves `under` as it is; a transparent `under`
+		///   is blended with as its color, black in the act palettes, the way the game's screen is black where
+		///   nothing is drawn.
+		pub fn blendTable(&self, palInverse: &[u8; RGBCUBE_VOLUME], blend: Blend) -> Box<BlendTable> {
+			let black = self.black();
+			let mut table =
+				Box::<BlendTable>::try_from(vec![0; NUM_COLORS * NUM_COLORS].into_boxed_slice()).unwrap();
+			for (under, row) in table.chunks_exact_mut(NUM_COLORS).enumerate() {
+				for (over, blended) in row.iter_mut().enumerate() {
+					*blended = if over == usize::from(self.transparent) {
+						under as _
+					} else {
+						self.inverse(palInverse, black, blend.colors(self.colors[under], self.colors[over]))
+					};
+				}
+			}
+			table
+		}
+
+		/// Every color scaled by `brightness`, 0 to 1.
+		pub fn lightTable(&self, palInverse: &[u8; RGBCUBE_VOLUME], brightness: f64) -> Table {
+			self.mapped(palInverse, |color| {
+				color.map(|component| (f64::from(component) * brightness).round() as _)
+			})
+		}
+
+		/// Every color's luma in `tint`'s hue: white becomes `tint`, black stays.
+		pub fn tintTable(&self, palInverse: &[u8; RGBCUBE_VOLUME], tint: Color) -> Table {
+			self.mapped(palInverse, |color| {
+				let luma = luma(color) / f64::from(u8::MAX);
+				tint.map(|component| (f64::from(component) * luma).round() as _)
+			})
+		}
+
+		/// Every color's nearest grey.
+		pub fn greyTable(&self, palInverse: &[u8; RGBCUBE_VOLUME]) -> Table {
+			self.mapped(palInverse, |color| [luma(color).round() as _; RGB_SIZE])
+		}
+
+		/// The tables `tables` describes, in `tables.dat`'s order.
+		pub fn tables(&self, palInverse: &[u8; RGBCUBE_VOLUME], tables: &Tables) -> Vec<Table> {
+			let Tables { lightLevels, grey, ref tints } = *tables;
+			Vec::from_iter(
+				(0..lightLevels)
+					.map(|i| self.lightTable(palInverse, (lightLevels - i) as f64 / lightLevels as f64))
+					.chain(tints.iter().map(|tint| self.tintTable(palInverse, tint.color)))
+					.chain(grey.then(|| self.greyTable(palInverse))),
+			)
+		}
+	}
+
+	/// How far apart two colors are taken to be when looking for the nearest.
+	#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+	pub enum Metric {
+		/// Plain squared RGB distance.
+		#[default]
+		Rgb,
+		/// RGB weighted by how red the two are on average, a cheap stand-in for perception.
+		Redmean,
+		/// ΔE*76, the distance in CIELAB (D65).
+		Cie76,
+		/// ΔE*00, CIELAB's distance corrected for hue and chroma; the slowest by far.
+		Ciede2000,
+		/// The distance in `OKLab`.
+		Oklab,
+	}
+	impl Metric {
+		pub const ALL: [Self; 5] = [Self::Rgb, Self::Redmean, Self::Cie76, Self::Ciede2000, Self::Oklab];
+
+		pub const fn name(self) -> &'static str {
+			match self {
+				Self::Rgb => "rgb",
+				Self::Redmean => "redmean",
+				Self::Cie76 => "cie76",
+				Self::Ciede2000 => "ciede2000",
+				Self::Oklab => "oklab",
+			}
+		}
+
+		/// What `distance` compares, worked out once per color.
+		pub fn coordinates(self, color: Color) -> [f64; 3] {
+			match self {
+				Self::Rgb | Self::Redmean => color.map(f64::from),
+				Self::Cie76 | Self::Ciede2000 => {
+					const WHITE: [f64; 3] = [0.950_47, 1.0, 1.088_83];
+					const Δ: f64 = 6.0 / 29.0;
+					let [red, green, blue] = color.map(linear);
+					let xyz = [
+						0.412_456_4 * red + 0.357_576_1 * green + 0.180_437_5 * blue,
+						0.212_672_9 * red + 0.715_152_2 * green + 0.072_175_0 * blue,
+						0.019_333_9 * red + 0.119_192_0 * green + 0.950_304_1 * blue,
+					];
+					let [fx, fy, fz] = array::from_fn(|i| {
+						let t = xyz[i] / WHITE[i];
+						if t > Δ.powi(3) {
+							t.cbrt()
+						} else {
+							t / (3.0 * Δ.powi(2)) + 4.0 / 29.0
+						}
+					});
+					[116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+				}
+				Self::Oklab => {
+					let [red, green, blue] = color.map(linear);
+					let [l, m, s] = [
+						(0.412_221_470_8 * red + 0.536_332_536_3 * green + 0.051_445_992_9 * blue).cbrt(),
+						(0.211_903_498_2 * red + 0.680_699_545_1 * green + 0.107_396_956_6 * blue).cbrt(),
+						(0.088_302_461_9 * red + 0.281_718_837_6 * green + 0.629_978_700_5 * blue).cbrt(),
+					];
+					[
+						0.210_454_255_3 * l + 0.793_617_785_0 * m - 0.004_072_046_8 * s,
+						1.977_998_495_1 * l - 2.428_592_205_0 * m + 0.450_593_709_9 * s,
+						0.025_904_037_1 * l + 0.782_771_766_2 * m - 0.808_675_766_0 * s,
+					]
+				}
+			}
+		}
+
+		// no more than `distance` between any two colors whose coordinates are at least `Δ` apart, each; written
+		//   the way `distance` is, for the float roundings to go the same way
+		fn lowerBound(self, Δ: [f64; 3]) -> f64 {
+			match self {
+				Self::Rgb | Self::Cie76 | Self::Oklab => Δ.iter().map(|Δ| Δ * Δ).sum(),
+				Self::Redmean => 2.0 * Δ[0] * Δ[0] + 4.0 * Δ[1] * Δ[1] + 2.0 * Δ[2] * Δ[2],
+				// S_L is at most 1.747, at L̄ 0 and 100. ΔC'² + ΔH'² is Δa'² + Δb'², Δa' at least Δa; S_C and S_H
+				//   are at most 8.22 and 4.79, C̄' no more than 160.5 between sRGB colors; and
+				//   c² + h² + R_T·c·h is at least (1 - sin 60°)(c² + h²), |R_T| being at most 2·sin 60°. Weak
+				//   still, ΔE*76 over 23 at most: ciede2000 tries 7 to 20 times the candidates cie76 does per block
+				Self::Ciede2000 => (Δ[0] / 1.75).powi(2) + 0.13 * (Δ[1] * Δ[1] + Δ[2] * Δ[2]) / (8.25 * 8.25),
+			}
+		}
+
+		/// Something that grows with the distance between two colors' `coordinates`; its square, mostly.
+		#[inline]
+		pub fn distance(self, a: [f64; 3], b: [f64; 3]) -> f64 {
+			let Δ: [f64; 3] = array::from_fn(|i| a[i] - b[i]);
+			match self {
+				Self::Rgb | Self::Cie76 | Self::Oklab => Δ.iter().map(|Δ| Δ * Δ).sum(),
+				Self::Redmean => {
+					let redmean = a[0].midpoint(b[0]);
+					(2.0 + redmean / 256.0) * Δ[0] * Δ[0]
+						+ 4.0 * Δ[1] * Δ[1]
+						+ (2.0 + (255.0 - redmean) / 256.0) * Δ[2] * Δ[2]
+				}
+				Self::Ciede2000 => ciede2000Squared(a, b),
+			}
+		}
+	}
+	impl fmt::Display for Metric {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			f.write_str(self.name())
+		}
+	}
+	impl FromStr for Metric {
+		type Err = String;
+		fn from_str(s: &str) -> Result<Self, Self::Err> {
+			Self::ALL.into_iter().find(|metric| metric.name() == s).ok_or_else(|| {
+				format!("{s:?} isn't one of {}", Vec::from_iter(Self::ALL.map(Self::name)).join(", "))
+			})
+		}
+	}
+
+	// sRGB's transfer function undone
+	fn linear(component: u8) -> f64 {
+		let component = f64::from(component) / f64::from(u8::MAX);
+		if component <= 0.040_45 {
+			component / 12.92
+		} else {
+			((component + 0.055) / 1.055).powf(2.4)
+		}
+	}
+
+	// Sharma, Wu and Dalal's "The CIEDE2000 Color-Difference Formula" (2005), with kL = kC = kH = 1
+	fn ciede2000Squared([l1, a1, b1]: [f64; 3], [l2, a2, b2]: [f64; 3]) -> f64 {
+		let pow25_7 = 25_f64.powi(7);
+		let cBar = a1.hypot(b1).midpoint(a2.hypot(b2));
+		let g = 0.5 * (1.0 - (cBar.powi(7) / (cBar.powi(7) + pow25_7)).sqrt());
+		let [a1, a2] = [a1 * (1.0 + g), a2 * (1.0 + g)];
+		let [c1, c2] = [a1.hypot(b1), a2.hypot(b2)];
+		let hue =
+			|a: f64, b: f64| if a == 0.0 && b == 0.0 { 0.0 } else { b.atan2(a).to_degrees().rem_euclid(360.0) };
+		let [h1, h2] = [hue(a1, b1), hue(a2, b2)];
+		let (ΔL, ΔC) = (l2 - l1, c2 - c1);
+		let Δh = match h2 - h1 {
+			_ if c1 * c2 == 0.0 => 0.0,
+			Δh if Δh > 180.0 => Δh - 360.0,
+			Δh if Δh < -180.0 => Δh + 360.0,
+			Δh => Δh,
+		};
+		let ΔH = 2.0 * (c1 * c2).sqrt() * (Δh / 2.0).to_radians().sin();
+		let (lBar, cBar) = (l1.midpoint(l2), c1.midpoint(c2));
+		let hBar = match h1 + h2 {
+			sum if c1 * c2 == 0.0 => sum,
+			sum if (h1 - h2).abs() <= 180.0 => sum / 2.0,
+			sum if sum < 360.0 => sum.midpoint(360.0),
+			sum => (sum - 360.0) / 2.0,
+		};
+		let cos = |degrees: f64| degrees.to_radians().cos();
+		let t = 1.0 - 0.17 * cos(hBar - 30.0) + 0.24 * cos(2.0 * hBar) + 0.32 * cos(3.0 * hBar + 6.0)
+			- 0.20 * cos(4.0 * hBar - 63.0);
+		let Δθ = 30.0 * (-((hBar - 275.0) / 25.0).powi(2)).exp();
+		let rC = 2.0 * (cBar.powi(7) / (cBar.powi(7) + pow25_7)).sqrt();
+		let sL = 1.0 + 0.015 * (lBar - 50.0).powi(2) / (20.0 + (lBar - 50.0).powi(2)).sqrt();
+		let (sC, sH) = (1.0 + 0.045 * cBar, 1.0 + 0.015 * cBar * t);
+		let rT = -(2.0 * Δθ).to_radians().sin() * rC;
+		let [l, c, h] = [ΔL / sL, ΔC / sC, ΔH / sH];
+		l * l + c * c + h * h + rT * c * h
+	}
+
+	/// `2_-_pngPAL_into_palInverse`'s nearest index of every RGB color, at `cubeIndex`, the metric it was made
+	///   with and the hash of the palette it's the inverse of. On file, a 16-byte header comes first: `d2swPI`,
+	///   the header's version (2), the metric's position in `Metric::ALL` and the palette's `Palette::hash`,
+	///   little-endian. Version 1 headers stop before the hash; files from before the header are the cube alone,
+	///   their metric unknown.
+	pub struct PalInverse {
+		pub metric: Option<Metric>,
+		pub paletteHash: Option<u64>,
+		pub cube: Box<[u8; RGBCUBE_VOLUME]>,
+	}
+	impl PalInverse {
+		pub const MAGIC: [u8; 6] = *b"d2swPI";
+		pub const VERSION: u8 = 2;
+		pub const HEADER_LEN: usize = Self::MAGIC.len() + 2 + size_of::<u64>();
+		const VERSION_1_HEADER_LEN: usize = Self::MAGIC.len() + 2;
+		// the side of the blocks of cells `ofPalette` works out together
+		const BLOCK: usize = 4;
+
+		/// Nearest by `metric`, the lowest index of those as near. The same as trying every color of the palette
+		///   for every cell, but 4x4x4 cells at a time, against only the colors that may be nearest to one of
+		///   them; the cube's red slabs are shared out between a thread per core.
+		pub fn ofPalette(palette: &Palette, metric: Metric) -> Self {
+			// a `distance` of its own for every metric, rather than one matching on it at every call
+			let cube = match metric {
+				Metric::Rgb => Self::cubeBy(palette, metric, |a, b| Metric::Rgb.distance(a, b)),
+				Metric::Redmean => Self::cubeBy(palette, metric, |a, b| Metric::Redmean.distance(a, b)),
+				Metric::Cie76 => Self::cubeBy(palette, metric, |a, b| Metric::Cie76.distance(a, b)),
+				Metric::Ciede2000 => Self::cubeBy(palette, metric, |a, b| Metric::Ciede2000.distance(a, b)),
+				Metric::Oklab => Self::cubeBy(palette, metric, |a, b| Metric::Oklab.distance(a, b)),
+			};
+			Self { metric: Some(metric), paletteHash: Some(palette.hash()), cube }
+		}
+
+		fn cubeBy(
+			palette: &Palette,
+			metric: Metric,
+			distance: impl Fn([f64; 3], [f64; 3]) -> f64 + Sync,
+		) -> Box<[u8; RGBCUBE_VOLUME]> {
+			let colors = &palette.colors.map(|color| metric.coordinates(color));
+			let mut cube = Self::emptyCube();
+			let slabs = &Mutex::new(
+				cube.chunks_mut(Self::BLOCK << (2 * u8::BITS)).zip((0..=u8::MAX).step_by(Self::BLOCK)),
+			);
+			thread::scope(|scope| {
+				for _ in 0..thread::available_parallelism().map_or(1, NonZeroUsize::get) {
+					scope.spawn(|| loop {
+						let Some((slab, red)) = slabs.lock().unwrap().next() else {
+							break;
+						};
+						for (green, blue) in (0..=u8::MAX)
+							.step_by(Self::BLOCK)
+							.flat_map(|green| (0..=u8::MAX).step_by(Self::BLOCK).map(move |blue| (green, blue)))
+						{
+							for (color, nearest) in Self::blockBy(colors, metric, &distance, [red, green, blue]) {
+								slab[cubeIndex(color) - (usize::from(red) << (2 * u8::BITS))] = nearest;
+							}
+						}
+					});
+				}
+			});
+			cube
+		}
+
+		// the nearest of `colors` to each of the `BLOCK`³ colors from `corner` on, tried against only those
+		//   that may be nearest to one of them
+		fn blockBy(
+			colors: &[[f64; 3]; NUM_COLORS],
+			metric: Metric,
+			distance: &impl Fn([f64; 3], [f64; 3]) -> f64,
+			[red, green, blue]: Color,
+		) -> [(Color, u8); Self::BLOCK * Self::BLOCK * Self::BLOCK] {
+			let nearestOf = |coordinates: [f64; 3], candidates: &[u8]| {
+				let (mut nearest, mut nearestDistance) = (0, f64::INFINITY);
+				for &i in candidates {
+					let distance = distance(coordinates, colors[usize::from(i)]);
+					if distance < nearestDistance {
+						(nearest, nearestDistance) = (i, distance);
+					}
+				}
+				nearest
+			};
+			let cells: [_; Self::BLOCK * Self::BLOCK * Self::BLOCK] = array::from_fn(|i| {
+				let [Δred, Δgreen, Δblue] =
+					[i / Self::BLOCK / Self::BLOCK, i / Self::BLOCK % Self::BLOCK, i % Self::BLOCK];
+				let color = [red + Δred as u8, green + Δgreen as u8, blue + Δblue as u8];
+				(color, metric.coordinates(color))
+			});
+			let [min, max]: [[f64; 3]; 2] = [f64::min, f64::max].map(|minOrMax| {
+				array::from_fn(|axis| {
+					cells.iter().map(|(_, coordinates)| coordinates[axis]).reduce(minOrMax).unwrap()
+				})
+			});
+			// no cell's nearest is farther than any one color is from the farthest cell
+			let all: &[u8; NUM_COLORS] = &array::from_fn(|i| i as _);
+			let any = colors[usize::from(nearestOf(cells[cells.len() / 2].1, all))];
+			let farthest = cells.iter().map(|&(_, coordinates)| distance(coordinates, any)).fold(0.0, f64::max);
+			let candidates = Vec::from_iter(all.iter().copied().filter(|&i| {
+				let color = colors[usize::from(i)];
+				let Δ = array::from_fn(|axis| (min[axis] - color[axis]).max(color[axis] - max[axis]).max(0.0));
+				metric.lowerBound(Δ) <= farthest
+			}));
+			cells.map(|(color, coordinates)| (color, nearestOf(coordinates, &candidates)))
+		}
+
+		/// `palette`'s inverse by `metric` from `cacheDir`, made and left there if it isn't yet.
+		pub fn cached(palette: &Palette, metric: Metric, cacheDir: &Path) -> Result<Self, Error> {
+			let paletteHash = palette.hash();
+			let path = &cacheDir.join(format!("{paletteHash:016x}.{metric}.palInverse.dat"));
+			// one that can't be read, or is another palette's after all, is made over
+			if let Some(palInverse) =
+				fs::File::open(path).ok().and_then(|file| Self::read(&mut io::BufReader::new(file)).ok()).filter(
+					|palInverse| (palInverse.metric, palInverse.paletteHash) == (Some(metric), Some(paletteHash)),
+				) {
+				return Ok(palInverse);
+			}
+			let palInverse = Self::ofPalette(palette, metric);
+			fs::create_dir_all(cacheDir)?;
+			// renamed into place whole, for the other tools asking for the same inverse at the same time
+			let partial = &path.with_extension(format!("{}.partial", std::process::id()));
+			let mut file = io::BufWriter::new(fs::File::create(partial)?);
+			palInverse.write(&mut file)?;
+			file.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+			fs::rename(partial, path)?;
+			Ok(palInverse)
+		}
+
+		/// The file at `path`, whatever its metric, or if it's a directory, `palette`'s inverse by `metric`
+		///   cached in it.
+		pub fn fromFileOrCache(path: &Path, palette: &Palette, metric: Metric) -> Result<Self, Error> {
+			if path.is_dir() {
+				Self::cached(palette, metric, path)
+			} else {
+				Self::read(&mut io::BufReader::new(fs::File::open(path)?))
+			}
+		}
+
+		pub fn emptyCube() -> Box<[u8; RGBCUBE_VOLUME]> {
+			vec![0; RGBCUBE_VOLUME].into_boxed_slice().try_into().unwrap()
+		}
+
+		pub fn read(from: &mut impl Read) -> Result<Self, Error> {
+			let (mut header, mut cube) = ([0; Self::VERSION_1_HEADER_LEN], Self::emptyCube());
+			from.read_exact(&mut header)?;
+			let (metric, paletteHash) = match header {
+				[m0, m1, m2, m3, m4, m5, version, metric] if [m0, m1, m2, m3, m4, m5] == Self::MAGIC => {
+					let paletteHash = match version {
+						1 => None,
+						Self::VERSION => {
+							let mut paletteHash = [0; size_of::<u64>()];
+							from.read_exact(&mut paletteHash)?;
+							Some(u64::from_le_bytes(paletteHash))
+						}
+						_ => return Err(Error::UnsupportedVersion(format!("palInverse header version {version}"))),
+					};
+					from.read_exact(&mut cube[..])?;
+					let metric = *Metric::ALL.get(usize::from(metric)).ok_or_else(|| Error::InvalidData {
+						offset: Self::VERSION_1_HEADER_LEN as u64 - 1,
+						reason: format!("no metric #{metric}"),
+					})?;
+					(Some(metric), paletteHash)
+				}
+				_ => {
+					cube[..Self::VERSION_1_HEADER_LEN].copy_from_slice(&header);
+					from.read_exact(&mut cube[Self::VERSION_1_HEADER_LEN..])?;
+					(None, None)
+				}
+			};
+			Ok(Self { metric, paletteHash, cube })
+		}
+
+		/// With the header, version 1's if there's no palette hash; without a metric, the cube alone as before
+		///   the header.
+		pub fn write(&self, to: &mut impl Write) -> io::Result<()> {
+			if let Some(metric) = self.metric {
+				let position = Metric::ALL.iter().position(|&other| other == metric).unwrap();
+				to.write_all(&Self::MAGIC)?;
+				match self.paletteHash {
+					Some(paletteHash) => {
+						to.write_all(&[Self::VERSION, position as _])?;
+						to.write_all(&paletteHash.to_le_bytes())?;
+					}
+					None => to.write_all(&[1, position as _])?,
+				}
+			}
+			to.write_all(&self.cube[..])
+		}
+	}
+
+	/// How what's lost picking every pixel's nearest color is made up for, if at all.
+	#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+	pub enum Dither {
+		#[default]
+		None,
+		FloydSteinberg,
+		Atkinson,
+		Bayer2,
+		Bayer4,
+		Bayer8,
+	}
+	impl Dither {
+		pub const ALL: [Self; 6] =
+			[Self::None, Self::FloydSteinberg, Self::Atkinson, Self::Bayer2, Self::Bayer4, Self::Bayer8];
+
+		pub const fn name(self) -> &'static str {
+			match self {
+				Self::None => "none",
+				Self::FloydSteinberg => "floyd-steinberg",
+				Self::Atkinson => "atkinson",
+				Self::Bayer2 => "bayer2",
+				Self::Bayer4 => "bayer4",
+				Self::Bayer8 => "bayer8",
+			}
+		}
+
+		/// Error diffusion's neighbours, `[Δx, Δy]` of the ones not yet picked, and their shares of the error;
+		///   Atkinson's add up to 3/4, the rest of the error dropped.
+		pub const fn diffusion(self) -> &'static [([isize; 2], f64)] {
+			match self {
+				Self::FloydSteinberg => {
+					&[([1, 0], 7.0 / 16.0), ([-1, 1], 3.0 / 16.0), ([0, 1], 5.0 / 16.0), ([1, 1], 1.0 / 16.0)]
+				}
+				Self::Atkinson => &[
+					([1, 0], 1.0 / 8.0),
+					([2, 0], 1.0 / 8.0),
+					([-1, 1], 1.0 / 8.0),
+					([0, 1], 1.0 / 8.0),
+					([1, 1], 1.0 / 8.0),
+					([0, 2], 1.0 / 8.0),
+				],
+				_ => &[],
+			}
+		}
+
+		/// Ordered dithering's threshold at `[x, y]`, from -1/2 to 1/2; 0 for the others.
+		pub fn threshold(self, [x, y]: [usize; 2]) -> f64 {
+			let bits = match self {
+				Self::Bayer2 => 1,
+				Self::Bayer4 => 2,
+				Self::Bayer8 => 3,
+				_ => return 0.0,
+			};
+			// the matrix's recursive definition, the coordinates' lowest bits the most significant
+			let mut value = 0;
+			for bit in 0..bits {
+				value = (value << 2) | (((x ^ y) >> bit & 1) << 1) | (y >> bit & 1);
+			}
+			(value as f64 + 0.5) / f64::from(1 << (2 * bits)) - 0.5
+		}
+	}
+	impl fmt::Display for Dither {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			f.write_str(self.name())
+		}
+	}
+	impl FromStr for Dither {
+		type Err = String;
+		fn from_str(s: &str) -> Result<Self, Self::Err> {
+			Self::ALL.into_iter().find(|dither| dither.name() == s).ok_or_else(|| {
+				format!("{s:?} isn't one of {}", Vec::from_iter(Self::ALL.map(Self::name)).join(", "))
+			})
+		}
+	}
+
+	/// How a translucent pixel is drawn over what's already there.
+	#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+	pub enum Blend {
+		Alpha25,
+		Alpha50,
+		Alpha75,
+		/// Light: the colors summed, each component up to 255.
+		Additive,
+	}
+	impl Blend {
+		pub const ALL: [Self; 4] = [Self::Alpha25, Self::Alpha50, Self::Alpha75, Self::Additive];
+
+		pub const fn name(self) -> &'static str {
+			match self {
+				Self::Alpha25 => "alpha25",
+				Self::Alpha50 => "alpha50",
+				Self::Alpha75 => "alpha75",
+				Self::Additive => "additive",
+			}
+		}
+
+		pub fn colors(self, under: Color, over: Color) -> Color {
+			let alpha = match self {
+				Self::Alpha25 => 0.25,
+				Self::Alpha50 => 0.5,
+				Self::Alpha75 => 0.75,
+				Self::Additive => return array::from_fn(|i| under[i].saturating_add(over[i])),
+			};
+			array::from_fn(|i| (f64::from(under[i]) * (1.0 - alpha) + f64::from(over[i]) * alpha).round() as _)
+		}
+	}
+	impl fmt::Display for Blend {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			f.write_str(self.name())
+		}
+	}
+	impl FromStr for Blend {
+		type Err = String;
+		fn from_str(s: &str) -> Result<Self, Self::Err> {
+			Self::ALL.into_iter().find(|blend| blend.name() == s).ok_or_else(|| {
+				format!("{s:?} isn't one of {}", Vec::from_iter(Self::ALL.map(Self::name)).join(", "))
+			})
+		}
+	}
+
+	/// `tables.toml`, what `tables.dat` holds: 256 bytes per table, the index every palette index becomes,
+	///   back to back. First come `lightLevels` tables from full brightness down, table `i` scaling every
+	///   color by `(lightLevels - i) / lightLevels`, then one per tint in `tints`' order, then the grey one if
+	///   `grey`.
+	#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+	pub struct Tables {
+		pub lightLevels: usize,
+		pub grey: bool,
+		pub tints: Vec<Tint>,
+	}
+	impl Tables {
+		pub fn names(&self) -> Vec<String> {
+			Vec::from_iter(
+				(0..self.lightLevels)
+					.map(|i| format!("light{i}"))
+					.chain(self.tints.iter().map(|tint| tint.name.clone()))
+					.chain(self.grey.then(|| "grey".to_owned())),
+			)
+		}
+
+		/// `tables.dat`'s tables by their names: `light0`…, the tints' and `grey`.
+		pub fn split(&self, tablesDAT: &[u8]) -> Result<Vec<(String, Table)>, Error> {
+			let names = self.names();
+			let tables = exactly(tablesDAT, names.len() * NUM_COLORS)?.chunks_exact(NUM_COLORS);
+			Ok(Vec::from_iter(names.into_iter().zip(tables.map(|table| <Table>::try_from(table).unwrap()))))
+		}
+	}
+
+	/// What white becomes under a curse-like tint; `red`, `green` and `blue` or `name=RRGGBB`.
+	#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+	pub struct Tint {
+		pub name: String,
+		pub color: Color,
+	}
+	impl Tint {
+		pub const NAMED: [(&'static str, Color); 3] =
+			[("red", [u8::MAX, 96, 96]), ("green", [96, u8::MAX, 96]), ("blue", [96, 96, u8::MAX])];
+	}
+	impl FromStr for Tint {
+		type Err = String;
+		fn from_str(s: &str) -> Result<Self, Self::Err> {
+			if let Some(&(name, color)) = Self::NAMED.iter().find(|&&(name, _)| name == s) {
+				return Ok(Self { name: name.to_owned(), color });
+			}
+			let (name, hex) = s.split_once('=').ok_or_else(|| {
+				format!(
+					"{s:?} is neither one of {} nor `name=RRGGBB`",
+					Vec::from_iter(Self::NAMED.map(|(name, _)| name)).join(", ")
+				)
+			})?;
+			match u32::from_str_radix(hex, 16) {
+				Ok(rgb) if hex.len() == 2 * RGB_SIZE => {
+					let [_, red, green, blue] = rgb.to_be_bytes();
+					Ok(Self { name: name.to_owned(), color: [red, green, blue] })
+				}
+				_ => Err(format!("{hex:?} isn't `RRGGBB`")),
+			}
+		}
+	}
+
+	fn exactly(bytes: &[u8], len: usize) -> Result<&[u8], Error> {
+		match bytes.len() {
+			n if n < len => Err(Error::Truncated { offset: n as _, needed: (len - n) as _ }),
+			n if n > len => Err(Error::TrailingBytes { offset: len as _, len: n - len }),
+			_ => Ok(bytes),
+		}
+	}
+
+	fn actPalDatPath(paletteDir: &Path, actIndex: i32) -> Option<PathBuf> {
+		let mut path = paletteDir.to_path_buf();
+		for component in [format!("act{}", actIndex + 1), "pal.dat".to_owned()] {
+			path = fs::read_dir(&path)
+				.ok()?
+				.filter_map(Result::ok)
+				.find(|entry| entry.file_name().to_string_lossy().eq_ignore_ascii_case(&component))?
+				.path();
+		}
+		Some(path)
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		// every color's red its index, so that the cube of every color's red is as good as a palInverse
+		fn testPalette() -> (Palette, Box<[u8; RGBCUBE_VOLUME]>) {
+			let palette = Palette::fromPngPAL(&Vec::from_iter(
+				(0..=u8::MAX).flat_map(|i| [i, i.wrapping_mul(37), i.wrapping_mul(101)]),
+			));
+			let cube = Vec::from_iter((0..RGBCUBE_VOLUME).map(|i| (i >> (2 * u8::BITS)) as u8));
+			(palette, cube.into_boxed_slice().try_into().unwrap())
+		}
+
+		#[test]
+		fn transparentOverLeavesUnder() {
+			let (mut palette, palInverse) = testPalette();
+			for transparent in [FULLY_TRANSPARENT, 7] {
+				palette.transparent = transparent;
+				for blend in Blend::ALL {
+					let table = palette.blendTable(&palInverse, blend);
+					for under in 0..NUM_COLORS {
+						assert_eq!(table[under << u8::BITS | usize::from(transparent)], under as u8, "{blend}");
+					}
+				}
+			}
+		}
+
+		#[test]
+		fn palInverseBlocksMatchTryingEveryColor() {
+			let ramp = Palette::fromPngPAL(&Vec::from_iter((0..=u8::MAX).flat_map(|i| [i, i / 2, u8::MAX - i])));
+			for palette in [testPalette().0, ramp] {
+				for metric in Metric::ALL {
+					let colors = &palette.colors.map(|color| metric.coordinates(color));
+					let distance = |a, b| metric.distance(a, b);
+					// blocks strewn over the cube, black's among them
+					for i in (0..=u8::MAX).step_by(PalInverse::BLOCK) {
+						let corner = [i, i.wrapping_mul(101), i.wrapping_mul(59)]
+							.map(|component| component / PalInverse::BLOCK as u8 * PalInverse::BLOCK as u8);
+						for (color, nearest) in PalInverse::blockBy(colors, metric, &distance, corner) {
+							let coordinates = metric.coordinates(color);
+							let everyColor = (0..=u8::MAX)
+								.min_by(|&a, &b| {
+									distance(coordinates, colors[usize::from(a)])
+										.total_cmp(&distance(coordinates, colors[usize::from(b)]))
+								})
+								.unwrap();
+							assert_eq!(nearest, everyColor, "{metric}: {color:?}");
+						}
+					}
+				}
+			}
+		}
+	}
+}
+
+pub mod dt1 {
+	use {
+		super::{
+			pack::Packing, CopyExt, Error, Image, Layout, MinAssign_MaxAssign_Ext, ReadExt, TileColumns,
+			TilesIterator, UsizeExt, Vec2, Vec2Ext, WriteExt, FULLY_TRANSPARENT, HEIGHT, WIDTH, X, Y,
+		},
+		byteorder::{WriteBytesExt, LE},
+		core::{
+			cmp::{max, min},
+			iter, mem,
+		},
+		serde::{Deserialize, Serialize},
+		std::io::{self, BufRead, Cursor, Write},
+	};
+
+	#[derive(Serialize, Deserialize)]
+	pub struct Metadata {
+		/// Where the block PNG's blocks are, in order, when it was packed rather than laid out in columns; its
+		///   `d2sw:packed-points` chunk says so too, but not every image editor keeps that.
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pub blockPoints: Option<Vec<Vec2>>,
+
+		pub fileHeader: FileHeader,
+
+		#[serde(rename = "tile")]
+		pub tiles: Vec<Tile>,
+	}
+
+	#[derive(Serialize, Deserialize)]
+	pub struct FileHeader {
+		pub version: [i32; 2],
+		pub tileHeadersPointer: i32,
+	}
+
+	const EXPECTED_VERSION: [i32; 2] = [7, 6];
+
+	pub const NUM_SUBTILES_PER_LINE: usize = 5;
+	const NUM_SUBTILES: usize = NUM_SUBTILES_PER_LINE.pow(2);
+	pub const FILEHEADER_SIZE: i32 = 276;
+	pub const TILEHEADER_SIZE: i32 = 96;
+	pub const BLOCKHEADER_SIZE: i32 = 20;
+	const ISOMETRIC: [u8; 2] = [1, 0];
+	const RLE_ISOMETRIC: [u8; 2] = [5, 32];
+	const RLE: [u8; 2] = [1, 16];
+
+	#[derive(Serialize, Deserialize)]
+	pub struct Tile {
+		pub direction: i32,
+		pub roofHeight: i16,
+		pub materialFlags: [u8; 2],
+		pub height: i32,
+		pub width: i32,
+		pub orientation: i32,
+		pub mainIndex: i32,
+		pub subIndex: i32,
+		pub rarityOrFrameIndex: i32,
+		pub unknown: [u8; 4],
+		pub subtileFlags: [u8; NUM_SUBTILES],
+		pub blockHeadersPointer: i32,
+		pub blocksDataLength: i32,
+		pub usuallyZeros: [u8; 4],
+
+		#[serde(rename = "block")]
+		pub blocks: Vec<Block>,
+	}
+
+	#[derive(Serialize, Deserialize)]
+	pub struct Block {
+		pub x: i16,
+		pub y: i16,
+		pub gridX: u8,
+		pub gridY: u8,
+		pub format: [u8; 2],
+		pub length: i32,
+		pub fileOffset: i32,
+	}
+
+	impl Block {
+		#[inline(always)]
+		pub fn drawFn<T: DrawDestination>(&self) -> DrawFn<T> {
+			if self.format == ISOMETRIC {
+				DrawDestination::drawBlockIsometric
+			} else {
+				DrawDestination::drawBlockRLE
+			}
+		}
+	}
+
+	impl Tile {
+		/// The topmost `y` of the blocks, and the height they span rounded up to whole floor blocks.
+		fn blocksSpan(&self) -> (i16, usize) {
+			let [mut startY, mut endY, blockHeight] = [i16::MAX, i16::MIN, self.blockHeight() as _];
+			for &Block { y, .. } in &self.blocks {
+				startY.minAssign(y);
+				endY.maxAssign(y + blockHeight);
+			}
+			(startY, (endY.wrapping_sub(startY) as usize).nextMultipleOf(FLOOR_ROOF_BLOCKHEIGHT))
+		}
+
+		#[inline(always)]
+		pub fn blockHeight(&self) -> usize {
+			match self.orientation {
+				FLOOR_ORIENTATION | ROOF_ORIENTATION => FLOOR_ROOF_BLOCKHEIGHT,
+				_ => MAX_BLOCKHEIGHT,
+			}
+		}
+
+		/// How far below the top corner of its cell's floor diamond the tile's `y == 0` is drawn: floors right
+		///   there, roofs raised by their roofHeight, walls and shadows standing on the bottom corner.
+		#[inline(always)]
+		pub fn drawnY(&self) -> i32 {
+			match self.orientation {
+				FLOOR_ORIENTATION => 0,
+				ROOF_ORIENTATION => -i32::from(self.roofHeight),
+				_ => FLOOR_ROOF_TILEHEIGHT as _,
+			}
+		}
+	}
+
+	impl Metadata {
+		pub fn new(dt1: &[u8]) -> Result<Self, Error> {
+			let mut cursor = io::Cursor::new(dt1);
+			let version = [cursor.readLE()?, cursor.readLE()?];
+			if version != EXPECTED_VERSION {
+				return Err(Error::UnsupportedVersion(format!(
+					"dt1.fileHeader.version == {version:?} != {EXPECTED_VERSION:?}"
+				)));
+			}
+			cursor.consumeZeros(260)?;
+			let numTilesOffset = cursor.position();
+			let numTiles = cursor.readCount("dt1.fileHeader.numTiles")?;
+			let tileHeadersPointer = cursor.readLE::<i32>()?;
+			expectPointer(&cursor, "dt1.fileHeader.tileHeadersPointer", tileHeadersPointer)?;
+			cursor.ensureRemaining(numTiles.checked_mul(TILEHEADER_SIZE as usize).ok_or_else(|| {
+				Error::InvalidData { offset: numTilesOffset, reason: "dt1.tiles overflow".to_owned() }
+			})?)?;
+			let mut tiles = Vec::with_capacity(numTiles);
+			let mut numsBlocks = Vec::with_capacity(tiles.capacity());
+			for _ in 0..numTiles {
+				tiles.push(Tile {
+					direction: cursor.readLE()?,
+					roofHeight: cursor.readLE()?,
+					materialFlags: cursor.read_u8_array()?,
+					height: cursor.readLE()?,
+					width: cursor.readLE()?,
+					orientation: {
+						cursor.consumeZeros(4)?;
+						cursor.readLE()?
+					},
+					mainIndex: cursor.readLE()?,
+					subIndex: cursor.readLE()?,
+					rarityOrFrameIndex: cursor.readLE()?,
+					unknown: cursor.read_u8_array()?,
+					subtileFlags: cursor.read_u8_array()?,
+					blockHeadersPointer: {
+						cursor.consumeZeros(7)?;
+						cursor.readLE()?
+					},
+					blocksDataLength: cursor.readLE()?,
+					blocks: Vec::new(),
+					usuallyZeros: {
+						numsBlocks.push((
+							cursor.position(),
+							cursor.readCount(&format!("dt1.tile[{}].numBlocks", tiles.len()))?,
+						));
+						cursor.consumeZeros(4)?;
+						cursor.read_u8_array()?
+					},
+				});
+				cursor.consumeZeros(4)?;
+			}
+			for (i, (tile, (numBlocksOffset, numBlocks))) in tiles.iter_mut().zip(numsBlocks).enumerate() {
+				expectPointer(&cursor, &format!("dt1.tile[{i}].blockHeadersPointer"), tile.blockHeadersPointer)?;
+				cursor.ensureRemaining(numBlocks.checked_mul(BLOCKHEADER_SIZE as usize).ok_or_else(|| {
+					Error::InvalidData { offset: numBlocksOffset, reason: format!("dt1.tile[{i}].blocks overflow") }
+				})?)?;
+				let mut totalLength = 0;
+				let blocks = &mut tile.blocks;
+				for _ in 0..numBlocks {
+					let block = Block {
+						x: cursor.readLE()?,
+						y: cursor.readLE()?,
+						gridX: {
+							cursor.consumeZeros(2)?;
+							cursor.readLE()?
+						},
+						gridY: cursor.readLE()?,
+						format: cursor.read_u8_array()?,
+						length: cursor.readLE()?,
+						fileOffset: {
+							cursor.consumeZeros(2)?;
+							cursor.readLE()?
+						},
+					};
+					if ![ISOMETRIC, RLE_ISOMETRIC, RLE].contains(&block.format) {
+						return Err(Error::InvalidBlockFormat {
+							offset: cursor.position() - BLOCKHEADER_SIZE as u64 + 8,
+							format: block.format,
+						});
+					}
+					let blockData = (tile.blockHeadersPointer + block.fileOffset) as u64;
+					if block.length < 0 || blockData + block.length as u64 > dt1.len() as u64 {
+						return Err(Error::Truncated {
+							offset: blockData,
+							needed: (blockData + block.length.max(0) as u64).saturating_sub(dt1.len() as _),
+						});
+					}
+					totalLength += block.length as usize;
+					blocks.push(block);
+				}
+				cursor.ensureRemaining(totalLength)?;
+				cursor.consume(totalLength);
+			}
+			if cursor.remaining() != 0 {
+				return Err(Error::TrailingBytes { offset: cursor.position(), len: cursor.remaining() });
+			}
+			return Ok(Self {
+				blockPoints: None,
+				fileHeader: FileHeader { version, tileHeadersPointer },
+				tiles,
+			});
+
+			fn expectPointer(cursor: &io::Cursor<&[u8]>, what: &str, pointer: i32) -> Result<(), Error> {
+				if cursor.position() != pointer as u64 {
+					return Err(Error::BadPointer {
+						what: what.to_owned(),
+						pointer: pointer.into(),
+						offset: cursor.position(),
+					});
+				}
+				Ok(())
+			}
+		}
+
+		/// `2_-_pngPAL-dt1_into_dt1TOML-blockPNG`: `dt1`'s metadata, and a PNG of its blocks in `pngPAL`'s
+		///   colors, laid out by `layout`.
+		pub fn newWithBlockPNG(pngPAL: &[u8], dt1: &[u8], layout: Layout) -> Result<(Self, Vec<u8>), Error> {
+			let mut dt1Metadata = Self::new(dt1)?;
+			let (image, packing) = Image::fromDT1(&dt1Metadata.tiles, dt1, layout);
+			dt1Metadata.blockPoints = packing.as_ref().map(|packing| packing.points.clone());
+			let blockPNG = indexedPNG(&image, pngPAL, |png| {
+				if let Some(packing) = &packing {
+					packing.addToPNG(png);
+				}
+			});
+			Ok((dt1Metadata, blockPNG))
+		}
+
+		/// `3_-_dt1TOML-blockPNG_into_tilePNG`: the blocks of `blockPNG` put back together into the tiles that
+		///   have any, in columns or packed by `layout`. With `zealousVerticalPacking`, every tile is cropped to
+		///   the rows its pixels are on rather than those its blocks are, and the PNG says which rows those are.
+		pub fn tilePNG(
+			&self,
+			blockPNG: &[u8],
+			zealousVerticalPacking: bool,
+			layout: Layout,
+		) -> Result<Vec<u8>, Error> {
+			let png = &mut png::Decoder::new(blockPNG)
+				.read_info()
+				.map_err(|err| Error::InvalidData { offset: 0, reason: err.to_string() })?;
+			let srcImage = &mut Image::fromPNG(png);
+			let pngPAL = png.info().palette.as_deref().ok_or_else(|| Error::InvalidData {
+				offset: 0,
+				reason: "a PNG without a PLTE chunk".to_owned(),
+			})?;
+			// the TOML's word on where a packed sheet's blocks are, the PNG's if it has none
+			let blockPoints = |srcImage: &Image| match &self.blockPoints {
+				Some(points) => TilesIterator::fromPoints(BLOCKWIDTH, points.clone()),
+				None => TilesIterator::fromPNG(BLOCKWIDTH, srcImage, png.info()),
+			};
+			let tiles = Vec::from_iter(self.tiles.iter().filter(|tile| !tile.blocks.is_empty()));
+			let rows = {
+				let srcPoints = &mut blockPoints(srcImage);
+				Vec::from_iter(tiles.iter().map(|tile| {
+					if !zealousVerticalPacking {
+						return tile.blocksSpan();
+					}
+					let (mut startY, mut endY, blockHeight) = (i16::MAX, i16::MIN, tile.blockHeight());
+					for block in &tile.blocks {
+						let [startΔy, endΔy] =
+							srcImage.boundingΔyRangeᐸBLOCKWIDTHᐳ(srcPoints.next(blockHeight), blockHeight);
+						startY.minAssign(block.y + startΔy);
+						endY.maxAssign(block.y + endΔy);
+					}
+					// no pixels at all; a row of floor blocks' worth of them still, to be edited in
+					if startY >= endY {
+						return (tile.blocksSpan().0, FLOOR_ROOF_BLOCKHEIGHT);
+					}
+					(startY, ((endY - startY) as usize).nextMultipleOf(FLOOR_ROOF_BLOCKHEIGHT))
+				}))
+			};
+			let packing = layout.pack(&Vec::from_iter(rows.iter().map(|&(_, height)| [TILEWIDTH, height])));
+			let mut destImage = if let Some(packing) = &packing {
+				Image::fromWidthHeight(packing.dimensions[WIDTH], packing.dimensions[HEIGHT])
+			} else {
+				let maxTileHeight = rows.iter().map(|&(_, height)| height).max().unwrap_or(0);
+				let (width, height);
+				{
+					let chosenTileColumns = &{
+						let choices = &mut Vec::<TileColumns>::new();
+						choices.push(TileColumns {
+							fullColumnHeight: maxTileHeight,
+							numOverflownColumns: 0,
+							lastColumnHeight: 0,
+						});
+						for &(_, tileHeight) in &rows {
+							choices.push(choices.last().unwrap().clone());
+							let mut i = 0;
+							while i < choices.len() {
+								let result = choices[i].pushTile(tileHeight);
+								if i == choices.len() - 2 {
+									let lastIndex = choices.len() - 1;
+									if result == 0 {
+										choices.truncate(lastIndex);
+									} else {
+										assert_eq!(choices[lastIndex].numOverflownColumns, 0);
+										choices[lastIndex].fullColumnHeight += FLOOR_ROOF_BLOCKHEIGHT;
+										choices.push(choices[lastIndex].clone());
+									}
+								}
+								i += 1;
+							}
+						}
+						choices.sort_by(|a, b| {
+							let dimensions = [a, b].map(|tileColumns| tileColumns.dimensions(TILEWIDTH));
+							let pow2SquareSizes =
+								dimensions.map(|[width, height]| max(width, height).next_power_of_two());
+							const A: usize = 0;
+							const B: usize = 1;
+							pow2SquareSizes[A]
+								.cmp(&pow2SquareSizes[B])
+								.then_with(|| dimensions[B][WIDTH].cmp(&dimensions[A][WIDTH]))
+						});
+						mem::take(&mut choices[0])
+					};
+					[width, height] = chosenTileColumns.dimensions(TILEWIDTH);
+					eprintln!(
+						"[{width}, {height}]; lastColumnHeight = {}, maxTileHeight = {maxTileHeight}",
+						chosenTileColumns.lastColumnHeight,
+					);
+				}
+				Image::fromWidthHeight(width, height)
+			};
+			{
+				let destPoints = &mut match &packing {
+					Some(packing) => TilesIterator::fromPoints(TILEWIDTH, packing.points.clone()),
+					None => TilesIterator::new(TILEWIDTH, &destImage),
+				};
+				let srcPoints = &mut blockPoints(srcImage);
+				for (tile, &(startY, height)) in iter::zip(&tiles, &rows) {
+					let (destPoint, blockHeight) = (destPoints.next(height), tile.blockHeight());
+					for block in &tile.blocks {
+						let srcPoint = srcPoints.next(blockHeight);
+						// only the tile's rows; a cropped tile's others are transparent
+						let [top, bottom] = [
+							max(block.y, startY),
+							min(block.y + blockHeight as i16, startY.saturating_add(height as _)),
+						];
+						if top < bottom {
+							destImage.blitPixelsRectangle(
+								destPoint.add([block.x as _, (top - startY) as _]),
+								[BLOCKWIDTH, (bottom - top) as _],
+								srcImage,
+								srcPoint.add([0, (top - block.y) as _]),
+							);
+						}
+					}
+				}
+			}
+			Ok(indexedPNG(&destImage, pngPAL, |png| {
+				if let Some(packing) = &packing {
+					packing.addToPNG(png);
+				}
+				if zealousVerticalPacking {
+					let text = Vec::from_iter(rows.iter().map(|(startY, height)| format!("{startY},{height}")));
+					png.add_text_chunk(TILE_ROWS_PNG_KEYWORD.to_owned(), text.join(" ")).unwrap();
+				}
+			}))
+		}
+
+		/// `4_-_dt1TOML-tilePNG_into_dt1`: the DT1 whose blocks `tilePNG` has, laid out as `tilePNG` says.
+		pub fn writeFromTilePNG(&self, tilePNG: &[u8], to: &mut impl Write) -> Result<(), Error> {
+			let png = &mut png::Decoder::new(tilePNG)
+				.read_info()
+				.map_err(|err| Error::InvalidData { offset: 0, reason: err.to_string() })?;
+			let tileImage = &Image::fromPNG(png);
+			let srcPoints = &mut TilesIterator::fromPNG(TILEWIDTH, tileImage, png.info());
+			let Some(rows) = tileRowsOfPNG(png.info())? else {
+				return self.writeWithBlockDataFromTileImage(tileImage, srcPoints, to);
+			};
+			// a cropped sheet is read back from one of the tiles' whole spans, in columns
+			let tiles = Vec::from_iter(self.tiles.iter().filter(|tile| !tile.blocks.is_empty()));
+			if rows.len() != tiles.len() {
+				return Err(Error::InvalidData {
+					offset: 0,
+					reason: format!(
+						"{TILE_ROWS_PNG_KEYWORD} has {} tiles' rows, not the {} tiles with blocks'",
+						rows.len(),
+						tiles.len(),
+					),
+				});
+			}
+			let spans = Vec::from_iter(tiles.iter().map(|tile| tile.blocksSpan()));
+			let destPoints = &mut TilesIterator {
+				tilewidth: TILEWIDTH,
+				sheetWidth: usize::MAX,
+				tileColumns: TileColumns {
+					fullColumnHeight: spans.iter().map(|&(_, spanHeight)| spanHeight).max().unwrap_or(0),
+					numOverflownColumns: 0,
+					lastColumnHeight: 0,
+				},
+				packedPoints: None,
+			};
+			let points = Vec::from_iter(spans.iter().map(|&(_, spanHeight)| destPoints.next(spanHeight)));
+			let mut spansImage = Image::fromWidthHeight(
+				points.iter().map(|point| point[X] + TILEWIDTH).max().unwrap_or(0),
+				iter::zip(&points, &spans)
+					.map(|(point, &(_, spanHeight))| point[Y] + spanHeight)
+					.max()
+					.unwrap_or(0),
+			);
+			for ((&(spanStartY, spanHeight), &(startY, height)), &point) in
+				iter::zip(iter::zip(&spans, &rows), &points)
+			{
+				let srcPoint = srcPoints.next(height);
+				let [top, bottom] = [
+					max(startY, spanStartY),
+					min(startY.saturating_add(height as _), spanStartY.saturating_add(spanHeight as _)),
+				];
+				if top < bottom {
+					spansImage.blitPixelsRectangle(
+						point.add([0, (top - spanStartY) as _]),
+						[TILEWIDTH, (bottom - top) as _],
+						tileImage,
+						srcPoint.add([0, (top - startY) as _]),
+					);
+				}
+			}
+			self.writeWithBlockDataFromTileImage(
+				&spansImage,
+				&mut TilesIterator::fromPoints(TILEWIDTH, points),
+				to,
+			)
+		}
+
+		/// `points` walks `tileImage` the way it was laid out, one tile with blocks at a time.
+		pub fn writeWithBlockDataFromTileImage(
+			&self,
+			tileImage: &Image,
+			points: &mut TilesIterator,
+			to: &mut impl Write,
+		) -> Result<(), Error> {
+			let (Self { fileHeader, tiles, .. }, cursor) =
+				(self, &mut Cursor::new(Vec::with_capacity(6 * 1024 * 1024)));
+			fileHeader.version.iter().try_for_each(|&versionElem| cursor.write_i32::<LE>(versionElem))?;
+			cursor.writeZeros(260)?;
+			cursor.write_i32::<LE>(tiles.len() as _)?;
+			cursor.write_i32::<LE>(fileHeader.tileHeadersPointer)?;
+			let mut blockHeadersPointer = FILEHEADER_SIZE + tiles.len() as i32 * TILEHEADER_SIZE;
+			for tile in tiles {
+				let &Tile {
+					direction,
+					roofHeight,
+					ref materialFlags,
+					height,
+					width,
+					orientation,
+					mainIndex,
+					subIndex,
+					rarityOrFrameIndex,
+					ref unknown,
+					ref subtileFlags,
+					blockHeadersPointer: _,
+					blocksDataLength: _,
+					ref usuallyZeros,
+					ref blocks,
+				} = tile;
+				let blocksDataLength = {
+					let (startY, spanHeight) = tile.blocksSpan();
+					let (mut fileOffset, position, point) = (
+						blocks.len() as i32 * BLOCKHEADER_SIZE,
+						cursor.position(),
+						if blocks.is_empty() {
+							[0; 2]
+						} else {
+							points.next(spanHeight).add([0, 0_usize.wrapping_sub(startY as _)])
+						},
+					);
+					cursor.set_position(blockHeadersPointer as _);
+					for &Block { x, y, gridX, gridY, format, length: _, fileOffset: _ } in blocks {
+						let (position, point) = (cursor.position(), point.add([x as _, y as _]));
+						cursor.set_position((blockHeadersPointer + fileOffset) as _);
+						let length = {
+							let position = cursor.position();
+							if format == ISOMETRIC {
+								cursor.writeBlockDataIsometric(point, tileImage)?;
+							} else {
+								cursor.writeBlockDataRLE(
+									point,
+									match format {
+										RLE => MAX_BLOCKHEIGHT,
+										RLE_ISOMETRIC => NBPIX.len(),
+										_ => unreachable!(),
+									},
+									tileImage,
+								)?;
+							}
+							(cursor.position() - position) as i32
+						};
+						cursor.set_position(position);
+						cursor.write_i16::<LE>(x)?;
+						cursor.write_i16::<LE>(y)?;
+						cursor.writeZeros(2)?;
+						cursor.write_u8(gridX)?;
+						cursor.write_u8(gridY)?;
+						cursor.write_all(&format)?;
+						cursor.write_i32::<LE>(length)?;
+						cursor.writeZeros(2)?;
+						cursor.write_i32::<LE>(fileOffset)?;
+						fileOffset += length;
+					}
+					cursor.set_position(position);
+					fileOffset
+				};
+				cursor.write_i32::<LE>(direction)?;
+				cursor.write_i16::<LE>(roofHeight)?;
+				cursor.write_all(materialFlags)?;
+				cursor.write_i32::<LE>(height)?;
+				cursor.write_i32::<LE>(width)?;
+				cursor.writeZeros(4)?;
+				cursor.write_i32::<LE>(orientation)?;
+				cursor.write_i32::<LE>(mainIndex)?;
+				cursor.write_i32::<LE>(subIndex)?;
+				cursor.write_i32::<LE>(rarityOrFrameIndex)?;
+				cursor.write_all(unknown)?;
+				cursor.write_all(subtileFlags)?;
+				cursor.writeZeros(7)?;
+				cursor.write_i32::<LE>(blockHeadersPointer)?;
+				cursor.write_i32::<LE>(blocksDataLength)?;
+				cursor.write_i32::<LE>(blocks.len() as _)?;
+				cursor.writeZeros(4)?;
+				cursor.write_all(usuallyZeros)?;
+				cursor.writeZeros(4)?;
+				blockHeadersPointer += blocksDataLength;
+			}
+			to.write_all(cursor.get_ref())?;
+			return Ok(());
+
+			trait WriteBlockDataExt {
+				fn writeBlockDataIsometric(&mut self, point: Vec2, tileImage: &Image) -> io::Result<()>;
+				fn writeBlockDataRLE(
+					&mut self,
+					point: Vec2,
+					blockHeight: usize,
+					tileImage: &Image,
+				) -> io::Result<()>;
+			}
+			impl<T: Write> WriteBlockDataExt for T {
+				#[inline(always)]
+				fn writeBlockDataIsometric(&mut self, point: Vec2, tileImage: &Image) -> io::Result<()> {
+					let mut i = point[Y] * tileImage.width + point[X];
+					for (&xjump, &nbpix) in iter::zip(XJUMP, NBPIX) {
+						self.write_all(&tileImage.data[i + xjump..][..nbpix])?;
+						i += tileImage.width;
+					}
+					Ok(())
+				}
+				#[inline(always)]
+				fn writeBlockDataRLE(
+					&mut self,
+					point: Vec2,
+					blockHeight: usize,
+					tileImage: &Image,
+				) -> io::Result<()> {
+					let (mut i, imageData) = ((point[Y] - 1) * tileImage.width + point[X], &tileImage.data);
+					for Δy in 0..blockHeight {
+						let ([mut xjump, nbpix], mut xsolid) = (
+							if blockHeight == MAX_BLOCKHEIGHT {
+								[0, BLOCKWIDTH as u8]
+							} else {
+								[XJUMP[Δy] as _, NBPIX[Δy] as _]
+							},
+							0,
+						);
+						i += tileImage.width;
+						let mut i = i + xjump as usize;
+						for Δx in 1..=nbpix {
+							let [mut nextXJump, mut nextXSolid] = [xjump, xsolid];
+							if imageData[i] == FULLY_TRANSPARENT {
+								nextXJump = xjump + 1;
+							} else {
+								nextXSolid = xsolid + 1;
+							};
+							if xsolid != 0 && nextXJump != xjump && {
+								xsolid = nextXSolid;
+								true
+							} || Δx == nbpix && nextXSolid != 0 && {
+								[xjump, xsolid] = [nextXJump, nextXSolid - 1];
+								true
+							} {
+								self.write_all(&[xjump, nextXSolid])?;
+								self.write_all(&imageData[i - xsolid as usize..][..nextXSolid as _])?;
+								nextXJump -= xjump;
+								nextXSolid = 0;
+							}
+							[xjump, xsolid] = [nextXJump, nextXSolid];
+							i += 1;
+						}
+						self.writeZeros(2)?;
+					}
+					Ok(())
+				}
+			}
+		}
+	}
+
+	/// A tile sheet's `tEXt` chunk of which rows of each tile with blocks it has, as "y,height y,height …",
+	///   when `--zealous-vertical-packing` cropped them; without it, they're the rows the tile's blocks are on.
+	pub const TILE_ROWS_PNG_KEYWORD: &str = "d2sw:tile-rows";
+
+	fn tileRowsOfPNG(info: &png::Info<'_>) -> Result<Option<Vec<(i16, usize)>>, Error> {
+		let Some(chunk) =
+			info.uncompressed_latin1_text.iter().find(|chunk| chunk.keyword == TILE_ROWS_PNG_KEYWORD)
+		else {
+			return Ok(None);
+		};
+		Result::from_iter(chunk.text.split_ascii_whitespace().map(|rows| {
+			rows
+				.split_once(',')
+				.and_then(|(y, height)| Some((y.parse().ok()?, height.parse().ok()?)))
+				.ok_or_else(|| Error::InvalidData {
+					offset: 0,
+					reason: format!("{TILE_ROWS_PNG_KEYWORD}: {rows:?} isn't a y and a height"),
+				})
+		}))
+		.map(Some)
+	}
+
+	// an indexed PNG of `image`, index 0 transparent, with whatever chunks `addChunks` adds
+	fn indexedPNG(
+		image: &Image,
+		pngPAL: &[u8],
+		addChunks: impl FnOnce(&mut png::Encoder<'_, &mut Vec<u8>>),
+	) -> Vec<u8> {
+		let mut bytes = Vec::new();
+		let mut png = png::Encoder::new(&mut bytes, image.width as _, image.height as _);
+		png.set_color(png::ColorType::Indexed);
+		png.set_palette(pngPAL);
+		png.set_trns(&[0][..]);
+		addChunks(&mut png);
+		png.write_header().unwrap().write_image_data(&image.data).unwrap();
+		bytes
+	}
+
+	pub const XJUMP: &[usize] = &[14, 12, 10, 8, 6, 4, 2, 0, 2, 4, 6, 8, 10, 12, 14];
+	pub const NBPIX: &[usize] = &[4, 8, 12, 16, 20, 24, 28, 32, 28, 24, 20, 16, 12, 8, 4];
+
+	type DrawFn<ImplDrawDestination> = fn(&mut ImplDrawDestination, x0: usize, y0: usize, data: &[u8]);
+
+	pub trait DrawDestination {
+		fn width(&self) -> usize;
+		fn putpixel(&mut self, atIndex: usize, value: u8);
+
+		/*
+			3D-isometric Block :
+
+			1st line : draw a line of 4 pixels
+			2nd line : draw a line of 8 pixels
+			3rd line : draw a line of 12 pixels
+			and so on...
+		*/
+		fn drawBlockIsometric(&mut self, x0: usize, y0: usize, data: &[u8]) {
+			let mut length = data.len();
+
+			// 3d-isometric subtile is 256 bytes, no more, no less
+			assert_eq!(length, 256);
+
+			// draw
+			let [mut Δy, width] = [0, self.width()];
+			let [mut i, mut yMulWidthAddX0] = [0, y0 * width + x0];
+			while length > 0 {
+				let [mut j, mut n] = [yMulWidthAddX0 + XJUMP[Δy], NBPIX[Δy]];
+				length -= n;
+				while n != 0 {
+					self.putpixel(j, data[i]);
+					i += 1;
+					j += 1;
+					n -= 1;
+				}
+				Δy += 1;
+				yMulWidthAddX0 += width;
+			}
+		}
+
+		/*
+			RLE Block :
+
+			1st byte is pixels to "jump", 2nd is number of "solid" pixels, followed by the pixel color indexes.
+			when 1st and 2nd bytes are 0 and 0, next line.
+		*/
+		fn drawBlockRLE(&mut self, x0: usize, y0: usize, data: &[u8]) {
+			let [mut length, width] = [data.len(), self.width()];
+
+			// draw
+			let (mut i, [mut j, mut yMulWidthAddX0]) = (0, [y0 * width + x0; 2]);
+			while length > 0 {
+				let [xjump, mut xsolid] = [data[i + 0] as usize, data[i + 1] as usize];
+				i += 2;
+				length -= 2;
+				if xjump != 0 || xsolid != 0 {
+					j += xjump;
+					length -= xsolid;
+					while xsolid != 0 {
+						self.putpixel(j, data[i]);
+						i += 1;
+						j += 1;
+						xsolid -= 1;
+					}
+				} else {
+					yMulWidthAddX0 += width;
+					j = yMulWidthAddX0;
+				}
+			}
+		}
+	}
+
+	pub const TILEWIDTH: usize = 160;
+	pub const FLOOR_ROOF_TILEHEIGHT: usize = 79 + 1;
+	pub const BLOCKWIDTH: usize = 32;
+	pub const FLOOR_ROOF_BLOCKHEIGHT: usize = NBPIX.len() + 1;
+	pub const MAX_BLOCKHEIGHT: usize = 32;
+
+	pub const SQUARE_TILE_SIZE: usize = TILEWIDTH / 2;
+	pub const SQUARE_SUBTILE_SIZE: usize = BLOCKWIDTH / 2;
+
+	pub const FLOOR_ORIENTATION: i32 = 0;
+	pub const SHADOW_ORIENTATION: i32 = 13;
+	pub const ROOF_ORIENTATION: i32 = 15;
+
+	#[macro_export]
+	macro_rules! lowerWalls {
+		() => {
+			(ROOF_ORIENTATION + 1)..
+		};
+	}
+
+	impl super::Image {
+		/// With a packed `layout`, also where the blocks went, in order.
+		pub fn fromDT1(tiles: &[Tile], dt1: &[u8], layout: Layout) -> (Self, Option<Packing>) {
+			let blocks = || tiles.iter().flat_map(|tile| tile.blocks.iter().map(move |block| (tile, block)));
+			if let Some(packing) =
+				layout.pack(&Vec::from_iter(blocks().map(|(tile, _)| [BLOCKWIDTH, tile.blockHeight()])))
+			{
+				let mut image = Self::fromWidthHeight(packing.dimensions[WIDTH], packing.dimensions[HEIGHT]);
+				for ((tile, block), &[x, y]) in iter::zip(blocks(), &packing.points) {
+					block.drawFn()(
+						&mut image,
+						x,
+						y,
+						&dt1[(tile.blockHeadersPointer + block.fileOffset) as _..][..block.length as _],
+					);
+				}
+				return (image, Some(packing));
+			}
+			let [mut minBlockHeight, mut maxBlockHeight] = [usize::MAX, 0];
+			for tile in tiles {
+				let blockHeight = tile.blockHeight();
+				if blockHeight == 0 {
+					continue;
+				}
+				minBlockHeight.minAssign(blockHeight);
+				maxBlockHeight.maxAssign(blockHeight);
+			}
+			let (width, height);
+			{
+				let chosenTileColumns = &{
+					let choices = &mut Vec::<TileColumns>::new();
+					choices.push(TileColumns {
+						fullColumnHeight: maxBlockHeight,
+						numOverflownColumns: 0,
+						lastColumnHeight: 0,
+					});
+					for tile in tiles {
+						let blockHeight = tile.blockHeight();
+						for _ in &tile.blocks {
+							choices.push(choices.last().unwrap().clone());
+							let mut i = 0;
+							while i < choices.len() {
+								let result = choices[i].pushTile(blockHeight);
+								if i == choices.len() - 2 {
+									let lastIndex = choices.len() - 1;
+									if result == 0 {
+										choices.truncate(lastIndex);
+									} else {
+										assert_eq!(choices[lastIndex].numOverflownColumns, 0);
+										choices[lastIndex].fullColumnHeight += minBlockHeight;
+										choices.push(choices[lastIndex].clone());
+									}
+								}
+								i += 1;
+							}
+						}
+					}
+					choices.sort_by(|a, b| {
+						let dimensions = [a, b].map(|tileColumns| tileColumns.dimensions(BLOCKWIDTH));
+						let pow2SquareSizes = dimensions.map(|[width, height]| max(width, height).next_power_of_two());
+						const A: usize = 0;
+						const B: usize = 1;
+						pow2SquareSizes[A]
+							.cmp(&pow2SquareSizes[B])
+							.then_with(|| dimensions[B][WIDTH].cmp(&dimensions[A][WIDTH]))
+					});
+					mem::take(&mut choices[0])
+				};
+				[width, height] = chosenTileColumns.dimensions(BLOCKWIDTH);
+				eprintln!(
+					"[{width}, {height}]; lastColumnHeight = {}, minBlockHeight = {minBlockHeight}",
+					chosenTileColumns.lastColumnHeight,
+				);
+			}
+			let mut image = Self::fromWidthHeight(width, height);
+			let (mut x, mut y) = (0, 0);
+			for tile in tiles {
+				for block in &tile.blocks {
+					let nextY = {
+						let blockHeight = tile.blockHeight();
+						let nextY = y + blockHeight;
+						if nextY > height {
+							x += BLOCKWIDTH;
+							y = 0;
+							blockHeight
+						} else {
+							nextY
+						}
+					};
+					block.drawFn()(
+						&mut image,
+						x,
+						y,
+						&dt1[(tile.blockHeadersPointer + block.fileOffset) as _..][..block.length as _],
+					);
+					y = nextY;
+				}
+			}
+			(image, None)
+		}
+
+		pub fn drawNoisySquareTile(&mut self, mut destPoint: Vec2, srcImage: &Self, mut srcPoint: Vec2) {
+			destPoint[Y] += 1;
+			srcPoint[X] += SQUARE_TILE_SIZE - 1;
+			let [mut iY, mut jY] = [srcPoint[Y] * srcImage.width, destPoint[Y] * self.width];
+			for Δx in 0..SQUARE_TILE_SIZE {
+				let [mut i, mut j] = [srcPoint[X] + iY, destPoint[X] + jY];
+				for Δy in 0..SQUARE_TILE_SIZE {
+					match srcImage.data[i] {
+						FULLY_TRANSPARENT => {}
+						pixelValue => {
+							assert_eq!(self.data[j], FULLY_TRANSPARENT);
+							self.data[j] = pixelValue;
+						}
+					}
+					i += usize::MAX + if Δy % 2 == 0 { 0 } else { srcImage.width };
+					j += self.width;
+				}
+				destPoint.addAssign([
+					1,
+					(if Δx % 2 == 0 {
+						srcPoint[X] += 2;
+						usize::MAX
+					} else {
+						srcPoint[Y] += 1;
+						iY += srcImage.width;
+						1
+					})
+					.also(|&Δy| jY += self.width.mulSignumOf(Δy)),
+				]);
+			}
+		}
+	}
+}
+
+pub mod dc6 {
+	use {
+		super::{Error, Image, MinAssign_MaxAssign_Ext, ReadExt, Vec2, FULLY_TRANSPARENT},
+		byteorder::{WriteBytesExt, LE},
+		serde::{Deserialize, Serialize},
+		std::io::{self, Cursor, Write},
+	};
+
+	#[derive(Serialize, Deserialize)]
+	pub struct Metadata {
+		pub fileHeader: FileHeader,
+
+		#[serde(rename = "frame")]
+		pub frames: Vec<Frame>,
+	}
+
+	#[derive(Serialize, Deserialize)]
+	pub struct FileHeader {
+		pub version: [i32; 3],
+		pub termination: [u8; 4],
+		pub numDirections: i32,
+		pub framesPerDirection: i32,
+	}
+
+	const EXPECTED_VERSION: [i32; 3] = [6, 1, 0];
+
+	const FILEHEADER_SIZE: i32 = 24;
+	const FRAMEHEADER_SIZE: i32 = 32;
+	const END_OF_LINE: u8 = 0x80;
+	const MAX_RUN_LENGTH: usize = 0x7F;
+
+	/// `[offsetX, offsetY]` is where the frame's bottom-left corner goes relative to the sprite's anchor.
+	#[derive(Serialize, Deserialize)]
+	pub struct Frame {
+		pub flip: i32,
+		pub width: i32,
+		pub height: i32,
+		pub offsetX: i32,
+		pub offsetY: i32,
+		pub unknown: [u8; 4],
+		pub nextBlock: i32,
+		pub length: i32,
+		pub termination: [u8; 3],
+		pub framePointer: i32,
+	}
+
+	impl Metadata {
+		pub fn new(dc6: &[u8]) -> Result<Self, Error> {
+			let mut cursor = io::Cursor::new(dc6);
+			let version = [cursor.readLE()?, cursor.readLE()?, cursor.readLE()?];
+			if version != EXPECTED_VERSION {
+				return Err(Error::UnsupportedVersion(format!(
+					"dc6.fileHeader.version == {version:?} != {EXPECTED_VERSION:?}"
+				)));
+			}
+			let termination = cursor.read_u8_array()?;
+			let countsOffset = cursor.position();
+			let fileHeader = FileHeader {
+				version,
+				termination,
+				numDirections: cursor.readCount("dc6.fileHeader.numDirections")? as _,
+				framesPerDirection: cursor.readCount("dc6.fileHeader.framesPerDirection")? as _,
+			};
+			let numFrames =
+				fileHeader.numDirections.checked_mul(fileHeader.framesPerDirection).ok_or_else(|| {
+					Error::InvalidData {
+						offset: countsOffset,
+						reason: format!(
+							"dc6.fileHeader.numDirections * framesPerDirection == {} * {} overflows",
+							fileHeader.numDirections, fileHeader.framesPerDirection,
+						),
+					}
+				})? as usize;
+			cursor.ensureRemaining(numFrames * 4)?;
+			let framePointers = Result::<Vec<i32>, _>::from_iter((0..numFrames).map(|_| cursor.readLE()))?;
+			let mut frames = Vec::with_capacity(framePointers.len());
+			for (i, framePointer) in framePointers.into_iter().enumerate() {
+				if cursor.position() != framePointer as u64 {
+					return Err(Error::BadPointer {
+						what: format!("dc6.frame[{i}].framePointer"),
+						pointer: framePointer.into(),
+						offset: cursor.position(),
+					});
+				}
+				let flip = cursor.readLE()?;
+				let [width, height] = [
+					cursor.readCount(&format!("dc6.frame[{i}].width"))?,
+					cursor.readCount(&format!("dc6.frame[{i}].height"))?,
+				];
+				let [offsetX, offsetY] = [cursor.readLE()?, cursor.readLE()?];
+				let unknown = cursor.read_u8_array()?;
+				let (nextBlock, length) = (cursor.readLE()?, cursor.readCount(&format!("dc6.frame[{i}].length"))?);
+				cursor.ensureRemaining(length)?;
+				cursor.set_position(cursor.position() + length as u64);
+				frames.push(Frame {
+					flip,
+					width: width as _,
+					height: height as _,
+					offsetX,
+					offsetY,
+					unknown,
+					nextBlock,
+					length: length as _,
+					termination: cursor.read_u8_array()?,
+					framePointer,
+				});
+			}
+			if cursor.remaining() != 0 {
+				return Err(Error::TrailingBytes { offset: cursor.position(), len: cursor.remaining() });
+			}
+			Ok(Self { fileHeader, frames })
+		}
+
+		/// The size of each cell of the frames image: one row per direction, one column per frame.
+		pub fn cellDimensions(&self) -> Vec2 {
+			let [mut width, mut height] = [0_i32; 2];
+			for frame in &self.frames {
+				width.maxAssign(frame.width);
+				height.maxAssign(frame.height);
+			}
+			[width as _, height as _]
+		}
+
+		/// Where a frame's cell is in the frames image, `cellDimensions` being what [`Self::cellDimensions`] gives,
+		///   worked out once by the caller rather than over every frame for each one.
+		#[inline(always)]
+		pub fn cellPoint(&self, [cellWidth, cellHeight]: Vec2, frameIndex: usize) -> Vec2 {
+			let framesPerDirection = self.fileHeader.framesPerDirection as usize;
+			[frameIndex % framesPerDirection * cellWidth, frameIndex / framesPerDirection * cellHeight]
+		}
+
+		pub fn frameImage(&self, frameIndex: usize, dc6: &[u8]) -> Result<Image, Error> {
+			let frame = &self.frames[frameIndex];
+			let mut image = Image::fromWidthHeight(frame.width as _, frame.height as _);
+			let dataOffset = (frame.framePointer + FRAMEHEADER_SIZE) as usize;
+			let data = &dc6[dataOffset..][..frame.length as usize];
+			let (mut i, mut x, mut row) = (0, 0, 0);
+			while i < data.len() && row < image.height {
+				let byte = data[i];
+				i += 1;
+				match byte {
+					END_OF_LINE => {
+						row += 1;
+						x = 0;
+					}
+					_ if byte & END_OF_LINE != 0 => x += (byte & !END_OF_LINE) as usize,
+					_ => {
+						let (y, length) = (frame.y(row, image.height), byte as usize);
+						if x + length > image.width || i + length > data.len() {
+							return Err(Error::InvalidData {
+								offset: (dataOffset + i - 1) as _,
+								reason: format!(
+									"dc6.frame[{frameIndex}]: a run of {length} pixels at x == {x} overruns {}",
+									if x + length > image.width { "the row" } else { "the frame's data" },
+								),
+							});
+						}
+						image.data[x + y * image.width..][..length].copy_from_slice(&data[i..][..length]);
+						i += length;
+						x += length;
+					}
+				}
+			}
+			Ok(image)
+		}
+
+		pub fn writeWithFrameDataFromFramesImage(
+			&self,
+			framesImage: &Image,
+			to: &mut impl Write,
+		) -> Result<(), Error> {
+			let (Self { fileHeader, frames }, cursor) = (self, &mut Cursor::new(Vec::new()));
+			fileHeader.version.iter().try_for_each(|&versionElem| cursor.write_i32::<LE>(versionElem))?;
+			cursor.write_all(&fileHeader.termination)?;
+			cursor.write_i32::<LE>(fileHeader.numDirections)?;
+			cursor.write_i32::<LE>(fileHeader.framesPerDirection)?;
+			let mut framePointer = FILEHEADER_SIZE + frames.len() as i32 * 4;
+			let cellDimensions = self.cellDimensions();
+			let framesData = Result::<Vec<_>, Error>::from_iter(frames.iter().enumerate().map(|(i, frame)| {
+				let point @ [x, y] = self.cellPoint(cellDimensions, i);
+				if x + frame.width as usize > framesImage.width || y + frame.height as usize > framesImage.height {
+					return Err(Error::InvalidData {
+						offset: 0,
+						reason: format!(
+							"dc6.frame[{i}] at {point:?} sticks out of the {}x{} frames image",
+							framesImage.width, framesImage.height,
+						),
+					});
+				}
+				let data = frame.encode(framesImage, point);
+				cursor.write_i32::<LE>(framePointer)?;
+				framePointer += FRAMEHEADER_SIZE + data.len() as i32 + frame.termination.len() as i32;
+				Ok((data, framePointer))
+			}))?;
+			for (frame, (data, nextBlock)) in frames.iter().zip(framesData) {
+				let &Frame {
+					flip,
+					width,
+					height,
+					offsetX,
+					offsetY,
+					ref unknown,
+					nextBlock: _,
+					length: _,
+					ref termination,
+					framePointer: _,
+				} = frame;
+				cursor.write_i32::<LE>(flip)?;
+				cursor.write_i32::<LE>(width)?;
+				cursor.write_i32::<LE>(height)?;
+				cursor.write_i32::<LE>(offsetX)?;
+				cursor.write_i32::<LE>(offsetY)?;
+				cursor.write_all(unknown)?;
+				cursor.write_i32::<LE>(nextBlock)?;
+				cursor.write_i32::<LE>(data.len() as _)?;
+				cursor.write_all(&data)?;
+				cursor.write_all(termination)?;
+			}
+			to.write_all(cursor.get_ref())?;
+			Ok(())
+		}
+	}
+
+	impl Frame {
+		// frames are stored bottom-up unless flipped
+		#[inline(always)]
+		fn y(&self, row: usize, height: usize) -> usize {
+			if self.flip == 0 {
+				height - 1 - row
+			} else {
+				row
+			}
+		}
+
+		// runs of transparent pixels are skipped, opaque ones are copied, and either kind is cut every
+		//   MAX_RUN_LENGTH pixels; a row's trailing transparent pixels are left to END_OF_LINE
+		fn encode(&self, framesImage: &Image, [x0, y0]: Vec2) -> Vec<u8> {
+			let (width, height) = (self.width as usize, self.height as usize);
+			let mut data = Vec::new();
+			for row in 0..height {
+				let line = &framesImage.data[x0 + (y0 + self.y(row, height)) * framesImage.width..][..width];
+				let end = line.iter().rposition(|&pixel| pixel != FULLY_TRANSPARENT).map_or(0, |x| x + 1);
+				let mut x = 0;
+				while x < end {
+					let isTransparent = line[x] == FULLY_TRANSPARENT;
+					let length = line[x..end]
+						.iter()
+						.take(MAX_RUN_LENGTH)
+						.take_while(|&&pixel| (pixel == FULLY_TRANSPARENT) == isTransparent)
+						.count();
+					if isTransparent {
+						data.push(END_OF_LINE | length as u8);
+					} else {
+						data.push(length as _);
+						data.extend_from_slice(&line[x..][..length]);
+					}
+					x += length;
+				}
+				data.push(END_OF_LINE);
+			}
+			data
+		}
+	}
+
+	impl super::Image {
+		pub fn fromDC6(metadata: &Metadata, dc6: &[u8]) -> Result<Self, Error> {
+			let cellDimensions @ [cellWidth, cellHeight] = metadata.cellDimensions();
+			let FileHeader { numDirections, framesPerDirection, .. } = metadata.fileHeader;
+			let mut image = Self::fromWidthHeight(
+				cellWidth * framesPerDirection as usize,
+				cellHeight * numDirections as usize,
+			);
+			for i in 0..metadata.frames.len() {
+				let frameImage = &metadata.frameImage(i, dc6)?;
+				image.blitPixelsRectangle(
+					metadata.cellPoint(cellDimensions, i),
+					[frameImage.width, frameImage.height],
+					frameImage,
+					[0, 0],
+				);
+			}
+			Ok(image)
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		// two directions of two frames, one of them flipped and one empty, whose rows have runs of either kind
+		//   longer than MAX_RUN_LENGTH, end in transparent pixels, or have none but those
+		fn sample() -> (Metadata, Image) {
+			const FRAME_DIMENSIONS: [Vec2; 4] = [[300, 4], [200, 3], [1, 1], [0, 0]];
+			let frames =
+				Vec::from_iter(FRAME_DIMENSIONS.iter().enumerate().map(|(i, &[width, height])| Frame {
+					flip: (i % 2) as _,
+					width: width as _,
+					height: height as _,
+					offsetX: -(i as i32),
+					offsetY: i as _,
+					unknown: [0; 4],
+					nextBlock: 0,
+					length: 0,
+					termination: [0xEE; 3],
+					framePointer: 0,
+				}));
+			let metadata = Metadata {
+				fileHeader: FileHeader {
+					version: EXPECTED_VERSION,
+					termination: [0xEE; 4],
+					numDirections: 2,
+					framesPerDirection: 2,
+				},
+				frames,
+			};
+			let cellDimensions @ [cellWidth, cellHeight] = metadata.cellDimensions();
+			let mut image = Image::fromWidthHeight(2 * cellWidth, 2 * cellHeight);
+			for (i, &[width, height]) in FRAME_DIMENSIONS.iter().enumerate() {
+				let [x0, y0] = metadata.cellPoint(cellDimensions, i);
+				for y in (0..height).filter(|&y| y != 2) {
+					for x in (0..width.saturating_sub(10)).filter(|&x| (x / 140 + y) % 2 == 0) {
+						image.data[x0 + x + (y0 + y) * image.width] = ((x * 3 + y * 5) % 255 + 1) as _;
+					}
+				}
+			}
+			(metadata, image)
+		}
+
+		#[test]
+		fn framesImageSurvivesEncodingAndDecoding() {
+			let (metadata, image) = sample();
+			let dc6 = &mut Vec::new();
+			metadata.writeWithFrameDataFromFramesImage(&image, dc6).unwrap();
+			let decoded = Metadata::new(dc6).unwrap();
+			assert_eq!(decoded.frames.len(), metadata.frames.len());
+			let cellDimensions = decoded.cellDimensions();
+			for (i, frame) in decoded.frames.iter().enumerate() {
+				let frameImage = decoded.frameImage(i, dc6).unwrap();
+				let [x0, y0] = decoded.cellPoint(cellDimensions, i);
+				assert_eq!([frameImage.width, frameImage.height], [frame.width as _, frame.height as _]);
+				for y in 0..frameImage.height {
+					assert_eq!(
+						&frameImage.data[y * frameImage.width..][..frameImage.width],
+						&image.data[x0 + (y0 + y) * image.width..][..frameImage.width],
+						"frame #{i}, row {y}",
+					);
+				}
+			}
+			let reencoded = &mut Vec::new();
+			decoded
+				.writeWithFrameDataFromFramesImage(&Image::fromDC6(&decoded, dc6).unwrap(), reencoded)
+				.unwrap();
+			assert_eq!(reencoded, dc6);
+		}
+
+		#[test]
+		fn malformedFramesAreInvalidData() {
+			let (metadata, image) = sample();
+			let dc6 = &mut Vec::new();
+			metadata.writeWithFrameDataFromFramesImage(&image, dc6).unwrap();
+			let widthOffset = Metadata::new(dc6).unwrap().frames[0].framePointer as usize + 4;
+
+			let negativeWidth = &mut dc6.clone();
+			negativeWidth[widthOffset..][..4].copy_from_slice(&(-1_i32).to_le_bytes());
+			assert!(matches!(Metadata::new(negativeWidth), Err(Error::InvalidData { .. })));
+
+			// the first row stored, the bottom one, has its first opaque run at x == 140, past a narrower frame's end
+			let narrowerFrame = &mut dc6.clone();
+			narrowerFrame[widthOffset..][..4].copy_from_slice(&100_i32.to_le_bytes());
+			let narrowerMetadata = Metadata::new(narrowerFrame).unwrap();
+			assert!(matches!(narrowerMetadata.frameImage(0, narrowerFrame), Err(Error::InvalidData { .. })));
+
+			let overflowing = &mut dc6.clone();
+			overflowing[16..][..8].copy_from_slice(&[i32::MAX.to_le_bytes(), 2_i32.to_le_bytes()].concat());
+			assert!(matches!(Metadata::new(overflowing), Err(Error::InvalidData { offset: 16, .. })));
+		}
+	}
+}
+
+pub mod tmx {
+	use {
+		super::{
+			ds1::{
+				existsTagLayer, Group, Node, Object, Path, RootStruct, LAYER_DRAWING_PRIORITY_MASK,
+				MAIN_INDEX_MASK, MAIN_INDEX_MAX, MAIN_INDEX_OFFSET, ORIENTATION_MASK, SUB_INDEX_MASK,
+				SUB_INDEX_MAX, SUB_INDEX_OFFSET,
+			},
+			dt1::{
+				self, FLOOR_ORIENTATION, FLOOR_ROOF_TILEHEIGHT, SHADOW_ORIENTATION, SQUARE_SUBTILE_SIZE, TILEWIDTH,
+			},
+			Vec2,
+		},
+		base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+		core::{fmt, iter, str::FromStr},
+		flate2::read::{GzDecoder, ZlibDecoder},
+		roxmltree::{Document, Node as XMLNode},
+		std::{
+			collections::{BTreeSet, HashMap},
+			error,
+			io::{self, Read, Write},
+		},
+	};
+
+	pub const FIRSTGID: u32 = 1;
+
+	/// Pixels per tile along either map axis of an isometric Tiled map, which is what object coordinates
+	/// are measured in; DS1 objects and paths are positioned in subtiles, groups in whole tiles.
+	pub const TILE_UNIT: usize = FLOOR_ROOF_TILEHEIGHT;
+	pub const SUBTILE_UNIT: usize = SQUARE_SUBTILE_SIZE;
+
+	/*
+		A gid is `[orientation, mainIndex, subIndex]` read as a mixed-radix number (offset by FIRSTGID),
+		which tops out at 2.pow(8 + 6 + 8) and thus never collides with Tiled's flipping flags.
+		Whatever a cell holds besides its main/sub index (the drawing priority among others) goes into the
+		"cellBits" layer property instead; so does the whole cell when it isn't drawn at all. Cells painted
+		in Tiled have no cellBits to speak of, so they get the "drawingPriority" layer property, which the
+		export sets to the priority most cells of that layer already have.
+	*/
+	#[inline(always)]
+	pub const fn gid(orientation: u32, mainIndex: u32, subIndex: u32) -> u32 {
+		FIRSTGID + (orientation * (MAIN_INDEX_MAX + 1) + mainIndex) * (SUB_INDEX_MAX + 1) + subIndex
+	}
+
+	#[inline(always)]
+	pub const fn gidComponents(gid: u32) -> [u32; 3] {
+		let id = gid - FIRSTGID;
+		[
+			id / (SUB_INDEX_MAX + 1) / (MAIN_INDEX_MAX + 1),
+			id / (SUB_INDEX_MAX + 1) % (MAIN_INDEX_MAX + 1),
+			id % (SUB_INDEX_MAX + 1),
+		]
+	}
+
+	#[inline(always)]
+	pub const fn splitCell(cell: u32, orientation: u32) -> [u32; 2] {
+		if cell & LAYER_DRAWING_PRIORITY_MASK == 0 {
+			[0, cell]
+		} else {
+			[
+				gid(
+					orientation,
+					cell >> MAIN_INDEX_OFFSET & MAIN_INDEX_MAX,
+					cell >> SUB_INDEX_OFFSET & SUB_INDEX_MAX,
+				),
+				cell & !(MAIN_INDEX_MASK | SUB_INDEX_MASK),
+			]
+		}
+	}
+
+	pub const CELL_BITS: &str = "cellBits";
+	pub const DRAWING_PRIORITY: &str = "drawingPriority";
+	pub const ORIENTATION_BITS: &str = "orientationBits";
+	/// The tileset property saying how its tile IDs map to cells; "arithmetic" for the one writeTMXTo emits,
+	/// whose IDs are gids less FIRSTGID.
+	pub const GIDS: &str = "d2sw:gids";
+	pub const ARITHMETIC_GIDS: &str = "arithmetic";
+
+	pub fn wallLayerName(i: usize) -> String {
+		format!("wall{}", i + 1)
+	}
+	pub fn floorLayerName(i: usize) -> String {
+		format!("floor{}", i + 1)
+	}
+	pub const SHADOW_LAYER_NAME: &str = "shadow";
+	pub const TAG_LAYER_NAME: &str = "tag";
+	pub const OBJECTS_LAYER_NAME: &str = "objects";
+	pub const GROUPS_LAYER_NAME: &str = "groups";
+	pub const PATHS_LAYER_NAME: &str = "paths";
+
+	impl RootStruct {
+		pub fn writeTMXTo(&self, to: &mut impl Write) {
+			let &RootStruct {
+				version,
+				xMax,
+				yMax,
+				actIndex,
+				tagType,
+				ref files,
+				ref unknown,
+				numWallLayers,
+				numFloors,
+				ref layers,
+				ref objects,
+				ref groups,
+				ref paths,
+			} = self;
+			let [width, height, numWallLayers, numFloors] =
+				[xMax + 1, yMax + 1, numWallLayers, numFloors].map(|n| n as usize);
+			let (mut layerID, mut objectID) = (1, 1);
+
+			// [name, gids, cellBits, orientationBits] per tile layer, bottom-most first, the way Tiled stacks them
+			let mut tileLayers = Vec::with_capacity(layers.len() - numWallLayers);
+			{
+				let mut pushTileLayer = |name, layer: &[u32], orientations: Result<&[u32], u32>| {
+					let (mut gids, mut cellBits, mut orientationBits) =
+						(Vec::with_capacity(layer.len()), Vec::with_capacity(layer.len()), Vec::new());
+					for (j, &cell) in layer.iter().enumerate() {
+						let orientation = orientations.map_or_else(|orientation| orientation, |layer| layer[j]);
+						let [gid, bits] = splitCell(cell, orientation & ORIENTATION_MASK);
+						gids.push(gid);
+						cellBits.push(bits);
+						if orientations.is_ok() {
+							orientationBits.push(if gid == 0 { orientation } else { orientation & !ORIENTATION_MASK });
+						}
+					}
+					tileLayers.push((name, gids, cellBits, orientationBits));
+				};
+				let floorLayers = &layers[numWallLayers * 2..][..numFloors];
+				for (i, floorLayer) in floorLayers.iter().enumerate() {
+					pushTileLayer(floorLayerName(i), floorLayer, Err(FLOOR_ORIENTATION as _));
+				}
+				pushTileLayer(
+					SHADOW_LAYER_NAME.to_owned(),
+					&layers[numWallLayers * 2 + numFloors],
+					Err(SHADOW_ORIENTATION as _),
+				);
+				for i in 0..numWallLayers {
+					pushTileLayer(wallLayerName(i), &layers[i * 2], Ok(&layers[i * 2 + 1]));
+				}
+			}
+
+			writeln!(to, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+			writeln!(
+				to,
+				concat!(
+					r#"<map version="1.10" tiledversion="1.10.2" orientation="isometric" renderorder="right-down""#,
+					r#" width="{}" height="{}" tilewidth="{}" tileheight="{}" infinite="0">"#,
+				),
+				width, height, TILEWIDTH, FLOOR_ROOF_TILEHEIGHT,
+			)
+			.unwrap();
+			writeln!(to, " <properties>").unwrap();
+			for (name, value) in [("version", version), ("actIndex", actIndex), ("tagType", tagType)] {
+				writeln!(to, r#"  <property name="{name}" type="int" value="{value}"/>"#).unwrap();
+			}
+			writeln!(to, r#"  <property name="files">{}</property>"#, escape(&files.join("\n"))).unwrap();
+			if let Some(unknown) = unknown {
+				writeln!(to, r#"  <property name="unknown" value="{}"/>"#, csv(unknown.iter())).unwrap();
+			}
+			writeln!(to, " </properties>").unwrap();
+
+			{
+				let usedGIDs = &mut BTreeSet::new();
+				for (_, gids, ..) in &tileLayers {
+					usedGIDs.extend(gids.iter().copied().filter(|&gid| gid != 0));
+				}
+				writeln!(
+					to,
+					r#" <tileset firstgid="{FIRSTGID}" name="ds1" tilewidth="{TILEWIDTH}" tileheight="{}" tilecount="{}" columns="0">"#,
+					FLOOR_ROOF_TILEHEIGHT,
+					usedGIDs.len(),
+				)
+				.unwrap();
+				writeln!(
+					to,
+					r#"  <grid orientation="isometric" width="{TILEWIDTH}" height="{FLOOR_ROOF_TILEHEIGHT}"/>"#,
+				)
+				.unwrap();
+				writeln!(to, "  <properties>").unwrap();
+				writeln!(to, r#"   <property name="{GIDS}" value="{ARITHMETIC_GIDS}"/>"#).unwrap();
+				writeln!(to, "  </properties>").unwrap();
+				for &gid in usedGIDs.iter() {
+					let [orientation, mainIndex, subIndex] = gidComponents(gid);
+					writeln!(to, r#"  <tile id="{}">"#, gid - FIRSTGID).unwrap();
+					writeln!(to, "   <properties>").unwrap();
+					for (name, value) in
+						[("orientation", orientation), ("mainIndex", mainIndex), ("subIndex", subIndex)]
+					{
+						writeln!(to, r#"    <property name="{name}" type="int" value="{value}"/>"#).unwrap();
+					}
+					writeln!(to, "   </properties>").unwrap();
+					writeln!(to, "  </tile>").unwrap();
+				}
+				writeln!(to, " </tileset>").unwrap();
+			}
+
+			let mut writeTileLayer = |name: &str, gids: &[u32], bits: &[(&str, &[u32])], visible: bool| {
+				writeln!(
+					to,
+					r#" <layer id="{layerID}" name="{name}" width="{width}" height="{height}"{}>"#,
+					if visible { "" } else { r#" visible="0""# },
+				)
+				.unwrap();
+				layerID += 1;
+				let drawingPriority = {
+					let counts = &mut [0_usize; (LAYER_DRAWING_PRIORITY_MASK + 1) as _];
+					for (&gid, &cellBits) in iter::zip(gids, bits[0].1) {
+						if gid != 0 {
+							counts[(cellBits & LAYER_DRAWING_PRIORITY_MASK) as usize] += 1;
+						}
+					}
+					(0..counts.len()).max_by_key(|&i| counts[i]).filter(|&i| counts[i] != 0)
+				};
+				if drawingPriority.is_some() || bits.iter().any(|(_, bits)| bits.iter().any(|&bits| bits != 0)) {
+					writeln!(to, "  <properties>").unwrap();
+					if let Some(drawingPriority) = drawingPriority {
+						writeln!(
+							to,
+							r#"   <property name="{DRAWING_PRIORITY}" type="int" value="{drawingPriority}"/>"#
+						)
+						.unwrap();
+					}
+					for &(name, bits) in bits {
+						if bits.iter().any(|&bits| bits != 0) {
+							writeln!(to, r#"   <property name="{name}" value="{}"/>"#, csv(bits.iter())).unwrap();
+						}
+					}
+					writeln!(to, "  </properties>").unwrap();
+				}
+				writeln!(to, r#"  <data encoding="csv">"#).unwrap();
+				for (i, row) in gids.chunks(width).enumerate() {
+					writeln!(to, "{}{}", csv(row.iter()), if i == height - 1 { "" } else { "," }).unwrap();
+				}
+				writeln!(to, "  </data>").unwrap();
+				writeln!(to, " </layer>").unwrap();
+			};
+			for (name, gids, cellBits, orientationBits) in &tileLayers {
+				writeTileLayer(name, gids, &[(CELL_BITS, cellBits), (ORIENTATION_BITS, orientationBits)], true);
+			}
+			if existsTagLayer(tagType) {
+				writeTileLayer(
+					TAG_LAYER_NAME,
+					&vec![0; width * height],
+					&[(CELL_BITS, layers.last().unwrap())],
+					false,
+				);
+			}
+
+			let mut writeObjectGroup = |name: &str, objects: &mut dyn Iterator<Item = [String; 2]>| {
+				writeln!(to, r#" <objectgroup id="{layerID}" name="{name}">"#).unwrap();
+				layerID += 1;
+				for [attributes, contents] in objects {
+					writeln!(to, r#"  <object id="{objectID}"{attributes}>"#).unwrap();
+					objectID += 1;
+					write!(to, "{contents}").unwrap();
+					writeln!(to, "  </object>").unwrap();
+				}
+				writeln!(to, " </objectgroup>").unwrap();
+			};
+			writeObjectGroup(
+				OBJECTS_LAYER_NAME,
+				&mut objects.iter().flatten().map(|&Object { r#type, id, x, y, flags }| {
+					[
+						format!(r#" x="{}" y="{}""#, x * SUBTILE_UNIT as i32, y * SUBTILE_UNIT as i32),
+						properties(&[("type", r#type), ("id", id), ("flags", flags)], "") + "   <point/>\n",
+					]
+				}),
+			);
+			writeObjectGroup(
+				GROUPS_LAYER_NAME,
+				&mut groups.iter().flatten().map(|&Group { x, y, width, height, unknown }| {
+					[
+						format!(
+							r#" x="{}" y="{}" width="{}" height="{}""#,
+							x * TILE_UNIT as i32,
+							y * TILE_UNIT as i32,
+							width * TILE_UNIT as i32,
+							height * TILE_UNIT as i32,
+						),
+						properties(&[("unknown", unknown)], ""),
+					]
+				}),
+			);
+			writeObjectGroup(
+				PATHS_LAYER_NAME,
+				&mut paths.iter().flatten().map(|&Path { x, y, ref nodes }| {
+					[
+						format!(r#" x="{}" y="{}""#, x * SUBTILE_UNIT as i32, y * SUBTILE_UNIT as i32),
+						properties(
+							&[],
+							&format!(
+								r#"    <property name="actions" value="{}"/>{}"#,
+								csv(nodes.iter().map(|node| node.action)),
+								"\n",
+							),
+						) + &if nodes.is_empty() {
+							"   <point/>\n".to_owned()
+						} else {
+							format!(
+								"   <polyline points=\"{}\"/>\n",
+								Vec::from_iter(nodes.iter().map(|&Node { x: nodeX, y: nodeY, .. }| {
+									format!("{},{}", (nodeX - x) * SUBTILE_UNIT as i32, (nodeY - y) * SUBTILE_UNIT as i32)
+								}))
+								.join(" "),
+							)
+						},
+					]
+				}),
+			);
+			writeln!(to, "</map>").unwrap();
+
+			fn properties(intProperties: &[(&str, i32)], rest: &str) -> String {
+				let mut string = String::from("   <properties>\n");
+				for (name, value) in intProperties {
+					string += &format!(r#"    <property name="{name}" type="int" value="{value}"/>{}"#, "\n");
+				}
+				string + rest + "   </properties>\n"
+			}
+		}
+	}
+
+	/// Where a tileset's tiles sit: a grid of `columns` cells, TILEWIDTH x `tileheight` each, the way
+	/// `4_-_dt1TOML-tilePNG_into_tiledPNG` lays them out, every tile's floor diamond at the same spot of its
+	/// cell, the cells' bottom edge `tileoffsetY` below the diamond's bottom corner.
+	pub struct TilesetImage<'a> {
+		pub source: &'a str,
+		pub dimensions: Vec2,
+		pub tileheight: usize,
+		pub columns: usize,
+		pub tileoffsetY: i32,
+	}
+
+	pub const ANIMATION_FRAME_DURATION: u32 = 100;
+
+	enum PropertyValue {
+		Int(i32),
+		String(String),
+	}
+
+	impl dt1::Metadata {
+		// [properties, animation frames] per tile; the animation goes to the lowest-rarityOrFrameIndex tile
+		//   of those sharing orientation, mainIndex and subIndex.
+		fn tilesetTiles(&self) -> Vec<(Vec<(&'static str, PropertyValue)>, Vec<usize>)> {
+			let frames = &mut HashMap::<[i32; 3], Vec<usize>>::new();
+			for (i, tile) in self.tiles.iter().enumerate() {
+				frames.entry([tile.orientation, tile.mainIndex, tile.subIndex]).or_default().push(i);
+			}
+			for frames in frames.values_mut() {
+				frames.sort_by_key(|&i| self.tiles[i].rarityOrFrameIndex);
+			}
+			let mut tilesetTiles = Vec::with_capacity(self.tiles.len());
+			for (i, tile) in self.tiles.iter().enumerate() {
+				use PropertyValue::{Int, String};
+				let &dt1::Tile {
+					orientation,
+					mainIndex,
+					subIndex,
+					rarityOrFrameIndex,
+					materialFlags,
+					subtileFlags,
+					..
+				} = tile;
+				tilesetTiles.push((
+					vec![
+						("orientation", Int(orientation)),
+						("mainIndex", Int(mainIndex)),
+						("subIndex", Int(subIndex)),
+						("rarityOrFrameIndex", Int(rarityOrFrameIndex)),
+						("materialFlags", String(csv(materialFlags.iter()))),
+						("subtileFlags", String(csv(subtileFlags.iter()))),
+					],
+					match &frames[&[orientation, mainIndex, subIndex]][..] {
+						frames @ [first, _, ..] if *first == i => frames.to_vec(),
+						_ => Vec::new(),
+					},
+				));
+			}
+			tilesetTiles
+		}
+
+		pub fn writeTSXTo(&self, to: &mut impl Write, name: &str, image: &TilesetImage<'_>) {
+			let &TilesetImage {
+				source,
+				dimensions: [imageWidth, imageHeight],
+				tileheight,
+				columns,
+				tileoffsetY,
+			} = image;
+			writeln!(to, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+			writeln!(
+				to,
+				concat!(
+					r#"<tileset version="1.10" tiledversion="1.10.2" name="{}" tilewidth="{}" tileheight="{}""#,
+					r#" tilecount="{}" columns="{}">"#,
+				),
+				escape(name),
+				TILEWIDTH,
+				tileheight,
+				self.tiles.len(),
+				columns,
+			)
+			.unwrap();
+			writeln!(to, r#" <tileoffset x="0" y="{tileoffsetY}"/>"#).unwrap();
+			writeln!(
+				to,
+				r#" <grid orientation="isometric" width="{TILEWIDTH}" height="{FLOOR_ROOF_TILEHEIGHT}"/>"#
+			)
+			.unwrap();
+			writeln!(to, r#" <image source="{}" width="{imageWidth}" height="{imageHeight}"/>"#, escape(source))
+				.unwrap();
+			for (i, (properties, frames)) in self.tilesetTiles().into_iter().enumerate() {
+				writeln!(to, r#" <tile id="{i}">"#).unwrap();
+				writeln!(to, "  <properties>").unwrap();
+				for (name, value) in properties {
+					match value {
+						PropertyValue::Int(value) => {
+							writeln!(to, r#"   <property name="{name}" type="int" value="{value}"/>"#).unwrap();
+						}
+						PropertyValue::String(value) => {
+							writeln!(to, r#"   <property name="{name}" value="{value}"/>"#).unwrap();
+						}
+					}
+				}
+				writeln!(to, "  </properties>").unwrap();
+				if !frames.is_empty() {
+					writeln!(to, "  <animation>").unwrap();
+					for tileid in frames {
+						writeln!(to, r#"   <frame tileid="{tileid}" duration="{ANIMATION_FRAME_DURATION}"/>"#)
+							.unwrap();
+					}
+					writeln!(to, "  </animation>").unwrap();
+				}
+				writeln!(to, " </tile>").unwrap();
+			}
+			writeln!(to, "</tileset>").unwrap();
+		}
+
+		pub fn writeTSJTo(&self, to: &mut impl Write, name: &str, image: &TilesetImage<'_>) {
+			let &TilesetImage {
+				source,
+				dimensions: [imageWidth, imageHeight],
+				tileheight,
+				columns,
+				tileoffsetY,
+			} = image;
+			writeln!(to, "{{").unwrap();
+			for (key, value) in [
+				("type", jsonString("tileset")),
+				("version", jsonString("1.10")),
+				("tiledversion", jsonString("1.10.2")),
+				("name", jsonString(name)),
+				("tilewidth", TILEWIDTH.to_string()),
+				("tileheight", tileheight.to_string()),
+				("tilecount", self.tiles.len().to_string()),
+				("columns", columns.to_string()),
+				("margin", "0".to_owned()),
+				("spacing", "0".to_owned()),
+				("tileoffset", format!(r#"{{ "x": 0, "y": {tileoffsetY} }}"#)),
+				(
+					"grid",
+					format!(
+						r#"{{ "orientation": "isometric", "width": {TILEWIDTH}, "height": {FLOOR_ROOF_TILEHEIGHT} }}"#
+					),
+				),
+				("image", jsonString(source)),
+				("imagewidth", imageWidth.to_string()),
+				("imageheight", imageHeight.to_string()),
+			] {
+				writeln!(to, r#" "{key}": {value},"#).unwrap();
+			}
+			writeln!(to, r#" "tiles": ["#).unwrap();
+			let tilesetTiles = self.tilesetTiles();
+			for (i, (properties, frames)) in tilesetTiles.iter().enumerate() {
+				writeln!(to, r#"  {{ "id": {i}, "properties": ["#).unwrap();
+				for (j, (name, value)) in properties.iter().enumerate() {
+					writeln!(
+						to,
+						r#"   {{ "name": "{name}", {} }}{}"#,
+						match value {
+							PropertyValue::Int(value) => format!(r#""type": "int", "value": {value}"#),
+							PropertyValue::String(value) =>
+								format!(r#""type": "string", "value": {}"#, jsonString(value)),
+						},
+						if j == properties.len() - 1 { "" } else { "," },
+					)
+					.unwrap();
+				}
+				write!(to, "  ]").unwrap();
+				if !frames.is_empty() {
+					write!(
+						to,
+						r#", "animation": [{}]"#,
+						Vec::from_iter(frames.iter().map(|tileid| {
+							format!(r#"{{ "tileid": {tileid}, "duration": {ANIMATION_FRAME_DURATION} }}"#)
+						}))
+						.join(", "),
+					)
+					.unwrap();
+				}
+				writeln!(to, " }}{}", if i == tilesetTiles.len() - 1 { "" } else { "," }).unwrap();
+			}
+			writeln!(to, " ]").unwrap();
+			writeln!(to, "}}").unwrap();
+		}
+	}
+
+	pub struct TMXError(String);
+	impl fmt::Display for TMXError {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			write!(f, "tmx: {}", self.0)
+		}
+	}
+	impl fmt::Debug for TMXError {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			fmt::Display::fmt(self, f)
+		}
+	}
+	impl error::Error for TMXError {}
+	macro_rules! tmxError {
+		($($arg: tt)*) => {
+			TMXError(format!($($arg)*))
+		};
+	}
+
+	const GID_FLIP_FLAGS_MASK: u32 = 0b1111 << 28;
+	const DEFAULT_DRAWING_PRIORITY: u32 = 1;
+
+	enum TilesetGIDs {
+		// a tileset written by writeTMXTo, as its GIDS property says, possibly lacking the <tile> elements of
+		//   tiles painted afterwards
+		Arithmetic,
+		ByTileID(HashMap<u32, [u32; 3]>),
+	}
+
+	impl RootStruct {
+		/// `readTileset` is handed the `source` attribute of every external tileset; tiles of those (and of
+		/// any inline tileset other than the one writeTMXTo emits) need orientation/mainIndex/subIndex properties.
+		pub fn fromTMX(tmx: &str, readTileset: impl Fn(&str) -> io::Result<String>) -> Result<Self, TMXError> {
+			let document = Document::parse(tmx).map_err(|err| tmxError!("{err}"))?;
+			let map = document.root_element();
+			if map.attribute("infinite") == Some("1") {
+				return Err(tmxError!("infinite maps aren't supported"));
+			}
+			let [width, height]: [usize; 2] = [parseAttribute(map, "width")?, parseAttribute(map, "height")?];
+			let mapProperties = properties(map);
+			let version =
+				parseProperty(&mapProperties, "version")?.ok_or_else(|| tmxError!("no version property"))?;
+			let [actIndex, tagType] = [
+				parseProperty(&mapProperties, "actIndex")?.unwrap_or(0),
+				parseProperty(&mapProperties, "tagType")?.unwrap_or(0),
+			];
+			let files = match mapProperties.get("files") {
+				None | Some(&"") => Vec::new(),
+				Some(files) => Vec::from_iter(files.lines().map(str::to_owned)),
+			};
+			let unknown = match mapProperties.get("unknown") {
+				Some(unknown) => Some(
+					<[u8; 8]>::try_from(parseCSV::<u8>(unknown)?)
+						.map_err(|unknown| tmxError!("unknown == {unknown:?} isn't 8 bytes long"))?,
+				),
+				None if matches!(version, 9..=13) => Some([0; 8]),
+				None => None,
+			};
+
+			let mut tilesets = Vec::<(u32, TilesetGIDs)>::new();
+			for tileset in map.children().filter(|node| node.has_tag_name("tileset")) {
+				tilesets.push((
+					parseAttribute(tileset, "firstgid")?,
+					match tileset.attribute("source") {
+						None => tilesetGIDs(tileset)?,
+						Some(source) => {
+							let tsx = readTileset(source).map_err(|err| tmxError!("{source:?}: {err}"))?;
+							tilesetGIDs(
+								Document::parse(&tsx).map_err(|err| tmxError!("{source:?}: {err}"))?.root_element(),
+							)?
+						}
+					},
+				));
+			}
+			tilesets.sort_by_key(|&(firstgid, _)| firstgid);
+			let resolveGID = |gid: u32| -> Result<[u32; 3], TMXError> {
+				let gid = gid & !GID_FLIP_FLAGS_MASK;
+				let (firstgid, tilesetGIDs) = tilesets
+					.iter()
+					.rev()
+					.find(|&&(firstgid, _)| firstgid <= gid)
+					.ok_or_else(|| tmxError!("gid {gid} belongs to no tileset"))?;
+				match tilesetGIDs {
+					TilesetGIDs::Arithmetic => Ok(gidComponents(gid - firstgid + FIRSTGID)),
+					TilesetGIDs::ByTileID(components) => components
+						.get(&(gid - firstgid))
+						.copied()
+						.ok_or_else(|| tmxError!("gid {gid} has no orientation/mainIndex/subIndex properties")),
+				}
+			};
+
+			let (mut walls, mut floors, mut shadow, mut tag) =
+				(HashMap::<usize, [Box<[u32]>; 2]>::new(), HashMap::new(), None, None);
+			for layer in map.descendants().filter(|node| node.has_tag_name("layer")) {
+				let name = layer.attribute("name").unwrap_or_default();
+				let layerProperties = properties(layer);
+				let bits = |name| -> Result<Vec<u32>, TMXError> {
+					Ok(match layerProperties.get(name) {
+						None => vec![0; width * height],
+						Some(bits) => parseCSV(bits)?,
+					})
+				};
+				let (gids, cellBits, orientationBits, drawingPriority) = (
+					layerGIDs(layer, width * height)?,
+					bits(CELL_BITS)?,
+					bits(ORIENTATION_BITS)?,
+					parseProperty(&layerProperties, DRAWING_PRIORITY)?.unwrap_or(DEFAULT_DRAWING_PRIORITY),
+				);
+				if [cellBits.len(), orientationBits.len()] != [gids.len(); 2] {
+					return Err(tmxError!("{name}: {CELL_BITS} or {ORIENTATION_BITS} isn't {width}x{height}"));
+				}
+				if name == TAG_LAYER_NAME {
+					tag = Some(cellBits.into_boxed_slice());
+					continue;
+				}
+				let (mut cells, mut orientations) =
+					(Vec::with_capacity(gids.len()), Vec::with_capacity(gids.len()));
+				for ((&gid, &cellBits), &orientationBits) in
+					iter::zip(iter::zip(&gids, &cellBits), &orientationBits)
+				{
+					let (cell, orientation) = if gid & !GID_FLIP_FLAGS_MASK == 0 {
+						if cellBits & LAYER_DRAWING_PRIORITY_MASK != 0 {
+							(0, 0) // erased in Tiled
+						} else {
+							(cellBits, orientationBits)
+						}
+					} else {
+						let [orientation, mainIndex, subIndex] = resolveGID(gid)?;
+						let cell = cellBits & !(MAIN_INDEX_MASK | SUB_INDEX_MASK)
+							| mainIndex << MAIN_INDEX_OFFSET
+							| subIndex << SUB_INDEX_OFFSET;
+						(
+							if cell & LAYER_DRAWING_PRIORITY_MASK == 0 { cell | drawingPriority } else { cell },
+							orientationBits & !ORIENTATION_MASK | orientation,
+						)
+					};
+					cells.push(cell);
+					orientations.push(orientation);
+				}
+				let (cells, orientations) = (cells.into_boxed_slice(), orientations.into_boxed_slice());
+				if name == SHADOW_LAYER_NAME {
+					shadow = Some(cells);
+				} else if let Some(i) = layerNumber(name, "floor") {
+					floors.insert(i, cells);
+				} else if let Some(i) = layerNumber(name, "wall") {
+					walls.insert(i, [cells, orientations]);
+				} else {
+					return Err(tmxError!(
+						"{name:?} is none of wallN, floorN, {SHADOW_LAYER_NAME} and {TAG_LAYER_NAME}"
+					));
+				}
+			}
+			let [numWallLayers, numFloors] =
+				[walls.keys().max(), floors.keys().max()].map(|max| max.map_or(0, |&max| max + 1));
+			let emptyLayer = || vec![0; width * height].into_boxed_slice();
+			let mut layers = Vec::new();
+			for i in 0..numWallLayers {
+				layers.extend(walls.remove(&i).unwrap_or_else(|| [emptyLayer(), emptyLayer()]));
+			}
+			for i in 0..numFloors {
+				layers.push(floors.remove(&i).unwrap_or_else(emptyLayer));
+			}
+			layers.push(shadow.unwrap_or_else(emptyLayer));
+			if existsTagLayer(tagType) {
+				layers.push(tag.unwrap_or_else(emptyLayer));
+			}
+
+			let (mut objects, mut groups, mut paths) = (Vec::new(), Vec::new(), Vec::new());
+			for objectGroup in map.descendants().filter(|node| node.has_tag_name("objectgroup")) {
+				let name = objectGroup.attribute("name").unwrap_or_default();
+				for object in objectGroup.children().filter(|node| node.has_tag_name("object")) {
+					let objectProperties = properties(object);
+					let intProperty =
+						|name, default| Ok::<_, TMXError>(parseProperty(&objectProperties, name)?.unwrap_or(default));
+					let coord = |name, unit| -> Result<i32, TMXError> {
+						Ok(match object.attribute(name) {
+							None => 0,
+							Some(_) => (parseAttribute::<f64>(object, name)? / unit as f64).round() as _,
+						})
+					};
+					let [x, y] = [coord("x", SUBTILE_UNIT)?, coord("y", SUBTILE_UNIT)?];
+					match name {
+						OBJECTS_LAYER_NAME => objects.push(Object {
+							r#type: intProperty("type", 0)?,
+							id: intProperty("id", 0)?,
+							x,
+							y,
+							flags: intProperty("flags", 0)?,
+						}),
+						GROUPS_LAYER_NAME => groups.push(Group {
+							x: coord("x", TILE_UNIT)?,
+							y: coord("y", TILE_UNIT)?,
+							width: coord("width", TILE_UNIT)?,
+							height: coord("height", TILE_UNIT)?,
+							unknown: intProperty("unknown", 0)?,
+						}),
+						PATHS_LAYER_NAME => {
+							let actions = match objectProperties.get("actions") {
+								None => Vec::new(),
+								Some(actions) => parseCSV(actions)?,
+							};
+							let mut nodes = Vec::new();
+							if let Some(polyline) = object.children().find(|node| node.has_tag_name("polyline")) {
+								for (i, point) in
+									polyline.attribute("points").unwrap_or_default().split_whitespace().enumerate()
+								{
+									let [Δx, Δy] = match parseCSV::<f64>(point)?[..] {
+										[Δx, Δy] => [Δx, Δy].map(|Δ| (Δ / SUBTILE_UNIT as f64).round() as i32),
+										_ => return Err(tmxError!("{point:?} isn't a polyline point")),
+									};
+									nodes
+										.push(Node { x: x + Δx, y: y + Δy, action: actions.get(i).copied().unwrap_or(1) });
+								}
+							}
+							paths.push(Path { x, y, nodes });
+						}
+						_ => {
+							return Err(tmxError!(
+								"{name:?} is none of {OBJECTS_LAYER_NAME}, {GROUPS_LAYER_NAME} and {PATHS_LAYER_NAME}"
+							))
+						}
+					}
+				}
+			}
+
+			fn optionVec<T>(vec: Vec<T>) -> Option<Vec<T>> {
+				(!vec.is_empty()).then_some(vec)
+			}
+
+			Ok(Self {
+				version,
+				xMax: width as i32 - 1,
+				yMax: height as i32 - 1,
+				actIndex,
+				tagType,
+				files,
+				unknown,
+				numWallLayers: numWallLayers as _,
+				numFloors: numFloors as _,
+				layers,
+				objects: optionVec(objects),
+				groups: optionVec(groups),
+				paths: optionVec(paths),
+			})
+		}
+	}
+
+	fn tilesetGIDs(tileset: XMLNode<'_, '_>) -> Result<TilesetGIDs, TMXError> {
+		let mut components = HashMap::new();
+		for tile in tileset.children().filter(|node| node.has_tag_name("tile")) {
+			let tileProperties = properties(tile);
+			if let [Some(orientation), Some(mainIndex), Some(subIndex)] = [
+				parseProperty(&tileProperties, "orientation")?,
+				parseProperty(&tileProperties, "mainIndex")?,
+				parseProperty(&tileProperties, "subIndex")?,
+			] {
+				components.insert(parseAttribute(tile, "id")?, [orientation, mainIndex, subIndex]);
+			}
+		}
+		Ok(if properties(tileset).get(GIDS) == Some(&ARITHMETIC_GIDS) {
+			TilesetGIDs::Arithmetic
+		} else {
+			TilesetGIDs::ByTileID(components)
+		})
+	}
+
+	fn layerGIDs(layer: XMLNode<'_, '_>, len: usize) -> Result<Vec<u32>, TMXError> {
+		let name = layer.attribute("name").unwrap_or_default();
+		let data = layer
+			.children()
+			.find(|node| node.has_tag_name("data"))
+			.ok_or_else(|| tmxError!("{name}: no <data>"))?;
+		if data.children().any(|node| node.has_tag_name("chunk")) {
+			return Err(tmxError!("{name}: chunked layer data isn't supported"));
+		}
+		let text = data.text().unwrap_or_default();
+		let gids = match data.attribute("encoding") {
+			None => {
+				let mut gids = Vec::with_capacity(len);
+				for tile in data.children().filter(|node| node.has_tag_name("tile")) {
+					gids.push(match tile.attribute("gid") {
+						None => 0,
+						Some(_) => parseAttribute(tile, "gid")?,
+					});
+				}
+				gids
+			}
+			Some("csv") => parseCSV(text)?,
+			Some("base64") => {
+				let bytes = BASE64
+					.decode(Vec::from_iter(text.bytes().filter(|byte| !byte.is_ascii_whitespace())))
+					.map_err(|err| tmxError!("{name}: {err}"))?;
+				let bytes = match data.attribute("compression") {
+					None => bytes,
+					Some(compression) => {
+						let (mut decompressed, reader): (_, &mut dyn Read) = (
+							Vec::with_capacity(len * 4),
+							match compression {
+								"zlib" => &mut ZlibDecoder::new(&bytes[..]),
+								"gzip" => &mut GzDecoder::new(&bytes[..]),
+								_ => return Err(tmxError!("{name}: {compression} compression isn't supported")),
+							},
+						);
+						reader.read_to_end(&mut decompressed).map_err(|err| tmxError!("{name}: {err}"))?;
+						decompressed
+					}
+				};
+				Vec::from_iter(bytes.chunks_exact(4).map(|gid| u32::from_le_bytes(gid.try_into().unwrap())))
+			}
+			Some(encoding) => return Err(tmxError!("{name}: {encoding} encoding isn't supported")),
+		};
+		if gids.len() != len {
+			return Err(tmxError!("{name}: {} gids instead of {len}", gids.len()));
+		}
+		Ok(gids)
+	}
+
+	fn layerNumber(name: &str, prefix: &str) -> Option<usize> {
+		name.strip_prefix(prefix)?.parse::<usize>().ok()?.checked_sub(1)
+	}
+
+	fn properties<'a>(node: XMLNode<'a, '_>) -> HashMap<&'a str, &'a str> {
+		let mut properties = HashMap::new();
+		if let Some(node) = node.children().find(|node| node.has_tag_name("properties")) {
+			for property in node.children().filter(|node| node.has_tag_name("property")) {
+				if let Some(name) = property.attribute("name") {
+					properties.insert(name, property.attribute("value").or(property.text()).unwrap_or_default());
+				}
+			}
+		}
+		properties
+	}
+
+	fn parseProperty<T: FromStr>(properties: &HashMap<&str, &str>, name: &str) -> Result<Option<T>, TMXError>
+	where
+		T::Err: fmt::Display,
+	{
+		properties
+			.get(name)
+			.map(|value| value.trim().parse().map_err(|err| tmxError!("{name} == {value:?}: {err}")))
+			.transpose()
+	}
+
+	fn parseAttribute<T: FromStr>(node: XMLNode<'_, '_>, name: &str) -> Result<T, TMXError>
+	where
+		T::Err: fmt::Display,
+	{
+		let value = node
+			.attribute(name)
+			.ok_or_else(|| tmxError!("<{}> has no {name} attribute", node.tag_name().name()))?;
+		value.trim().parse().map_err(|err| tmxError!("{name}=\"{value}\": {err}"))
+	}
+
+	fn parseCSV<T: FromStr>(csv: &str) -> Result<Vec<T>, TMXError>
+	where
+		T::Err: fmt::Display,
+	{
+		let mut values = Vec::new();
+		for value in csv.split(',').map(str::trim).filter(|value| !value.is_empty()) {
+			values.push(value.parse().map_err(|err| tmxError!("{value:?}: {err}"))?);
+		}
+		Ok(values)
+	}
+
+	fn csv<T: ToString>(values: impl Iterator<Item = T>) -> String {
+		Vec::from_iter(values.map(|value| value.to_string())).join(",")
+	}
+
+	pub(crate) fn jsonString(string: &str) -> String {
+		let mut escaped = String::from('"');
+		for char in string.chars() {
+			match char {
+				'"' => escaped += "\\\"",
+				'\\' => escaped += "\\\\",
+				'\n' => escaped += "\\n",
+				char if char < ' ' => escaped += &format!("\\u{:04x}", char as u32),
+				_ => escaped.push(char),
+			}
+		}
+		escaped + "\""
+	}
+
+	pub(crate) fn escape(string: &str) -> String {
+		let mut escaped = String::with_capacity(string.len());
+		for char in string.chars() {
+			match char {
+				'&' => escaped += "&amp;",
+				'<' => escaped += "&lt;",
+				'>' => escaped += "&gt;",
+				'"' => escaped += "&quot;",
+				'\'' => escaped += "&apos;",
+				_ => escaped.push(char),
+			}
+		}
+		escaped
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use {
+			super::*,
+			crate::ds1::{HIDDEN_MASK, ONE_SHADOW_LAYER, UNKNOWN_BITS_MASK},
+			flate2::{write::ZlibEncoder, Compression},
+		};
+
+		// two walls and two floors over 4x3 cells with a tag layer, whose cells are empty, drawn, drawn hidden,
+		//   hidden but not drawn, or drawn with unknown bits, and whose orientations have bits of their own
+		fn sample() -> RootStruct {
+			let cell = |layer: usize, j: usize| {
+				let n = (layer * 31 + j * 17) as u32;
+				let [main, sub] = [
+					(n % (MAIN_INDEX_MAX + 1)) << MAIN_INDEX_OFFSET,
+					(n * 7 % (SUB_INDEX_MAX + 1)) << SUB_INDEX_OFFSET,
+				];
+				match j % 5 {
+					0 => 0,
+					1 => (n % 3 + 1) | main | sub,
+					2 => (n % 3 + 1) | main | sub | HIDDEN_MASK,
+					3 => main | HIDDEN_MASK,
+					_ => 2 | main | sub | ((n << 16) & UNKNOWN_BITS_MASK),
+				}
+			};
+			let [numWallLayers, numFloors, len] = [2, 2, 4 * 3];
+			let mut layers = Vec::new();
+			for i in 0..numWallLayers {
+				layers.push(Vec::from_iter((0..len).map(|j| cell(2 * i, j))).into_boxed_slice());
+				layers.push(
+					Vec::from_iter((0..len).map(|j| (j % 20) as u32 | if j % 3 == 0 { 0x100 } else { 0 }))
+						.into_boxed_slice(),
+				);
+			}
+			for i in 2 * numWallLayers..2 * numWallLayers + numFloors + ONE_SHADOW_LAYER {
+				layers.push(Vec::from_iter((0..len).map(|j| cell(i, j))).into_boxed_slice());
+			}
+			layers
+				.push(Vec::from_iter((0..len as u32).map(|j| j.wrapping_mul(2_654_435_761))).into_boxed_slice());
+			RootStruct {
+				version: 18,
+				xMax: 3,
+				yMax: 2,
+				actIndex: 1,
+				tagType: 2,
+				files: vec!["a.dt1".to_owned(), "b & <c>.dt1".to_owned()],
+				unknown: None,
+				numWallLayers: numWallLayers as _,
+				numFloors: numFloors as _,
+				layers,
+				objects: Some(vec![
+					Object { r#type: 1, id: 5, x: 3, y: 7, flags: 0 },
+					Object { r#type: 2, id: 9, x: 0, y: 14, flags: -1 },
+				]),
+				groups: Some(vec![Group { x: 1, y: 0, width: 2, height: 3, unknown: 4 }]),
+				paths: Some(vec![
+					Path { x: 3, y: 7, nodes: vec![Node { x: 4, y: 7, action: 1 }, Node { x: 4, y: 9, action: 2 }] },
+					Path { x: 0, y: 14, nodes: Vec::new() },
+				]),
+			}
+		}
+
+		// every layer's CSV data as base64, zlib-compressed or not
+		fn reencoded(tmx: &str, compression: Option<&str>) -> String {
+			let [start, end] = [r#"<data encoding="csv">"#, "</data>"];
+			let (mut reencoded, mut rest) = (String::new(), tmx);
+			while let Some((before, after)) = rest.split_once(start) {
+				let (csv, after) = after.split_once(end).unwrap();
+				let mut bytes =
+					Vec::from_iter(parseCSV::<u32>(csv).unwrap().iter().flat_map(|gid| gid.to_le_bytes()));
+				if compression.is_some() {
+					let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+					encoder.write_all(&bytes).unwrap();
+					bytes = encoder.finish().unwrap();
+				}
+				reencoded += before;
+				reencoded += r#"<data encoding="base64""#;
+				if let Some(compression) = compression {
+					reencoded += &[r#" compression=""#, compression, "\""].concat();
+				}
+				reencoded += ">";
+				reencoded += &BASE64.encode(bytes);
+				reencoded += end;
+				rest = after;
+			}
+			reencoded + rest
+		}
+
+		#[test]
+		fn ds1SurvivesTMXInEveryEncoding() {
+			let ds1 = sample();
+			let expected = &mut Vec::new();
+			ds1.writeTo(expected).unwrap();
+			let tmx = &mut Vec::new();
+			ds1.writeTMXTo(tmx);
+			let tmx = &String::from_utf8(tmx.clone()).unwrap();
+			for name in [CELL_BITS, ORIENTATION_BITS, DRAWING_PRIORITY, TAG_LAYER_NAME] {
+				assert!(tmx.contains(&format!(r#"name="{name}""#)), "{name}");
+			}
+			for (encoding, tmx) in
+				[("csv", tmx.clone()), ("base64", reencoded(tmx, None)), ("zlib", reencoded(tmx, Some("zlib")))]
+			{
+				let imported =
+					RootStruct::fromTMX(&tmx, |source| panic!("{source}: no external tilesets")).unwrap();
+				let actual = &mut Vec::new();
+				imported.writeTo(actual).unwrap();
+				assert!(actual == expected, "{encoding}");
+			}
+		}
+	}
+}
+
+pub mod mpq {
+	use {
+		bzip2::read::BzDecoder,
+		core::{fmt, mem::size_of},
+		flate2::read::ZlibDecoder,
+		std::{error, io::Read},
+	};
+
+	pub struct MPQError(String);
+	impl fmt::Display for MPQError {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			write!(f, "mpq: {}", self.0)
+		}
+	}
+	impl fmt::Debug for MPQError {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			fmt::Display::fmt(self, f)
+		}
+	}
+	impl error::Error for MPQError {}
+	macro_rules! mpqError {
+		($($arg: tt)*) => {
+			MPQError(format!($($arg)*))
+		};
+	}
+
+	const MAGIC: &[u8; 4] = b"MPQ\x1A";
+	const HEADER_ALIGNMENT: usize = 512;
+	const HASH_ENTRY_SIZE: usize = 4 * size_of::<u32>();
+	const BLOCK_ENTRY_SIZE: usize = 4 * size_of::<u32>();
+
+	const HASH_TABLE_OFFSET: usize = 0;
+	const HASH_NAME_A: usize = 1;
+	const HASH_NAME_B: usize = 2;
+	const HASH_FILE_KEY: usize = 3;
+	const HASH_KEY2_MIX: usize = 4;
+
+	const BLOCK_INDEX_EMPTY: u32 = 0xFFFF_FFFF;
+	const BLOCK_INDEX_DELETED: u32 = 0xFFFF_FFFE;
+
+	const FILE_IMPLODE: u32 = 0x0000_0100;
+	const FILE_COMPRESS: u32 = 0x0000_0200;
+	const FILE_ENCRYPTED: u32 = 0x0001_0000;
+	const FILE_FIX_KEY: u32 = 0x0002_0000;
+	const FILE_SINGLE_UNIT: u32 = 0x0100_0000;
+	const FILE_EXISTS: u32 = 0x8000_0000;
+
+	const COMPRESSION_HUFFMAN: u8 = 0x01;
+	const COMPRESSION_ZLIB: u8 = 0x02;
+	const COMPRESSION_PKWARE: u8 = 0x08;
+	const COMPRESSION_BZIP2: u8 = 0x10;
+	const COMPRESSION_ADPCM_MONO: u8 = 0x40;
+	const COMPRESSION_ADPCM_STEREO: u8 = 0x80;
+
+	pub const LISTFILE: &str = "(listfile)";
+
+	const CRYPT_TABLE: [u32; 0x500] = {
+		let (mut cryptTable, mut seed, mut i) = ([0; 0x500], 0x0010_0001_u32, 0);
+		while i < 0x100 {
+			let mut j = i;
+			while j < cryptTable.len() {
+				seed = (seed * 125 + 3) % 0x2A_AAAB;
+				let high = (seed & 0xFFFF) << 16;
+				seed = (seed * 125 + 3) % 0x2A_AAAB;
+				cryptTable[j] = high | seed & 0xFFFF;
+				j += 0x100;
+			}
+			i += 1;
+		}
+		cryptTable
+	};
+
+	/// Archive paths are case-insensitive and backslash-separated; forward slashes are accepted too.
+	pub fn hashString(path: &str, hashType: usize) -> u32 {
+		let [mut seed1, mut seed2] = [0x7FED_7FED_u32, 0xEEEE_EEEE_u32];
+		for byte in path.bytes() {
+			let byte = match byte {
+				b'/' => b'\\',
+				_ => byte.to_ascii_uppercase(),
+			} as u32;
+			seed1 = CRYPT_TABLE[hashType * 0x100 + byte as usize] ^ seed1.wrapping_add(seed2);
+			seed2 = byte.wrapping_add(seed1).wrapping_add(seed2).wrapping_add(seed2 << 5).wrapping_add(3);
+		}
+		seed1
+	}
+
+	fn decrypt(data: &mut [u8], mut key: u32) {
+		let mut seed = 0xEEEE_EEEE_u32;
+		for chunk in data.chunks_exact_mut(size_of::<u32>()) {
+			seed = seed.wrapping_add(CRYPT_TABLE[HASH_KEY2_MIX * 0x100 + (key & 0xFF) as usize]);
+			let value = u32::from_le_bytes(chunk.try_into().unwrap()) ^ key.wrapping_add(seed);
+			key = (!key << 21).wrapping_add(0x1111_1111) | key >> 11;
+			seed = value.wrapping_add(seed).wrapping_add(seed << 5).wrapping_add(3);
+			chunk.copy_from_slice(&value.to_le_bytes());
+		}
+	}
+
+	struct HashEntry {
+		nameA: u32,
+		nameB: u32,
+		blockIndex: u32,
+	}
+
+	struct BlockEntry {
+		filePosition: u32,
+		compressedSize: u32,
+		fileSize: u32,
+		flags: u32,
+	}
+
+	pub struct Archive {
+		mpq: Box<[u8]>,
+		headerOffset: usize,
+		sectorSize: usize,
+		hashTable: Box<[HashEntry]>,
+		blockTable: Box<[BlockEntry]>,
+	}
+
+	impl Archive {
+		pub fn new(mpq: Box<[u8]>) -> Result<Self, MPQError> {
+			let headerOffset = (0..mpq.len())
+				.step_by(HEADER_ALIGNMENT)
+				.find(|&offset| mpq[offset..].starts_with(MAGIC))
+				.ok_or_else(|| mpqError!("no {MAGIC:?} header"))?;
+			let u32At = |offset: usize| -> Result<u32, MPQError> {
+				let offset = headerOffset + offset;
+				Ok(u32::from_le_bytes(
+					mpq
+						.get(offset..offset + size_of::<u32>())
+						.ok_or_else(|| mpqError!("truncated at {offset:#x}"))?
+						.try_into()
+						.unwrap(),
+				))
+			};
+			let sectorSize = 512 << (u32At(12)? >> 16);
+			let [hashTableOffset, blockTableOffset, hashTableLen, blockTableLen] =
+				[u32At(16)?, u32At(20)?, u32At(24)?, u32At(28)?].map(|value| value as usize);
+			if !hashTableLen.is_power_of_two() {
+				return Err(mpqError!("hash table length {hashTableLen} isn't a power of two"));
+			}
+			let table = |offset: usize, len: usize, entrySize: usize, key: &str| -> Result<Vec<u8>, MPQError> {
+				let offset = headerOffset + offset;
+				let mut table = mpq
+					.get(offset..offset + len * entrySize)
+					.ok_or_else(|| mpqError!("{key} at {offset:#x} is truncated"))?
+					.to_vec();
+				decrypt(&mut table, hashString(key, HASH_FILE_KEY));
+				Ok(table)
+			};
+			let words = |table: Vec<u8>| -> Vec<u32> {
+				Vec::from_iter(
+					table.chunks_exact(size_of::<u32>()).map(|word| u32::from_le_bytes(word.try_into().unwrap())),
+				)
+			};
+			let hashTable = Vec::from_iter(
+				words(table(hashTableOffset, hashTableLen, HASH_ENTRY_SIZE, "(hash table)")?)
+					.chunks_exact(HASH_ENTRY_SIZE / size_of::<u32>())
+					.map(|entry| HashEntry { nameA: entry[0], nameB: entry[1], blockIndex: entry[3] }),
+			);
+			let blockTable = Vec::from_iter(
+				words(table(blockTableOffset, blockTableLen, BLOCK_ENTRY_SIZE, "(block table)")?)
+					.chunks_exact(BLOCK_ENTRY_SIZE / size_of::<u32>())
+					.map(|entry| BlockEntry {
+						filePosition: entry[0],
+						compressedSize: entry[1],
+						fileSize: entry[2],
+						flags: entry[3],
+					}),
+			);
+			Ok(Self {
+				mpq,
+				headerOffset,
+				sectorSize,
+				hashTable: hashTable.into_boxed_slice(),
+				blockTable: blockTable.into_boxed_slice(),
+			})
+		}
+
+		fn blockEntry(&self, path: &str) -> Option<&BlockEntry> {
+			let (mask, [nameA, nameB]) =
+				(self.hashTable.len() - 1, [HASH_NAME_A, HASH_NAME_B].map(|hashType| hashString(path, hashType)));
+			let mut i = hashString(path, HASH_TABLE_OFFSET) as usize & mask;
+			for _ in 0..self.hashTable.len() {
+				let entry = &self.hashTable[i];
+				match entry.blockIndex {
+					BLOCK_INDEX_EMPTY => break,
+					BLOCK_INDEX_DELETED => {}
+					blockIndex if [entry.nameA, entry.nameB] == [nameA, nameB] => {
+						return self.blockTable.get(blockIndex as usize);
+					}
+					_ => {}
+				}
+				i = (i + 1) & mask;
+			}
+			None
+		}
+
+		pub fn contains(&self, path: &str) -> bool {
+			self.blockEntry(path).map_or(false, |block| block.flags & FILE_EXISTS != 0)
+		}
+
+		pub fn read(&self, path: &str) -> Result<Vec<u8>, MPQError> {
+			let &BlockEntry { filePosition, compressedSize, fileSize, flags } = self
+				.blockEntry(path)
+				.filter(|block| block.flags & FILE_EXISTS != 0)
+				.ok_or_else(|| mpqError!("{path:?}: no such file"))?;
+			let [filePosition, compressedSize, fileSize] =
+				[filePosition, compressedSize, fileSize].map(|value| value as usize);
+			let start = self.headerOffset + filePosition;
+			let raw = self
+				.mpq
+				.get(start..start + compressedSize)
+				.ok_or_else(|| mpqError!("{path:?} at {start:#x} is truncated"))?;
+			let key = if flags & FILE_ENCRYPTED == 0 {
+				0
+			} else {
+				let key = hashString(path.rsplit(['\\', '/']).next().unwrap(), HASH_FILE_KEY);
+				if flags & FILE_FIX_KEY == 0 {
+					key
+				} else {
+					key.wrapping_add(filePosition as _) ^ fileSize as u32
+				}
+			};
+			let decompress = |sector: &[u8], expectedLen: usize| -> Result<Vec<u8>, MPQError> {
+				let decompressed =
+					if flags & FILE_IMPLODE != 0 { explode(sector) } else { decompressMulti(sector) }
+						.map_err(|err| mpqError!("{path:?}: {err}"))?;
+				if decompressed.len() != expectedLen {
+					return Err(mpqError!(
+						"{path:?}: {} bytes decompressed instead of {expectedLen}",
+						decompressed.len()
+					));
+				}
+				Ok(decompressed)
+			};
+			let isCompressed = flags & (FILE_IMPLODE | FILE_COMPRESS) != 0;
+
+			if flags & FILE_SINGLE_UNIT != 0 {
+				let mut data = raw.to_vec();
+				if flags & FILE_ENCRYPTED != 0 {
+					decrypt(&mut data, key);
+				}
+				return if isCompressed && compressedSize < fileSize {
+					decompress(&data, fileSize)
+				} else {
+					Ok(data)
+				};
+			}
+
+			let numSectors = (fileSize + self.sectorSize - 1) / self.sectorSize;
+			let sectorOffsets = if isCompressed {
+				let mut offsets = raw
+					.get(..(numSectors + 1) * size_of::<u32>())
+					.ok_or_else(|| mpqError!("{path:?}: truncated sector offset table"))?
+					.to_vec();
+				if flags & FILE_ENCRYPTED != 0 {
+					decrypt(&mut offsets, key.wrapping_sub(1));
+				}
+				Vec::from_iter(
+					offsets
+						.chunks_exact(size_of::<u32>())
+						.map(|offset| u32::from_le_bytes(offset.try_into().unwrap()) as usize),
+				)
+			} else {
+				Vec::from_iter((0..=numSectors).map(|i| (i * self.sectorSize).min(fileSize)))
+			};
+			let mut data = Vec::with_capacity(fileSize);
+			for i in 0..numSectors {
+				let mut sector = raw
+					.get(sectorOffsets[i]..sectorOffsets[i + 1])
+					.ok_or_else(|| mpqError!("{path:?}: sector #{i} is out of bounds"))?
+					.to_vec();
+				if flags & FILE_ENCRYPTED != 0 {
+					decrypt(&mut sector, key.wrapping_add(i as _));
+				}
+				let expectedLen = self.sectorSize.min(fileSize - i * self.sectorSize);
+				if isCompressed && sector.len() < expectedLen {
+					data.extend(decompress(&sector, expectedLen)?);
+				} else {
+					data.extend(sector);
+				}
+			}
+			Ok(data)
+		}
+
+		/// The archive's own `(listfile)`, one path per line (or per `;`).
+		pub fn listfile(&self) -> Result<Vec<String>, MPQError> {
+			Ok(parseListfile(&String::from_utf8_lossy(&self.read(LISTFILE)?)))
+		}
+	}
+
+	pub fn parseListfile(listfile: &str) -> Vec<String> {
+		Vec::from_iter(
+			listfile.split(['\r', '\n', ';']).map(str::trim).filter(|path| !path.is_empty()).map(str::to_owned),
+		)
+	}
+
+	fn decompressMulti(sector: &[u8]) -> Result<Vec<u8>, String> {
+		let (&mask, data) = sector.split_first().ok_or("empty compressed sector")?;
+		let mut data = data.to_vec();
+		for (compression, decompress) in [
+			(
+				COMPRESSION_BZIP2,
+				(|data| readToEnd(BzDecoder::new(data))) as fn(&[u8]) -> Result<Vec<u8>, String>,
+			),
+			(COMPRESSION_PKWARE, explode),
+			(COMPRESSION_ZLIB, |data| readToEnd(ZlibDecoder::new(data))),
+		] {
+			if mask & compression != 0 {
+				data = decompress(&data)?;
+			}
+		}
+		let unsupported = mask & !(COMPRESSION_BZIP2 | COMPRESSION_PKWARE | COMPRESSION_ZLIB);
+		if unsupported != 0 {
+			return Err(format!(
+				"compression mask {mask:#04x} has unsupported bits {unsupported:#04x}{}",
+				if unsupported & (COMPRESSION_HUFFMAN | COMPRESSION_ADPCM_MONO | COMPRESSION_ADPCM_STEREO) != 0 {
+					" (WAVE compression)"
+				} else {
+					""
+				},
+			));
+		}
+		Ok(data)
+	}
+
+	fn readToEnd(mut reader: impl Read) -> Result<Vec<u8>, String> {
+		let mut vec = Vec::new();
+		reader.read_to_end(&mut vec).map_err(|err| err.to_string())?;
+		Ok(vec)
+	}
+
+	/*
+		PKWARE Data Compression Library "explode", after Mark Adler's blast.c (zlib/contrib/blast):
+		a literal is either 8 raw bits or a Huffman code, a match is a Huffman-coded length (519 ends
+		the stream) followed by a Huffman-coded distance whose low 2 (for length 2) or `dict` bits are raw.
+		Huffman codes are stored bit-inverted, and bits are read starting from the least significant one.
+	*/
+	const MAXBITS: usize = 13;
+
+	struct Huffman {
+		count: [u16; MAXBITS + 1],
+		symbol: Vec<u16>,
+	}
+
+	impl Huffman {
+		fn new(compactLengths: &[u8], numSymbols: usize) -> Self {
+			let mut lengths = Vec::with_capacity(numSymbols);
+			for &byte in compactLengths {
+				for _ in 0..=(byte >> 4) {
+					lengths.push((byte & 0b1111) as usize);
+				}
+			}
+			let mut count = [0_u16; MAXBITS + 1];
+			for &length in &lengths {
+				count[length] += 1;
+			}
+			let mut offsets = [0_u16; MAXBITS + 1];
+			for length in 1..MAXBITS {
+				offsets[length + 1] = offsets[length] + count[length];
+			}
+			let mut symbol = vec![0; lengths.len()];
+			for (i, &length) in lengths.iter().enumerate() {
+				if length != 0 {
+					symbol[offsets[length] as usize] = i as _;
+					offsets[length] += 1;
+				}
+			}
+			Self { count, symbol }
+		}
+	}
+
+	struct BitReader<'a> {
+		data: &'a [u8],
+		position: usize,
+		bitBuffer: u32,
+		bitCount: u32,
+	}
+
+	impl BitReader<'_> {
+		fn bits(&mut self, need: u32) -> Result<u32, String> {
+			while self.bitCount < need {
+				let &byte = self.data.get(self.position).ok_or("ran out of input")?;
+				self.bitBuffer |= (byte as u32) << self.bitCount;
+				self.position += 1;
+				self.bitCount += 8;
+			}
+			let value = self.bitBuffer & ((1 << need) - 1);
+			self.bitBuffer >>= need;
+			self.bitCount -= need;
+			Ok(value)
+		}
+
+		fn decode(&mut self, huffman: &Huffman) -> Result<usize, String> {
+			let [mut code, mut first, mut index] = [0_i32; 3];
+			for length in 1..=MAXBITS {
+				code |= (self.bits(1)? ^ 1) as i32;
+				let count = huffman.count[length] as i32;
+				if code - count < first {
+					return Ok(huffman.symbol[(index + (code - first)) as usize] as _);
+				}
+				index += count;
+				first = (first + count) << 1;
+				code <<= 1;
+			}
+			Err("invalid Huffman code".to_owned())
+		}
+	}
+
+	pub fn explode(imploded: &[u8]) -> Result<Vec<u8>, String> {
+		#[rustfmt::skip]
+		const LITERAL_LENGTHS: &[u8] = &[
+			11, 124, 8, 7, 28, 7, 188, 13, 76, 4, 10, 8, 12, 10, 12, 10, 8, 23, 8,
+			9, 7, 6, 7, 8, 7, 6, 55, 8, 23, 24, 12, 11, 7, 9, 11, 12, 6, 7, 22, 5,
+			7, 24, 6, 11, 9, 6, 7, 22, 7, 11, 38, 7, 9, 8, 25, 11, 8, 11, 9, 12,
+			8, 12, 5, 38, 5, 38, 5, 11, 7, 5, 6, 21, 6, 10, 53, 8, 7, 24, 10, 27,
+			44, 253, 253, 253, 252, 252, 252, 13, 12, 45, 12, 45, 12, 61, 12, 45,
+			44, 173,
+		];
+		const LENGTH_LENGTHS: &[u8] = &[2, 35, 36, 53, 38, 23];
+		const DISTANCE_LENGTHS: &[u8] = &[2, 20, 53, 230, 247, 151, 248];
+		const BASE: [u32; 16] = [3, 2, 4, 5, 6, 7, 8, 9, 10, 12, 16, 24, 40, 72, 136, 264];
+		const EXTRA: [u32; 16] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8];
+		const END_OF_STREAM: u32 = 519;
+
+		let (literalCode, lengthCode, distanceCode) = (
+			Huffman::new(LITERAL_LENGTHS, 256),
+			Huffman::new(LENGTH_LENGTHS, 16),
+			Huffman::new(DISTANCE_LENGTHS, 64),
+		);
+		let reader = &mut BitReader { data: imploded, position: 0, bitBuffer: 0, bitCount: 0 };
+		let codedLiterals = match reader.bits(8)? {
+			0 => false,
+			1 => true,
+			other => return Err(format!("literal mode {other} is neither 0 nor 1")),
+		};
+		let dictionaryBits = reader.bits(8)?;
+		if !(4..=6).contains(&dictionaryBits) {
+			return Err(format!("dictionary size {dictionaryBits} isn't within 4..=6"));
+		}
+		let mut exploded = Vec::with_capacity(imploded.len() * 4);
+		loop {
+			if reader.bits(1)? == 1 {
+				let symbol = reader.decode(&lengthCode)?;
+				let length = BASE[symbol] + reader.bits(EXTRA[symbol])?;
+				if length == END_OF_STREAM {
+					break;
+				}
+				let lowBits = if length == 2 { 2 } else { dictionaryBits };
+				let distance = ((reader.decode(&distanceCode)? as u32) << lowBits) + reader.bits(lowBits)? + 1;
+				let start = exploded
+					.len()
+					.checked_sub(distance as usize)
+					.ok_or_else(|| format!("distance {distance} reaches before the output start"))?;
+				for i in start..start + length as usize {
+					exploded.push(exploded[i]);
+				}
+			} else {
+				exploded.push(if codedLiterals {
+					reader.decode(&literalCode)? as u8
+				} else {
+					reader.bits(8)? as u8
+				});
+			}
+		}
+		Ok(exploded)
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		#[test]
+		fn hashesMatchKnownAnswers() {
+			assert_eq!(hashString("(hash table)", HASH_FILE_KEY), 0xC3AF_3770);
+			assert_eq!(hashString("(block table)", HASH_FILE_KEY), 0xEC83_B3A3);
+			assert_eq!(
+				hashString("data/global/tiles/act1/town/floor.dt1", HASH_NAME_A),
+				hashString(r"DATA\GLOBAL\TILES\ACT1\TOWN\FLOOR.DT1", HASH_NAME_A),
+			);
+		}
+
+		#[test]
+		fn explodesBlastsTestVector() {
+			assert_eq!(explode(&[0x00, 0x04, 0x82, 0x24, 0x25, 0x8F, 0x80, 0x7F]).unwrap(), b"AIAIAIAIAIAIA");
+			assert!(explode(&[0x00, 0x04, 0x82, 0x24]).is_err());
+		}
+	}
+}
+
+pub mod dcc {
+	use {
+		super::{Image, MinAssign_MaxAssign_Ext, UsizeExt},
+		core::fmt,
+		std::error,
+	};
+
+	pub struct DCCError(String);
+	impl fmt::Display for DCCError {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			write!(f, "dcc: {}", self.0)
+		}
+	}
+	impl fmt::Debug for DCCError {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			fmt::Display::fmt(self, f)
+		}
+	}
+	impl error::Error for DCCError {}
+	macro_rules! dccError {
+		($($arg: tt)*) => {
+			DCCError(format!($($arg)*))
+		};
+	}
+
+	const SIGNATURE: u8 = 0x74;
+	const EXPECTED_VERSION: u8 = 6;
+	const FILEHEADER_SIZE: usize = 15;
+	const CELL_SIZE: i32 = 4;
+	const COMPRESSION_EQUAL_CELLS: u32 = 0b10;
+	const COMPRESSION_RAW_PIXELS: u32 = 0b01;
+	const BITSTREAM_SIZE_BITS: u32 = 20;
+	// direction headers store field widths as 4-bit indices into this table
+	const FIELD_WIDTHS: [u32; 16] = [0, 1, 2, 4, 6, 8, 10, 12, 14, 16, 20, 24, 26, 28, 30, 32];
+	// wider and taller than any sprite's direction, small enough that its canvas stays under 64 MiB
+	const MAX_DIMENSION: i32 = 1 << 13;
+
+	/// A frame's `image` spans `[xOffset, yOffset - height + 1]..=[xOffset + width - 1, yOffset]` relative to
+	///   the sprite's anchor, the bottom-left corner being at `[xOffset, yOffset]`.
+	pub struct Frame {
+		pub xOffset: i32,
+		pub yOffset: i32,
+		pub image: Image,
+	}
+
+	pub struct Direction {
+		/// `[[left, top], [right, bottom]]`, exclusive of `right` and `bottom`, of all the direction's frames.
+		pub bounds: [[i32; 2]; 2],
+		pub frames: Vec<Frame>,
+	}
+
+	impl Frame {
+		#[inline(always)]
+		pub fn top(&self) -> i32 {
+			self.yOffset - self.image.height as i32 + 1
+		}
+	}
+
+	struct BitStream<'a> {
+		data: &'a [u8],
+		position: usize,
+		end: usize,
+		name: &'static str,
+	}
+
+	impl<'a> BitStream<'a> {
+		// bits are read starting from the least significant one
+		fn bits(&mut self, count: u32) -> Result<u32, DCCError> {
+			if self.position + count as usize > self.end {
+				return Err(dccError!("{} bitstream ended at bit {}", self.name, self.position));
+			}
+			let mut value = 0_u64;
+			for i in 0..count as usize {
+				let position = self.position + i;
+				value |= u64::from(self.data[position / 8] >> (position % 8) & 1) << i;
+			}
+			self.position += count as usize;
+			Ok(value as _)
+		}
+
+		fn signedBits(&mut self, count: u32) -> Result<i32, DCCError> {
+			let value = self.bits(count)?;
+			Ok(match count {
+				0 => 0,
+				_ => ((value << (32 - count)) as i32) >> (32 - count),
+			})
+		}
+
+		// the substreams of a direction are laid out back to back, unaligned
+		fn split(&mut self, sizeInBits: usize, name: &'static str) -> Result<Self, DCCError> {
+			if self.position + sizeInBits > self.end {
+				return Err(dccError!("{name} bitstream of {sizeInBits} bits overruns the direction"));
+			}
+			let substream =
+				Self { data: self.data, position: self.position, end: self.position + sizeInBits, name };
+			self.position += sizeInBits;
+			Ok(substream)
+		}
+	}
+
+	struct FrameHeader {
+		width: i32,
+		height: i32,
+		xOffset: i32,
+		yOffset: i32,
+		numOptionalBytes: u32,
+	}
+
+	struct Cell {
+		x: i32,
+		y: i32,
+		width: i32,
+		height: i32,
+	}
+
+	struct PixelBufferEntry {
+		values: [u8; 4],
+		frame: usize,
+		frameCellIndex: usize,
+	}
+
+	// sizes of the cells covering `length` pixels from `offset` within the direction: the first one ends at the
+	//   next multiple of CELL_SIZE, and a 1-pixel remainder is absorbed by the one before it
+	fn cellSpans(offset: i32, length: i32) -> Vec<i32> {
+		let first = CELL_SIZE - offset % CELL_SIZE;
+		if length - first <= 1 {
+			return vec![length];
+		}
+		let remaining = length - first - 1;
+		let count = 2 + remaining / CELL_SIZE - i32::from(remaining % CELL_SIZE == 0);
+		let mut spans = vec![CELL_SIZE; count as usize];
+		spans[0] = first;
+		spans[count as usize - 1] = length - first - CELL_SIZE * (count - 2);
+		spans
+	}
+
+	pub fn decode(dcc: &[u8]) -> Result<Vec<Direction>, DCCError> {
+		if dcc.len() < FILEHEADER_SIZE {
+			return Err(dccError!("{} bytes is too short for a file header", dcc.len()));
+		}
+		let (signature, version, numDirections) = (dcc[0], dcc[1], dcc[2] as usize);
+		if [signature, version] != [SIGNATURE, EXPECTED_VERSION] {
+			return Err(dccError!(
+				"[signature, version] == {:?} != {:?}",
+				[signature, version],
+				[SIGNATURE, EXPECTED_VERSION],
+			));
+		}
+		let u32At = |offset: usize| -> Result<u32, DCCError> {
+			Ok(u32::from_le_bytes(
+				dcc.get(offset..offset + 4).ok_or_else(|| dccError!("truncated at {offset}"))?.try_into().unwrap(),
+			))
+		};
+		let framesPerDirection = u32At(3)? as usize;
+		let mut directions = Vec::with_capacity(numDirections);
+		for i in 0..numDirections {
+			let directionOffset = u32At(FILEHEADER_SIZE + 4 * i)? as usize;
+			let directionEnd = match i + 1 {
+				next if next < numDirections => u32At(FILEHEADER_SIZE + 4 * next)? as usize,
+				_ => dcc.len(),
+			};
+			if directionOffset > directionEnd || directionEnd > dcc.len() {
+				return Err(dccError!("direction #{i} at {directionOffset}..{directionEnd} is out of bounds"));
+			}
+			directions.push(
+				decodeDirection(
+					&mut BitStream {
+						data: dcc,
+						position: directionOffset * 8,
+						end: directionEnd * 8,
+						name: "direction",
+					},
+					framesPerDirection,
+				)
+				.map_err(|DCCError(err)| dccError!("direction #{i}: {err}"))?,
+			);
+		}
+		Ok(directions)
+	}
+
+	fn decodeDirection(bits: &mut BitStream<'_>, numFrames: usize) -> Result<Direction, DCCError> {
+		let _outSizeCoded = bits.bits(32)?;
+		let compressionFlags = bits.bits(2)?;
+		let mut fieldWidths = [0; 7];
+		for fieldWidth in &mut fieldWidths {
+			*fieldWidth = FIELD_WIDTHS[bits.bits(4)? as usize];
+		}
+		let [variable0Bits, widthBits, heightBits, xOffsetBits, yOffsetBits, optionalBytesBits, codedBytesBits] =
+			fieldWidths;
+		let mut frameHeaders = Vec::with_capacity(numFrames);
+		for i in 0..numFrames {
+			let _variable0 = bits.bits(variable0Bits)?;
+			let frameHeader = FrameHeader {
+				width: bits.bits(widthBits)? as _,
+				height: bits.bits(heightBits)? as _,
+				xOffset: bits.signedBits(xOffsetBits)?,
+				yOffset: bits.signedBits(yOffsetBits)?,
+				numOptionalBytes: bits.bits(optionalBytesBits)?,
+			};
+			let _numCodedBytes = bits.bits(codedBytesBits)?;
+			if bits.bits(1)? == 1 {
+				return Err(dccError!("frame #{i} is bottom-up, which isn't supported"));
+			}
+			let FrameHeader { width, height, .. } = frameHeader;
+			if !(1..=MAX_DIMENSION).contains(&width) || !(1..=MAX_DIMENSION).contains(&height) {
+				return Err(dccError!(
+					"frame #{i} is {width}x{height}, not 1 to {MAX_DIMENSION} pixels either way"
+				));
+			}
+			frameHeaders.push(frameHeader);
+		}
+		let [mut left, mut top, mut right, mut bottom] = [i32::MAX, i32::MAX, i32::MIN, i32::MIN];
+		for (i, frameHeader) in frameHeaders.iter().enumerate() {
+			let &FrameHeader { width, height, xOffset, yOffset, .. } = frameHeader;
+			let [frameTop, frameRight, frameBottom] =
+				[yOffset.checked_sub(height - 1), xOffset.checked_add(width), yOffset.checked_add(1)]
+					.map(|coord| coord.ok_or_else(|| dccError!("frame #{i} at [{xOffset}, {yOffset}] overflows")));
+			left.minAssign(xOffset);
+			top.minAssign(frameTop?);
+			right.maxAssign(frameRight?);
+			bottom.maxAssign(frameBottom?);
+		}
+		if frameHeaders.is_empty() {
+			[left, top, right, bottom] = [0; 4];
+		}
+		let [directionWidth, directionHeight] = match [right.checked_sub(left), bottom.checked_sub(top)] {
+			[Some(width @ 0..=MAX_DIMENSION), Some(height @ 0..=MAX_DIMENSION)] => [width, height],
+			_ => {
+				return Err(dccError!(
+					"the frames' box [[{left}, {top}], [{right}, {bottom}]] is over {MAX_DIMENSION} pixels either way"
+				))
+			}
+		};
+		let numOptionalBytes: u32 = frameHeaders.iter().map(|frameHeader| frameHeader.numOptionalBytes).sum();
+		if numOptionalBytes != 0 {
+			bits.position = bits.position.nextMultipleOf(8) + numOptionalBytes as usize * 8;
+		}
+
+		let equalCellsSize =
+			if compressionFlags & COMPRESSION_EQUAL_CELLS != 0 { bits.bits(BITSTREAM_SIZE_BITS)? } else { 0 };
+		let pixelMaskSize = bits.bits(BITSTREAM_SIZE_BITS)?;
+		let [encodingTypeSize, rawPixelCodesSize] = if compressionFlags & COMPRESSION_RAW_PIXELS != 0 {
+			[bits.bits(BITSTREAM_SIZE_BITS)?, bits.bits(BITSTREAM_SIZE_BITS)?]
+		} else {
+			[0; 2]
+		};
+		let mut pixelValues = Vec::with_capacity(256);
+		for value in 0..=u8::MAX {
+			if bits.bits(1)? == 1 {
+				pixelValues.push(value);
+			}
+		}
+		let equalCells = &mut bits.split(equalCellsSize as _, "equal cells")?;
+		let pixelMask = &mut bits.split(pixelMaskSize as _, "pixel mask")?;
+		let encodingType = &mut bits.split(encodingTypeSize as _, "encoding type")?;
+		let rawPixelCodes = &mut bits.split(rawPixelCodesSize as _, "raw pixel codes")?;
+		let pixelCodesAndDisplacements =
+			&mut bits.split(bits.end - bits.position, "pixel code and displacement")?;
+
+		let [numCellsX, numCellsY] =
+			[directionWidth, directionHeight].map(|length| 1 + (length - 1) / CELL_SIZE);
+		let frameCells = Vec::from_iter(frameHeaders.iter().map(|frameHeader| {
+			let [x0, y0] = [frameHeader.xOffset - left, frameHeader.yOffset - frameHeader.height + 1 - top];
+			let [widths, heights] = [cellSpans(x0, frameHeader.width), cellSpans(y0, frameHeader.height)];
+			let mut cells = Vec::with_capacity(widths.len() * heights.len());
+			let mut y = y0;
+			for &height in &heights {
+				let mut x = x0;
+				for &width in &widths {
+					cells.push(Cell { x, y, width, height });
+					x += width;
+				}
+				y += height;
+			}
+			cells
+		}));
+		let bufferCellIndex = |cell: &Cell| (cell.x / CELL_SIZE + cell.y / CELL_SIZE * numCellsX) as usize;
+
+		// stage 1: the (up to) 4 pixel values of each cell, for the cells which change from the previous frame
+		let mut pixelBuffer = Vec::<PixelBufferEntry>::new();
+		let mut cellBuffer = vec![None::<usize>; (numCellsX * numCellsY) as usize];
+		for (frame, cells) in frameCells.iter().enumerate() {
+			for (frameCellIndex, cell) in cells.iter().enumerate() {
+				let bufferCell = &mut cellBuffer[bufferCellIndex(cell)];
+				let pixelMask = match *bufferCell {
+					None => 0b1111,
+					Some(_) if equalCellsSize != 0 && equalCells.bits(1)? == 1 => continue,
+					Some(_) => pixelMask.bits(4)?,
+				};
+				let numPixels = pixelMask.count_ones() as usize;
+				let isRaw = numPixels != 0 && encodingTypeSize != 0 && encodingType.bits(1)? == 1;
+				let (mut decoded, mut numDecoded, mut lastPixel) = ([0_u32; 4], 0, 0);
+				for _ in 0..numPixels {
+					let pixel = if isRaw {
+						rawPixelCodes.bits(8)?
+					} else {
+						let mut pixel = lastPixel;
+						loop {
+							let displacement = pixelCodesAndDisplacements.bits(4)?;
+							pixel += displacement;
+							if displacement != 0b1111 {
+								break pixel;
+							}
+						}
+					};
+					if pixel == lastPixel {
+						break;
+					}
+					decoded[numDecoded] = pixel;
+					numDecoded += 1;
+					lastPixel = pixel;
+				}
+				let mut values = [0; 4];
+				for (i, value) in values.iter_mut().enumerate() {
+					*value = if pixelMask & 1 << i == 0 {
+						pixelBuffer[bufferCell.unwrap()].values[i]
+					} else if numDecoded != 0 {
+						numDecoded -= 1;
+						decoded[numDecoded] as _
+					} else {
+						0
+					};
+				}
+				*bufferCell = Some(pixelBuffer.len());
+				pixelBuffer.push(PixelBufferEntry { values, frame, frameCellIndex });
+			}
+		}
+		for entry in &mut pixelBuffer {
+			for value in &mut entry.values {
+				*value = *pixelValues.get(*value as usize).ok_or_else(|| {
+					dccError!("pixel code {value} is beyond the {} pixel values", pixelValues.len())
+				})?;
+			}
+		}
+
+		// stage 2: paint every frame's cells on the direction-sized canvas, new cells from the pixel buffer and
+		//   equal ones by copying them from where that buffer cell was last painted
+		let canvas = &mut Image::fromWidthHeight(directionWidth as _, directionHeight as _);
+		let mut lastPainted = vec![None::<[i32; 4]>; cellBuffer.len()];
+		let mut entries = pixelBuffer.iter().peekable();
+		let mut frames = Vec::with_capacity(numFrames);
+		for (frame, (cells, frameHeader)) in frameCells.iter().zip(&frameHeaders).enumerate() {
+			for (frameCellIndex, cell) in cells.iter().enumerate() {
+				let &Cell { x, y, width, height } = cell;
+				let pixelIndex = |x: i32, y: i32| (x + y * directionWidth) as usize;
+				let last = &mut lastPainted[bufferCellIndex(cell)];
+				match entries.next_if(|entry| [entry.frame, entry.frameCellIndex] == [frame, frameCellIndex]) {
+					Some(&PixelBufferEntry { values, .. }) => {
+						let numBits = match values {
+							[first, second, ..] if first == second => 0,
+							[_, second, third, _] if second == third => 1,
+							_ => 2,
+						};
+						for dy in 0..height {
+							for dx in 0..width {
+								canvas.data[pixelIndex(x + dx, y + dy)] =
+									values[pixelCodesAndDisplacements.bits(numBits)? as usize];
+							}
+						}
+					}
+					None => match *last {
+						Some([lastX, lastY, lastWidth, lastHeight]) if [lastWidth, lastHeight] == [width, height] => {
+							for dy in 0..height {
+								for dx in 0..width {
+									canvas.data[pixelIndex(x + dx, y + dy)] =
+										canvas.data[pixelIndex(lastX + dx, lastY + dy)];
+								}
+							}
+						}
+						_ => {
+							for dy in 0..height {
+								for dx in 0..width {
+									canvas.data[pixelIndex(x + dx, y + dy)] = 0;
+								}
+							}
+						}
+					},
+				}
+				*last = Some([x, y, width, height]);
+			}
+			let &FrameHeader { width, height, xOffset, yOffset, .. } = frameHeader;
+			let mut image = Image::fromWidthHeight(width as _, height as _);
+			let [x0, y0] = [xOffset - left, yOffset - height + 1 - top];
+			for dy in 0..height {
+				let start = (x0 + (y0 + dy) * directionWidth) as usize;
+				image.data[(dy * width) as usize..][..width as usize]
+					.copy_from_slice(&canvas.data[start..][..width as usize]);
+			}
+			frames.push(Frame { xOffset, yOffset, image });
+		}
+		Ok(Direction { bounds: [[left, top], [right, bottom]], frames })
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		#[test]
+		fn cellSpansEndOnCellBoundariesAndAbsorbRemainders() {
+			assert_eq!(cellSpans(0, 8), [4, 4]);
+			assert_eq!(cellSpans(0, 9), [4, 5]);
+			assert_eq!(cellSpans(1, 8), [3, 5]);
+			assert_eq!(cellSpans(3, 10), [1, 4, 5]);
+			assert_eq!(cellSpans(6, 3), [3]);
+			assert_eq!(cellSpans(2, 2), [2]);
+			for offset in 0..2 * CELL_SIZE {
+				for length in 1..10 * CELL_SIZE {
+					let spans = cellSpans(offset, length);
+					assert_eq!(spans.iter().sum::<i32>(), length, "{offset}, {length}: {spans:?}");
+					let mut end = offset;
+					for &span in &spans[..spans.len() - 1] {
+						end += span;
+						assert_eq!(end % CELL_SIZE, 0, "{offset}, {length}: {spans:?}");
+					}
+					assert!(
+						spans.iter().all(|&span| (1..=CELL_SIZE + 1).contains(&span)),
+						"{offset}, {length}: {spans:?}"
+					);
+				}
+			}
+		}
+
+		// bits written the way `BitStream` reads them, least significant first
+		#[derive(Default)]
+		struct BitWriter(Vec<bool>);
+		impl BitWriter {
+			fn push(&mut self, value: u32, count: u32) -> &mut Self {
+				self.0.extend((0..count).map(|i| value >> i & 1 == 1));
+				self
+			}
+			fn bytes(&self) -> Vec<u8> {
+				Vec::from_iter(
+					self
+						.0
+						.chunks(8)
+						.map(|byte| byte.iter().enumerate().fold(0, |acc, (i, &bit)| acc | u8::from(bit) << i)),
+				)
+			}
+		}
+
+		const PIXEL_VALUES: [u8; 4] = [0, 10, 20, 30];
+
+		// one direction of one frame, its headers' fields 32 bits wide, with no equal cells or raw pixels
+		fn oneFrameDCC(
+			[width, height]: [u32; 2],
+			[xOffset, yOffset]: [i32; 2],
+			pixelCodes: &BitWriter,
+		) -> Vec<u8> {
+			let bits = &mut BitWriter::default();
+			bits.push(0, 32).push(0, 2);
+			for fieldWidthIndex in [0, 15, 15, 15, 15, 0, 0] {
+				bits.push(fieldWidthIndex, 4);
+			}
+			bits.push(width, 32).push(height, 32).push(xOffset as _, 32).push(yOffset as _, 32).push(0, 1);
+			bits.push(0, BITSTREAM_SIZE_BITS);
+			for value in 0..=u8::MAX {
+				bits.push(u32::from(PIXEL_VALUES.contains(&value)), 1);
+			}
+			bits.0.extend(&pixelCodes.0);
+			let mut dcc = vec![SIGNATURE, EXPECTED_VERSION, 1];
+			dcc.extend(1_u32.to_le_bytes());
+			dcc.resize(FILEHEADER_SIZE, 0);
+			dcc.extend((FILEHEADER_SIZE as u32 + 4).to_le_bytes());
+			dcc.extend(bits.bytes());
+			dcc
+		}
+
+		#[test]
+		fn decodesOneFrameDirection() {
+			// a 9x3 frame is two cells, 4 and 5 pixels wide; the first one's pixel codes are 1 and 3, that is
+			//   values [30, 10, 0, 0] picked with 2 bits, the second one's 2, values [20, 0, 0, 0] picked with 1
+			let [cell0, cell1] = [|dx: u32, dy: u32| (dx + dy) % 4, |dx: u32, dy: u32| dx * dy % 2];
+			let pixelCodes = &mut BitWriter::default();
+			pixelCodes.push(1, 4).push(2, 4).push(0, 4).push(2, 4).push(0, 4);
+			for dy in 0..3 {
+				for dx in 0..4 {
+					pixelCodes.push(cell0(dx, dy), 2);
+				}
+			}
+			for dy in 0..3 {
+				for dx in 0..5 {
+					pixelCodes.push(cell1(dx, dy), 1);
+				}
+			}
+			let directions = decode(&oneFrameDCC([9, 3], [-3, -1], pixelCodes)).unwrap();
+			assert_eq!(directions.len(), 1);
+			let Direction { bounds, frames } = &directions[0];
+			assert_eq!(*bounds, [[-3, -3], [6, 0]]);
+			assert_eq!(frames.len(), 1);
+			let Frame { xOffset, yOffset, image } = &frames[0];
+			assert_eq!([*xOffset, *yOffset, frames[0].top()], [-3, -1, -3]);
+			assert_eq!([image.width, image.height], [9, 3]);
+			for y in 0..3 {
+				for x in 0..9 {
+					let expected =
+						if x < 4 { [30, 10, 0, 0][cell0(x, y) as usize] } else { [20, 0][cell1(x - 4, y) as usize] };
+					assert_eq!(image.data[(x + y * 9) as usize], expected, "[{x}, {y}]");
+				}
+			}
+		}
+
+		#[test]
+		fn rejectsOutlandishFrames() {
+			let pixelCodes = &BitWriter::default();
+			for (dimensions, offsets, reason) in [
+				([u32::MAX, 3], [0, 0], "either way"),
+				([0, 3], [0, 0], "either way"),
+				([9, MAX_DIMENSION as u32 + 1], [0, 0], "either way"),
+				([9, 3], [i32::MAX - 4, 0], "overflows"),
+				([9, 3], [0, i32::MIN + 1], "overflows"),
+			] {
+				let err = decode(&oneFrameDCC(dimensions, offsets, pixelCodes)).err().unwrap().to_string();
+				assert!(err.ends_with(reason), "{dimensions:?} at {offsets:?}: {err}");
+			}
+		}
+	}
+}
+
+/// POSIX tar streams, the way several files go through one pipe: every entry is preceded by a pax header
+///   carrying its media type as the `user.mime_type` xattr (which GNU tar and bsdtar know how to keep), so
+///   `tar -tv`, `tar -x` and friends work on them as they are.
+pub mod tar {
+	use {
+		super::Error,
+		core::str,
+		std::io::{self, Read, Write},
+	};
+
+	pub const TOML: &str = "application/toml";
+	pub const PNG: &str = "image/png";
+	pub const OCTET_STREAM: &str = "application/octet-stream";
+	pub const JSON: &str = "application/json";
+	pub const XML: &str = "application/xml";
+	pub const TEXT: &str = "text/plain";
+
+	const BLOCK_SIZE: usize = 512;
+	const NAME_LEN: usize = 100;
+	const MEDIA_TYPE_KEY: &str = "SCHILY.xattr.user.mime_type";
+
+	const NAME: usize = 0;
+	const MODE: usize = 100;
+	const UID: usize = 108;
+	const GID: usize = 116;
+	const SIZE: usize = 124;
+	const MTIME: usize = 136;
+	const CHECKSUM: usize = 148;
+	const TYPEFLAG: usize = 156;
+	const MAGIC: usize = 257;
+	const PREFIX: usize = 345;
+
+	const REGULAR: u8 = b'0';
+	const OLD_REGULAR: u8 = b'\0';
+	const CONTIGUOUS: u8 = b'7';
+	const PAX_HEADER: u8 = b'x';
+	const GNU_LONG_NAME: u8 = b'L';
+
+	/// The media type an entry without one gets, going by its name's extension.
+	pub fn mediaTypeFromName(name: &str) -> &'static str {
+		let extension = name.rsplit_once('.').map_or("", |(_, extension)| extension);
+		[
+			("toml", TOML),
+			("png", PNG),
+			("json", "application/json"),
+			("tmx", "application/xml"),
+			("tsx", "application/xml"),
+			("txt", "text/plain"),
+		]
+		.into_iter()
+		.find(|(knownExtension, _)| extension.eq_ignore_ascii_case(knownExtension))
+		.map_or(OCTET_STREAM, |(_, mediaType)| mediaType)
+	}
+
+	pub struct Entry {
+		pub name: String,
+		pub mediaType: String,
+		pub data: Vec<u8>,
+	}
+	impl Entry {
+		/// Case-insensitively, as in `hasExtension("dt1")`.
+		pub fn hasExtension(&self, extension: &str) -> bool {
+			self.name.rsplit_once('.').is_some_and(|(_, own)| own.eq_ignore_ascii_case(extension))
+		}
+	}
+
+	/// A whole stream's entries, for binaries that look them up by the role they play rather than by position.
+	pub struct Entries(pub Vec<Entry>);
+	impl Entries {
+		pub fn read(from: impl Read) -> Result<Self, Error> {
+			Reader::new(from).collect::<Result<_, _>>().map(Self)
+		}
+
+		/// The one entry of `mediaType`.
+		pub fn only(&self, mediaType: &str) -> Result<&Entry, Error> {
+			let mut entries = self.0.iter().filter(|entry| entry.mediaType == mediaType);
+			match (entries.next(), entries.next()) {
+				(Some(entry), None) => Ok(entry),
+				(None, _) => Err(Error::Role(format!("no {mediaType} entry in the stream"))),
+				(Some(first), Some(second)) => Err(Error::Role(format!(
+					"both {:?} and {:?} are {mediaType}, only one is expected",
+					first.name, second.name
+				))),
+			}
+		}
+
+		pub fn all<'a>(&'a self, mediaType: &'a str) -> impl Iterator<Item = &'a Entry> {
+			self.0.iter().filter(move |entry| entry.mediaType == mediaType)
+		}
+	}
+
+	pub struct Reader<R: Read> {
+		from: R,
+		offset: u64,
+		isDone: bool,
+	}
+	impl<R: Read> Reader<R> {
+		pub fn new(from: R) -> Self {
+			Self { from, offset: 0, isDone: false }
+		}
+
+		fn nextEntry(&mut self) -> Result<Option<Entry>, Error> {
+			let (mut name, mut mediaType) = (None, None);
+			loop {
+				let headerOffset = self.offset;
+				let Some(header) = self.readHeader()? else {
+					return Ok(None);
+				};
+				let data = self.readData(parseNumber(&header[SIZE..][..12], headerOffset + SIZE as u64)?)?;
+				match header[TYPEFLAG] {
+					PAX_HEADER => {
+						for (key, value) in paxRecords(&data, headerOffset + BLOCK_SIZE as u64)? {
+							match key {
+								"path" => name = Some(value.to_owned()),
+								MEDIA_TYPE_KEY => mediaType = Some(value.to_owned()),
+								_ => {}
+							}
+						}
+					}
+					GNU_LONG_NAME => name = Some(String::from_utf8_lossy(cString(&data)).into_owned()),
+					REGULAR | OLD_REGULAR | CONTIGUOUS => {
+						let name = name.unwrap_or_else(|| {
+							let (prefix, name) =
+								(cString(&header[PREFIX..][..155]), cString(&header[NAME..][..NAME_LEN]));
+							String::from_utf8_lossy(&if prefix.is_empty() {
+								name.to_vec()
+							} else {
+								[prefix, b"/", name].concat()
+							})
+							.into_owned()
+						});
+						return Ok(Some(Entry {
+							mediaType: mediaType.unwrap_or_else(|| mediaTypeFromName(&name).to_owned()),
+							name,
+							data,
+						}));
+					}
+					// directories, links and the like have nothing to hand on
+					_ => (name, mediaType) = (None, None),
+				}
+			}
+		}
+
+		// `None` on the end-of-archive zero block, or on a clean end of input without one
+		fn readHeader(&mut self) -> Result<Option<[u8; BLOCK_SIZE]>, Error> {
+			let (mut header, offset) = ([0; BLOCK_SIZE], self.offset);
+			let len = self.readUpTo(&mut header)?;
+			if len == 0 || header.iter().all(|&byte| byte == 0) {
+				return Ok(None);
+			}
+			if len < BLOCK_SIZE {
+				return Err(Error::Truncated { offset: self.offset, needed: (BLOCK_SIZE - len) as _ });
+			}
+			let expected = parseNumber(&header[CHECKSUM..][..8], offset + CHECKSUM as u64)?;
+			let actual = checksum(&header);
+			if expected != actual {
+				return Err(Error::InvalidData {
+					offset,
+					reason: format!("tar header checksum is {expected:o} but the header sums to {actual:o}"),
+				});
+			}
+			Ok(Some(header))
+		}
+
+		fn readData(&mut self, len: u64) -> Result<Vec<u8>, Error> {
+			let mut data = Vec::new();
+			let read = (&mut self.from).take(len).read_to_end(&mut data)? as u64;
+			self.offset += read;
+			if read < len {
+				return Err(Error::Truncated { offset: self.offset, needed: len - read });
+			}
+			let padding = &mut [0; BLOCK_SIZE][..paddingLen(len)];
+			let read = self.readUpTo(padding)?;
+			if read < padding.len() {
+				return Err(Error::Truncated { offset: self.offset, needed: (padding.len() - read) as _ });
+			}
+			Ok(data)
+		}
+
+		fn readUpTo(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+			let mut len = 0;
+			while len < buffer.len() {
+				match self.from.read(&mut buffer[len..]) {
+					Ok(0) => break,
+					Ok(read) => len += read,
+					Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+					Err(err) => return Err(err),
+				}
+			}
+			self.offset += len as u64;
+			Ok(len)
+		}
+	}
+	impl<R: Read> Iterator for Reader<R> {
+		type Item = Result<Entry, Error>;
+		fn next(&mut self) -> Option<Self::Item> {
+			if self.isDone {
+				return None;
+			}
+			let entry = self.nextEntry().transpose();
+			self.isDone = !matches!(entry, Some(Ok(_)));
+			entry
+		}
+	}
+
+	pub struct Writer<W: Write> {
+		to: W,
+	}
+	impl<W: Write> Writer<W> {
+		pub fn new(to: W) -> Self {
+			Self { to }
+		}
+
+		pub fn append(&mut self, name: &str, mediaType: &str, data: &[u8]) -> io::Result<()> {
+			self.appendFrom(name, mediaType, data.len() as _, &mut &*data)
+		}
+
+		/// Copies exactly `len` bytes from `from`, so that files don't have to be read into memory first.
+		pub fn appendFrom(
+			&mut self,
+			name: &str,
+			mediaType: &str,
+			len: u64,
+			from: &mut impl Read,
+		) -> io::Result<()> {
+			let records = &mut String::new();
+			if name.len() > NAME_LEN {
+				paxRecord(records, "path", name);
+			}
+			paxRecord(records, MEDIA_TYPE_KEY, mediaType);
+			self.writeHeader(
+				&format!("PaxHeaders/{}", name.rsplit('/').next().unwrap()),
+				PAX_HEADER,
+				records.len() as _,
+			)?;
+			self.to.write_all(records.as_bytes())?;
+			self.writePadding(records.len() as _)?;
+
+			self.writeHeader(name, REGULAR, len)?;
+			let copied = io::copy(&mut from.take(len), &mut self.to)?;
+			if copied < len {
+				return Err(io::Error::new(
+					io::ErrorKind::UnexpectedEof,
+					format!("{name:?}: {len} bytes expected, only {copied} there"),
+				));
+			}
+			self.writePadding(len)
+		}
+
+		pub fn finish(mut self) -> io::Result<W> {
+			self.to.write_all(&[0; 2 * BLOCK_SIZE])?;
+			self.to.flush()?;
+			Ok(self.to)
+		}
+
+		// a name too long for the header is cut short there, the pax `path` record has it whole
+		fn writeHeader(&mut self, name: &str, typeflag: u8, size: u64) -> io::Result<()> {
+			if size > 0o777_7777_7777 {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidInput,
+					format!("{name:?}: too big for a tar entry"),
+				));
+			}
+			let header = &mut [0; BLOCK_SIZE];
+			let nameLen = (0..=name.len().min(NAME_LEN)).rev().find(|&len| name.is_char_boundary(len)).unwrap();
+			header[NAME..][..nameLen].copy_from_slice(&name.as_bytes()[..nameLen]);
+			for (field, len, value) in
+				[(MODE, 8, 0o644), (UID, 8, 0), (GID, 8, 0), (SIZE, 12, size), (MTIME, 12, 0)]
+			{
+				writeOctal(&mut header[field..][..len], value);
+			}
+			header[TYPEFLAG] = typeflag;
+			header[MAGIC..][..8].copy_from_slice(b"ustar\x0000");
+			let checksum = checksum(header);
+			writeOctal(&mut header[CHECKSUM..][..7], checksum);
+			header[CHECKSUM + 7] = b' ';
+			self.to.write_all(header)
+		}
+
+		fn writePadding(&mut self, len: u64) -> io::Result<()> {
+			self.to.write_all(&[0; BLOCK_SIZE][..paddingLen(len)])
+		}
+	}
+
+	fn paddingLen(len: u64) -> usize {
+		(BLOCK_SIZE - (len % BLOCK_SIZE as u64) as usize) % BLOCK_SIZE
+	}
+
+	// the checksum field counts as spaces
+	fn checksum(header: &[u8; BLOCK_SIZE]) -> u64 {
+		header
+			.iter()
+			.enumerate()
+			.map(|(i, &byte)| u64::from(if (CHECKSUM..TYPEFLAG).contains(&i) { b' ' } else { byte }))
+			.sum()
+	}
+
+	// zero-padded, NUL-terminated
+	fn writeOctal(field: &mut [u8], value: u64) {
+		let digits = format!("{value:0width$o}", width = field.len() - 1);
+		field[..digits.len()].copy_from_slice(digits.as_bytes());
+		field[digits.len()] = 0;
+	}
+
+	// octal, space or NUL terminated, or GNU's base-256 for what octal can't hold
+	fn parseNumber(field: &[u8], offset: u64) -> Result<u64, Error> {
+		if field[0] & 0x80 != 0 {
+			return Ok(field[1..].iter().fold(0, |number, &byte| number << 8 | u64::from(byte)));
+		}
+		let digits = str::from_utf8(field).ok().map(|field| field.trim_matches([' ', '\0']));
+		digits.and_then(|digits| u64::from_str_radix(digits, 8).ok()).ok_or_else(|| Error::InvalidData {
+			offset,
+			reason: format!("{:?} isn't an octal number", String::from_utf8_lossy(field)),
+		})
+	}
+
+	fn cString(bytes: &[u8]) -> &[u8] {
+		bytes.split(|&byte| byte == 0).next().unwrap()
+	}
+
+	// `"{len} {key}={value}\n"`, `len` counting its own digits
+	fn paxRecord(records: &mut String, key: &str, value: &str) {
+		let withoutLen = key.len() + value.len() + " =\n".len();
+		let mut len = withoutLen + withoutLen.to_string().len();
+		len = withoutLen + len.to_string().len();
+		records.push_str(&format!("{len} {key}={value}\n"));
+	}
+
+	fn paxRecords(mut records: &[u8], mut offset: u64) -> Result<Vec<(&str, &str)>, Error> {
+		let mut parsed = Vec::new();
+		while !records.is_empty() {
+			let len = records
+				.iter()
+				.position(|&byte| byte == b' ')
+				.and_then(|end| str::from_utf8(&records[..end]).ok()?.parse::<usize>().ok())
+				.filter(|&len| len <= records.len());
+			let keyValue = len.and_then(|len| {
+				str::from_utf8(&records[..len]).ok()?.strip_suffix('\n')?.split_once(' ')?.1.split_once('=')
+			});
+			let (Some(len), Some(keyValue)) = (len, keyValue) else {
+				return Err(Error::InvalidData { offset, reason: "malformed pax record".to_owned() });
+			};
+			parsed.push(keyValue);
+			records = &records[len..];
+			offset += len as u64;
+		}
+		Ok(parsed)
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use {super::*, core::iter};
+
+		fn roundTrip(entries: &[(&str, &str, &[u8])]) -> Vec<Entry> {
+			let mut writer = Writer::new(Vec::new());
+			for &(name, mediaType, data) in entries {
+				writer.append(name, mediaType, data).unwrap();
+			}
+			let stream = writer.finish().unwrap();
+			assert_eq!(stream.len() % BLOCK_SIZE, 0);
+			Entries::read(&*stream).unwrap().0
+		}
+
+		fn assertSame(entries: &[(&str, &str, &[u8])], read: &[Entry]) {
+			assert_eq!(read.len(), entries.len());
+			for (&(name, mediaType, data), entry) in iter::zip(entries, read) {
+				assert_eq!(entry.name, name);
+				assert_eq!(entry.mediaType, mediaType, "{name}");
+				assert_eq!(entry.data, data, "{name}");
+			}
+		}
+
+		#[test]
+		fn entriesSurviveWritingAndReading() {
+			let longName = &format!("{}floor.dt1.toml", "act1/outdoors/".repeat(8));
+			let longMultibyteName = &format!("{}é.png", "x".repeat(NAME_LEN - 1));
+			let entries: &[(&str, &str, &[u8])] = &[
+				("dt1.toml", TOML, b"[fileHeader]\n"),
+				("empty", OCTET_STREAM, b""),
+				(longName, TOML, &[7; BLOCK_SIZE]),
+				(longMultibyteName, PNG, &[1; BLOCK_SIZE + 1]),
+				("a.png", "image/x-not-really-png", &[2; BLOCK_SIZE - 1]),
+			];
+			assert!(longName.len() > NAME_LEN);
+			assertSame(entries, &roundTrip(entries));
+		}
+
+		#[test]
+		fn paxRecordsCountTheirOwnLength() {
+			for valueLen in 0..1100 {
+				let records = &mut String::new();
+				paxRecord(records, "path", &"v".repeat(valueLen));
+				let (len, _) = records.split_once(' ').unwrap();
+				assert_eq!(len.parse::<usize>().unwrap(), records.len(), "{records:?}");
+			}
+			// 98 bytes without the length, 2 digits for it make 100, so it takes 3 and the record is 101 long
+			let records = &mut String::new();
+			paxRecord(records, MEDIA_TYPE_KEY, &"m".repeat(98 - MEDIA_TYPE_KEY.len() - " =\n".len()));
+			assert!(records.starts_with("101 "), "{records:?}");
+			assert_eq!(records.len(), 101);
+
+			let mediaType =
+				&format!("application/{}", "m".repeat(98 - MEDIA_TYPE_KEY.len() - " =\n".len() - 12));
+			let entries: &[(&str, &str, &[u8])] = &[("crossing", mediaType, b"data")];
+			assertSame(entries, &roundTrip(entries));
+		}
+
+		#[test]
+		fn mediaTypesGoInTheirXattrOrComeFromTheName() {
+			let mut writer = Writer::new(Vec::new());
+			writer.append("tiles.bin", JSON, b"{}").unwrap();
+			let stream = writer.finish().unwrap();
+			let record = format!("{MEDIA_TYPE_KEY}={JSON}\n");
+			assert!(stream.windows(record.len()).any(|window| window == record.as_bytes()));
+
+			// plain ustar entries, as other tars write them
+			let mut writer = Writer::new(Vec::new());
+			for (name, data) in [("dt1.toml", &b"a"[..]), ("block.PNG", b"b"), ("pal.dat", b"c")] {
+				writer.writeHeader(name, REGULAR, data.len() as _).unwrap();
+				writer.to.write_all(data).unwrap();
+				writer.writePadding(data.len() as _).unwrap();
+			}
+			let entries = Entries::read(&*writer.finish().unwrap()).unwrap();
+			assertSame(
+				&[("dt1.toml", TOML, b"a"), ("block.PNG", PNG, b"b"), ("pal.dat", OCTET_STREAM, b"c")],
+				&entries.0,
+			);
+		}
+
+		#[test]
+		fn emptyStreamsHaveNoEntries() {
+			assert!(roundTrip(&[]).is_empty());
+			assert!(Entries::read(&[][..]).unwrap().0.is_empty());
+		}
+	}
+}
+
+/// Rectangle packing for atlases and block and tile sheets. `MaxRects` follows Jylänki's "A Thousand Ways to Pack
+///   the Bin" (best short side fit, best area fit, bottom-left); skyline is bottom-left. Nothing is rotated:
+///   sprites can't be.
+pub mod pack {
+	use {
+		super::{Rectangle, Vec2, DIMENSIONS, HEIGHT, WIDTH, X, Y},
+		core::{
+			cmp::{max, min, Reverse},
+			fmt, iter,
+			str::FromStr,
+		},
+		std::io::Write,
+	};
+
+	#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+	pub enum Heuristic {
+		BestShortSideFit,
+		BestAreaFit,
+		BottomLeft,
+		Skyline,
+	}
+	impl Heuristic {
+		pub const ALL: [Self; 4] =
+			[Self::BestShortSideFit, Self::BestAreaFit, Self::BottomLeft, Self::Skyline];
+
+		pub const fn name(self) -> &'static str {
+			match self {
+				Self::BestShortSideFit => "best-short-side-fit",
+				Self::BestAreaFit => "best-area-fit",
+				Self::BottomLeft => "bottom-left",
+				Self::Skyline => "skyline",
+			}
+		}
+	}
+	impl fmt::Display for Heuristic {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			f.write_str(self.name())
+		}
+	}
+	impl FromStr for Heuristic {
+		type Err = String;
+		fn from_str(s: &str) -> Result<Self, Self::Err> {
+			Self::ALL.into_iter().find(|heuristic| heuristic.name() == s).ok_or_else(|| {
+				format!("{s:?} isn't one of {}", Vec::from_iter(Self::ALL.map(Self::name)).join(", "))
+			})
+		}
+	}
+
+	/// Where each rectangle went, in the order they were given, and the dimensions of what they cover.
+	pub struct Packing {
+		pub dimensions: Vec2,
+		pub points: Vec<Vec2>,
+	}
+	impl Packing {
+		// a packed sheet can't be walked like columns; its PNG carries the points, as "x,y x,y …"
+		pub const PNG_KEYWORD: &'static str = "d2sw:packed-points";
+
+		pub fn addToPNG<W: Write>(&self, png: &mut png::Encoder<'_, W>) {
+			let text = Vec::from_iter(self.points.iter().map(|[x, y]| format!("{x},{y}"))).join(" ");
+			png.add_text_chunk(Self::PNG_KEYWORD.to_owned(), text).unwrap();
+		}
+
+		/// The points a packed sheet's PNG carries; `None` for a sheet in columns.
+		pub fn pointsOfPNG(info: &png::Info<'_>) -> Option<Vec<Vec2>> {
+			let chunk = info.uncompressed_latin1_text.iter().find(|chunk| chunk.keyword == Self::PNG_KEYWORD)?;
+			Some(Vec::from_iter(chunk.text.split_ascii_whitespace().map(|point| {
+				point
+					.split_once(',')
+					.and_then(|(x, y)| Some([x.parse().ok()?, y.parse().ok()?]))
+					.unwrap_or_else(|| panic!("{}: {point:?} isn't a point", Self::PNG_KEYWORD))
+			})))
+		}
+	}
+
+	/// Packs `dimensions` into the smallest bin found: a spread of widths around the square one, each with the
+	///   least height it fits in. The same `dimensions` always pack the same way.
+	pub fn pack(dimensions: &[Vec2], heuristic: Heuristic) -> Packing {
+		if dimensions.is_empty() {
+			return Packing { dimensions: [0; 2], points: Vec::new() };
+		}
+		let area = dimensions.iter().map(|&[width, height]| width * height).sum::<usize>();
+		let [maxWidth, maxHeight] =
+			[WIDTH, HEIGHT].map(|dimension| dimensions.iter().map(|d| d[dimension]).max().unwrap());
+		let [sumWidth, sumHeight] =
+			[WIDTH, HEIGHT].map(|dimension| dimensions.iter().map(|d| d[dimension]).sum::<usize>());
+		let side = (area as f64).sqrt().ceil() as usize;
+		let mut binWidths =
+			Vec::from_iter((4..=16).map(|eighths| (side * eighths / 8).clamp(maxWidth, sumWidth)));
+		binWidths.dedup();
+		let mut best: Option<Packing> = None;
+		for binWidth in binWidths {
+			// one on top of another, everything fits `sumHeight`
+			let [mut low, mut high] = [max(maxHeight, area.div_ceil(binWidth)), sumHeight];
+			let mut points = packInto([binWidth, high], dimensions, heuristic).unwrap();
+			while low < high {
+				let middle = low.midpoint(high);
+				if let Some(middlePoints) = packInto([binWidth, middle], dimensions, heuristic) {
+					(high, points) = (middle, middlePoints);
+				} else {
+					low = middle + 1;
+				}
+			}
+			let packing = Packing { dimensions: covered(dimensions, &points), points };
+			// the least area, then the squarest
+			let key =
+				|&Packing { dimensions: [width, height], .. }: &Packing| (width * height, max(width, height));
+			if best.as_ref().is_none_or(|best| key(&packing) < key(best)) {
+				best = Some(packing);
+			}
+		}
+		best.unwrap()
+	}
+
+	/// Packs `dimensions` into a bin of `binDimensions`, biggest first; `None` if they don't all fit.
+	pub fn packInto(binDimensions: Vec2, dimensions: &[Vec2], heuristic: Heuristic) -> Option<Vec<Vec2>> {
+		let (mut points, bin) = (vec![[0; 2]; dimensions.len()], &mut Bin::new(binDimensions, heuristic));
+		for i in biggestFirst(dimensions, 0..dimensions.len()) {
+			points[i] = bin.insert(dimensions[i])?;
+		}
+		Some(points)
+	}
+
+	/// Packs `dimensions` onto pages no bigger than `maxDimensions`, filling one before starting the next; each
+	///   page then shrinks to the smallest bin `pack` finds for it that's within them. Returns every page's
+	///   packing with which of `dimensions` it holds, in order.
+	pub fn packPages(
+		dimensions: &[Vec2],
+		heuristic: Heuristic,
+		maxDimensions: Vec2,
+	) -> Vec<(Packing, Vec<usize>)> {
+		let (mut pages, mut left) = (Vec::new(), biggestFirst(dimensions, 0..dimensions.len()));
+		while !left.is_empty() {
+			let (bin, placed) = (&mut Bin::new(maxDimensions, heuristic), &mut Vec::new());
+			left.retain(|&i| {
+				let Some(point) = bin.insert(dimensions[i]) else {
+					return true;
+				};
+				placed.push((i, point));
+				false
+			});
+			assert!(!placed.is_empty(), "{:?} doesn't fit a page of {maxDimensions:?}", dimensions[left[0]]);
+			placed.sort_unstable();
+			let (page, points) = placed.iter().copied().unzip::<_, _, Vec<_>, Vec<_>>();
+			let pageDimensions = &Vec::from_iter(page.iter().map(|&i| dimensions[i]));
+			let packing = pack(pageDimensions, heuristic);
+			pages.push((
+				if packing.dimensions[WIDTH] <= maxDimensions[WIDTH]
+					&& packing.dimensions[HEIGHT] <= maxDimensions[HEIGHT]
+				{
+					packing
+				} else {
+					Packing { dimensions: covered(pageDimensions, &points), points }
+				},
+				page,
+			));
+		}
+		pages
+	}
+
+	fn biggestFirst(dimensions: &[Vec2], indices: impl Iterator<Item = usize>) -> Vec<usize> {
+		let mut indices = Vec::from_iter(indices);
+		indices.sort_by_key(|&i| {
+			let [width, height] = dimensions[i];
+			(Reverse(max(width, height)), Reverse(width * height))
+		});
+		indices
+	}
+
+	struct Bin {
+		heuristic: Heuristic,
+		maxRects: MaxRects,
+		skyline: Skyline,
+	}
+	impl Bin {
+		fn new(binDimensions: Vec2, heuristic: Heuristic) -> Self {
+			Self { heuristic, maxRects: MaxRects::new(binDimensions), skyline: Skyline::new(binDimensions) }
+		}
+
+		fn insert(&mut self, dimensions @ [width, height]: Vec2) -> Option<Vec2> {
+			if width == 0 || height == 0 {
+				return Some([0; 2]);
+			}
+			match self.heuristic {
+				Heuristic::Skyline => self.skyline.insert(dimensions),
+				heuristic => self.maxRects.insert(dimensions, heuristic),
+			}
+		}
+	}
+
+	fn covered(dimensions: &[Vec2], points: &[Vec2]) -> Vec2 {
+		iter::zip(dimensions, points)
+			.fold([0; 2], |[width, height], (&[w, h], &[x, y])| [max(width, x + w), max(height, y + h)])
+	}
+
+	struct MaxRects {
+		freeRects: Vec<Rectangle>,
+	}
+	impl MaxRects {
+		fn new(binDimensions: Vec2) -> Self {
+			Self { freeRects: vec![[[0; 2], binDimensions]] }
+		}
+
+		fn insert(&mut self, dimensions @ [width, height]: Vec2, heuristic: Heuristic) -> Option<Vec2> {
+			let (_, point) = self
+				.freeRects
+				.iter()
+				.filter(|freeRect| freeRect[DIMENSIONS][WIDTH] >= width && freeRect[DIMENSIONS][HEIGHT] >= height)
+				.map(|&[point, [freeWidth, freeHeight]]| {
+					let [leftoverX, leftoverY] = [freeWidth - width, freeHeight - height];
+					let score = match heuristic {
+						Heuristic::BestShortSideFit => [min(leftoverX, leftoverY), max(leftoverX, leftoverY)],
+						Heuristic::BestAreaFit => [freeWidth * freeHeight - width * height, min(leftoverX, leftoverY)],
+						_ => [point[Y] + height, point[X]],
+					};
+					(score, point)
+				})
+				.min()?;
+			self.split([point, dimensions]);
+			Some(point)
+		}
+
+		// every free rectangle `placed` overlaps gives way to the up-to-four maximal ones around it, dropping those
+		//   inside another; only these can be, as the untouched ones weren't inside the ones they come from
+		fn split(&mut self, [[x, y], [width, height]]: Rectangle) {
+			let pieces = &mut Vec::new();
+			self.freeRects.retain(|&[[freeX, freeY], [freeWidth, freeHeight]]| {
+				if x >= freeX + freeWidth || x + width <= freeX || y >= freeY + freeHeight || y + height <= freeY {
+					return true;
+				}
+				if x > freeX {
+					pieces.push([[freeX, freeY], [x - freeX, freeHeight]]);
+				}
+				if x + width < freeX + freeWidth {
+					pieces.push([[x + width, freeY], [freeX + freeWidth - (x + width), freeHeight]]);
+				}
+				if y > freeY {
+					pieces.push([[freeX, freeY], [freeWidth, y - freeY]]);
+				}
+				if y + height < freeY + freeHeight {
+					pieces.push([[freeX, y + height], [freeWidth, freeY + freeHeight - (y + height)]]);
+				}
+				false
+			});
+			let contains = |[[x, y], [width, height]]: Rectangle,
+			                [[innerX, innerY], [innerWidth, innerHeight]]: Rectangle| {
+				x <= innerX
+					&& y <= innerY
+					&& innerX + innerWidth <= x + width
+					&& innerY + innerHeight <= y + height
+			};
+			let kept = Vec::from_iter(pieces.iter().enumerate().filter(|&(i, &piece)| {
+				!self.freeRects.iter().any(|&freeRect| contains(freeRect, piece))
+					&& !pieces
+						.iter()
+						.enumerate()
+						.any(|(j, &other)| j != i && contains(other, piece) && (other != piece || j < i))
+			}));
+			self.freeRects.extend(kept.into_iter().map(|(_, &piece)| piece));
+		}
+	}
+
+	struct Skyline {
+		binDimensions: Vec2,
+		// [x, y, width], left to right, covering the bin's width
+		segments: Vec<[usize; 3]>,
+	}
+	impl Skyline {
+		fn new(binDimensions: Vec2) -> Self {
+			Self { binDimensions, segments: vec![[0, 0, binDimensions[WIDTH]]] }
+		}
+
+		fn insert(&mut self, [width, height]: Vec2) -> Option<Vec2> {
+			let ((_, i), y) = (0..self.segments.len())
+				.filter_map(|i| {
+					let y = self.fitAt(i, width, height)?;
+					Some(((y + height, i), y))
+				})
+				.min()?;
+			let x = self.segments[i][0];
+			self.place(i, [x, y], [width, height]);
+			Some([x, y])
+		}
+
+		// where a rectangle starting at segment `i` rests, if it's inside the bin there
+		fn fitAt(&self, i: usize, width: usize, height: usize) -> Option<usize> {
+			let [binWidth, binHeight] = self.binDimensions;
+			let x = self.segments[i][0];
+			if x + width > binWidth {
+				return None;
+			}
+			let (mut y, mut widthLeft) = (0, width);
+			for &[_, segmentY, segmentWidth] in &self.segments[i..] {
+				if widthLeft == 0 {
+					break;
+				}
+				y = max(y, segmentY);
+				widthLeft = widthLeft.saturating_sub(segmentWidth);
+			}
+			(y + height <= binHeight).then_some(y)
+		}
+
+		fn place(&mut self, i: usize, [x, y]: Vec2, [width, height]: Vec2) {
+			self.segments.insert(i, [x, y + height, width]);
+			let end = x + width;
+			while let Some(segment) = self.segments.get_mut(i + 1) {
+				if segment[0] >= end {
+					break;
+				}
+				let segmentEnd = segment[0] + segment[2];
+				if segmentEnd <= end {
+					self.segments.remove(i + 1);
+				} else {
+					*segment = [end, segment[1], segmentEnd - end];
+					break;
+				}
+			}
+			self.segments.dedup_by(|next, segment| {
+				let merge = segment[1] == next[1];
+				if merge {
+					segment[2] += next[2];
+				}
+				merge
+			});
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		// a few blocks' and sprites' worth of sizes, with repeats and odd ones out
+		fn samples() -> [Vec<Vec2>; 3] {
+			[
+				vec![[32, 32]; 25],
+				Vec::from_iter((1..40).map(|i| [(i * 7) % 53 + 1, (i * 13) % 41 + 1])),
+				vec![[160, 80], [32, 15], [1, 1], [97, 3], [3, 97], [64, 64], [32, 32], [160, 80], [5, 200]],
+			]
+		}
+
+		fn assertFits(binDimensions: Vec2, dimensions: &[Vec2], points: &[Vec2], heuristic: Heuristic) {
+			assert_eq!(points.len(), dimensions.len(), "{heuristic}");
+			for (i, (&[x, y], &[width, height])) in iter::zip(points, dimensions).enumerate() {
+				assert!(
+					x + width <= binDimensions[WIDTH] && y + height <= binDimensions[HEIGHT],
+					"{heuristic}: #{i} at {:?} sticks out of {binDimensions:?}",
+					[x, y],
+				);
+				for (j, (&[otherX, otherY], &[otherWidth, otherHeight])) in
+					iter::zip(points, dimensions).enumerate().skip(i + 1)
+				{
+					assert!(
+						x + width <= otherX
+							|| otherX + otherWidth <= x
+							|| y + height <= otherY
+							|| otherY + otherHeight <= y,
+						"{heuristic}: #{i} and #{j} overlap",
+					);
+				}
+			}
+		}
+
+		#[test]
+		fn packHasNoOverlapsWithinItsDimensions() {
+			for dimensions in samples() {
+				for heuristic in Heuristic::ALL {
+					let Packing { dimensions: binDimensions, points } = pack(&dimensions, heuristic);
+					assertFits(binDimensions, &dimensions, &points, heuristic);
 				}
-				Δy += 1;
-				yMulWidthAddX0 += width;
 			}
 		}
 
-		/*
-			RLE Block :
-
-			1st byte is pixels to "jump", 2nd is number of "solid" pixels, followed by the pixel color indexes.
-			when 1st and 2nd bytes are 0 and 0, next line.
-		*/
-		fn drawBlockRLE(&mut self, x0: usize, y0: usize, data: &[u8]) {
-			let [mut length, width] = [data.len(), self.width()];
+		#[test]
+		fn packIsDeterministic() {
+			for dimensions in samples() {
+				for heuristic in Heuristic::ALL {
+					let (first, second) = (pack(&dimensions, heuristic), pack(&dimensions, heuristic));
+					assert_eq!((first.dimensions, first.points), (second.dimensions, second.points), "{heuristic}");
+				}
+			}
+		}
 
-			// draw
-			let (mut i, [mut j, mut yMulWidthAddX0]) = (0, [y0 * width + x0; 2]);
-			while length > 0 {
-				let [xjump, mut xsolid] = [data[i + 0] as usize, data[i + 1] as usize];
-				i += 2;
-				length -= 2;
-				if xjump != 0 || xsolid != 0 {
-					j += xjump;
-					length -= xsolid;
-					while xsolid != 0 {
-						self.putpixel(j, data[i]);
-						i += 1;
-						j += 1;
-						xsolid -= 1;
+		#[test]
+		fn packIntoFitsItsBinOrGivesUp() {
+			for dimensions in samples() {
+				for heuristic in Heuristic::ALL {
+					for binDimensions in [[256, 256], [200, 200], [161, 400], [64, 64]] {
+						let points = packInto(binDimensions, &dimensions, heuristic);
+						assert_eq!(points, packInto(binDimensions, &dimensions, heuristic), "{heuristic}");
+						if let Some(points) = points {
+							assertFits(binDimensions, &dimensions, &points, heuristic);
+						}
 					}
-				} else {
-					yMulWidthAddX0 += width;
-					j = yMulWidthAddX0;
 				}
 			}
+			assert_eq!(packInto([64, 64], &[[65, 1]], Heuristic::Skyline), None);
 		}
 	}
+}
 
-	pub const TILEWIDTH: usize = 160;
-	pub const FLOOR_ROOF_TILEHEIGHT: usize = 79 + 1;
-	pub const BLOCKWIDTH: usize = 32;
-	pub const FLOOR_ROOF_BLOCKHEIGHT: usize = NBPIX.len() + 1;
-	pub const MAX_BLOCKHEIGHT: usize = 32;
+/// What an atlas's packed sprites are written out as, for the engines that read them.
+pub mod atlas {
+	use {
+		super::{
+			tar,
+			tmx::{escape, jsonString},
+			toml_toStringPretty, Rectangle, Vec2, DIMENSIONS, HEIGHT, WIDTH, X, Y,
+		},
+		core::{fmt, iter, str::FromStr},
+		std::{collections::BTreeMap, fmt::Write as _, path::Path},
+	};
 
-	pub const SQUARE_TILE_SIZE: usize = TILEWIDTH / 2;
-	pub const SQUARE_SUBTILE_SIZE: usize = BLOCKWIDTH / 2;
+	/// One sprite: where it is in the atlas, and where that was in the tile it was trimmed from.
+	#[derive(Clone)]
+	pub struct Sprite {
+		pub gid: usize,
+		pub rectangle: Rectangle,
+		pub trimmedPoint: Vec2,
+		pub tileDimensions: Vec2,
+		/// From the rectangle's point to the point the sprite is drawn by; flars' `ox, oy`.
+		pub offset: [isize; 2],
+	}
+	impl Sprite {
+		fn isTrimmed(&self) -> bool {
+			self.trimmedPoint != [0; 2] || self.rectangle[DIMENSIONS] != self.tileDimensions
+		}
+		// the point the sprite is drawn by, as a fraction of the tile
+		fn pivot(&self) -> [f64; 2] {
+			[X, Y].map(|axis| {
+				(self.trimmedPoint[axis] as isize + self.offset[axis]) as f64 / self.tileDimensions[axis] as f64
+			})
+		}
+	}
 
-	pub const FLOOR_ORIENTATION: i32 = 0;
-	pub const ROOF_ORIENTATION: i32 = 15;
+	/// One atlas image and the sprites on it.
+	pub struct Page {
+		/// The path the definitions refer to it by.
+		pub image: String,
+		pub dimensions: Vec2,
+		pub sprites: Vec<Sprite>,
+	}
 
-	#[macro_export]
-	macro_rules! lowerWalls {
-		() => {
-			(ROOF_ORIENTATION + 1)..
-		};
+	/// `image` itself for a single page; `atlas-0.png`, `atlas-1.png`… for several.
+	pub fn pageImage(image: &str, page: usize, numPages: usize) -> String {
+		if numPages == 1 {
+			return image.to_owned();
+		}
+		match image.rsplit_once('.') {
+			Some((stem, extension)) if !extension.contains('/') => format!("{stem}-{page}.{extension}"),
+			_ => format!("{image}-{page}"),
+		}
 	}
 
-	impl super::Image {
-		pub fn fromDT1(tiles: &[Tile], dt1: &[u8]) -> Self {
-			let [mut minBlockHeight, mut maxBlockHeight] = [usize::MAX, 0];
-			for tile in tiles {
-				let blockHeight = tile.blockHeight();
-				if blockHeight == 0 {
-					continue;
-				}
-				minBlockHeight.minAssign(blockHeight);
-				maxBlockHeight.maxAssign(blockHeight);
+	#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+	pub enum Format {
+		#[default]
+		Flars,
+		TexturePackerHash,
+		TexturePackerArray,
+		LibGDX,
+		Godot,
+		Tiled,
+	}
+	impl Format {
+		pub const ALL: [Self; 6] = [
+			Self::Flars,
+			Self::TexturePackerHash,
+			Self::TexturePackerArray,
+			Self::LibGDX,
+			Self::Godot,
+			Self::Tiled,
+		];
+
+		pub const fn name(self) -> &'static str {
+			match self {
+				Self::Flars => "flars",
+				Self::TexturePackerHash => "texturepacker-hash",
+				Self::TexturePackerArray => "texturepacker-array",
+				Self::LibGDX => "libgdx",
+				Self::Godot => "godot",
+				Self::Tiled => "tiled",
 			}
-			let (width, height);
-			{
-				let chosenTileColumns = &{
-					let choices = &mut Vec::<TileColumns>::new();
-					choices.push(TileColumns {
-						fullColumnHeight: maxBlockHeight,
-						numOverflownColumns: 0,
-						lastColumnHeight: 0,
-					});
-					for tile in tiles {
-						let blockHeight = tile.blockHeight();
-						for _ in &tile.blocks {
-							choices.push(choices.last().unwrap().clone());
-							let mut i = 0;
-							while i < choices.len() {
-								let result = choices[i].pushTile(blockHeight);
-								if i == choices.len() - 2 {
-									let lastIndex = choices.len() - 1;
-									if result == 0 {
-										choices.truncate(lastIndex);
-									} else {
-										assert_eq!(choices[lastIndex].numOverflownColumns, 0);
-										choices[lastIndex].fullColumnHeight += minBlockHeight;
-										choices.push(choices[lastIndex].clone());
-									}
-								}
-								i += 1;
-							}
+		}
+
+		/// The tar entries `pages` make in this format, as `(name, media type, data)`.
+		pub fn entries(self, pages: &[Page], firstgid: usize) -> Vec<(String, &'static str, Vec<u8>)> {
+			let mut text = String::new();
+			let name = match self {
+				Self::Flars => {
+					let atlasDefs = BTreeMap::from_iter(pages.iter().map(|page| {
+						(
+							&*page.image,
+							Vec::from_iter(page.sprites.iter().map(|sprite| {
+								let [[x, y], [width, height]] = sprite.rectangle;
+								(sprite.gid, x, y, width, height, sprite.offset[X], sprite.offset[Y])
+							})),
+						)
+					}));
+					text = toml_toStringPretty(&atlasDefs).unwrap_or_else(|err| panic!("{err}"));
+					"atlasDefs.toml"
+				}
+				Self::TexturePackerHash | Self::TexturePackerArray => {
+					let names = Vec::from_iter((0..pages.len()).map(|i| pageImage("atlas.json", i, pages.len())));
+					return Vec::from_iter(iter::zip(pages, &names).map(|(page, name)| {
+						let relatedMultiPacks =
+							Vec::from_iter(names.iter().filter(|&other| other != name).map(|other| jsonString(other)));
+						(name.clone(), tar::JSON, self.texturePackerJSON(page, &relatedMultiPacks).into_bytes())
+					}));
+				}
+				Self::LibGDX => {
+					for (i, page) in pages.iter().enumerate() {
+						let [pageWidth, pageHeight] = page.dimensions;
+						if i != 0 {
+							writeln!(text).unwrap();
+						}
+						writeln!(text, "{}", page.image).unwrap();
+						writeln!(text, "size: {pageWidth}, {pageHeight}").unwrap();
+						writeln!(text, "format: RGBA8888").unwrap();
+						writeln!(text, "filter: Nearest, Nearest").unwrap();
+						writeln!(text, "repeat: none").unwrap();
+						for sprite in &page.sprites {
+							let ([[x, y], [width, height]], [trimmedX, trimmedY], [tileWidth, tileHeight]) =
+								(sprite.rectangle, sprite.trimmedPoint, sprite.tileDimensions);
+							writeln!(text, "{}", sprite.gid).unwrap();
+							writeln!(text, "  rotate: false").unwrap();
+							writeln!(text, "  xy: {x}, {y}").unwrap();
+							writeln!(text, "  size: {width}, {height}").unwrap();
+							writeln!(text, "  orig: {tileWidth}, {tileHeight}").unwrap();
+							// libGDX counts the offset up from the bottom
+							writeln!(text, "  offset: {trimmedX}, {}", tileHeight - trimmedY - height).unwrap();
+							writeln!(text, "  index: -1").unwrap();
 						}
 					}
-					choices.sort_by(|a, b| {
-						let dimensions = [a, b].map(|tileColumns| tileColumns.dimensions(BLOCKWIDTH));
-						let pow2SquareSizes = dimensions.map(|[width, height]| max(width, height).next_power_of_two());
-						const A: usize = 0;
-						const B: usize = 1;
-						pow2SquareSizes[A]
-							.cmp(&pow2SquareSizes[B])
-							.then_with(|| dimensions[B][WIDTH].cmp(&dimensions[A][WIDTH]))
+					"atlas.atlas"
+				}
+				Self::Godot => {
+					return Vec::from_iter(pages.iter().flat_map(|page| {
+						page.sprites.iter().map(|sprite| {
+							let ([[x, y], [width, height]], [trimmedX, trimmedY], [tileWidth, tileHeight]) =
+								(sprite.rectangle, sprite.trimmedPoint, sprite.tileDimensions);
+							let mut text = String::new();
+							writeln!(text, r#"[gd_resource type="AtlasTexture" load_steps=2 format=3]"#).unwrap();
+							writeln!(text).unwrap();
+							writeln!(text, r#"[ext_resource type="Texture2D" path={} id="1"]"#, jsonString(&page.image))
+								.unwrap();
+							writeln!(text).unwrap();
+							writeln!(text, "[resource]").unwrap();
+							writeln!(text, r#"atlas = ExtResource("1")"#).unwrap();
+							writeln!(text, "region = Rect2({x}, {y}, {width}, {height})").unwrap();
+							writeln!(
+								text,
+								"margin = Rect2({trimmedX}, {trimmedY}, {}, {})",
+								tileWidth - width,
+								tileHeight - height,
+							)
+							.unwrap();
+							(format!("{}.tres", sprite.gid), tar::TEXT, text.into_bytes())
+						})
+					}));
+				}
+				Self::Tiled => {
+					// an image collection with every tile a sub-rectangle of its page, as Tiled 1.9 has them;
+					//   Tiled anchors tiles bottom-left, so the offsets go along as properties
+					let sprites =
+						|| pages.iter().flat_map(|page| page.sprites.iter().map(move |sprite| (page, sprite)));
+					let [tilewidth, tileheight] = [WIDTH, HEIGHT].map(|dimension| {
+						sprites().map(|(_, sprite)| sprite.rectangle[DIMENSIONS][dimension]).max().unwrap_or(0)
 					});
-					mem::take(&mut choices[0])
-				};
-				[width, height] = chosenTileColumns.dimensions(BLOCKWIDTH);
-				eprintln!(
-					"[{width}, {height}]; lastColumnHeight = {}, minBlockHeight = {minBlockHeight}",
-					chosenTileColumns.lastColumnHeight,
-				);
-			}
-			let mut image = Self::fromWidthHeight(width, height);
-			let (mut x, mut y) = (0, 0);
-			for tile in tiles {
-				for block in &tile.blocks {
-					let nextY = {
-						let blockHeight = tile.blockHeight();
-						let nextY = y + blockHeight;
-						if nextY > height {
-							x += BLOCKWIDTH;
-							y = 0;
-							blockHeight
-						} else {
-							nextY
+					writeln!(text, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+					writeln!(
+						text,
+						concat!(
+							r#"<tileset version="1.10" tiledversion="1.10.2" name="{}" tilewidth="{}" tileheight="{}""#,
+							r#" tilecount="{}" columns="0">"#,
+						),
+						escape(&pages.first().map_or_else(String::new, |page| {
+							Path::new(&page.image).file_stem().unwrap_or_default().to_string_lossy().into_owned()
+						})),
+						tilewidth,
+						tileheight,
+						sprites().count(),
+					)
+					.unwrap();
+					writeln!(text, r#" <grid orientation="orthogonal" width="1" height="1"/>"#).unwrap();
+					for (page, sprite) in sprites() {
+						let ([[x, y], [width, height]], [pageWidth, pageHeight]) = (sprite.rectangle, page.dimensions);
+						writeln!(
+							text,
+							r#" <tile id="{}" x="{x}" y="{y}" width="{width}" height="{height}">"#,
+							sprite.gid - firstgid,
+						)
+						.unwrap();
+						writeln!(text, "  <properties>").unwrap();
+						for (name, value) in [("offsetX", sprite.offset[X]), ("offsetY", sprite.offset[Y])] {
+							writeln!(text, r#"   <property name="{name}" type="int" value="{value}"/>"#).unwrap();
 						}
-					};
-					block.drawFn()(
-						&mut image,
-						x,
-						y,
-						&dt1[(tile.blockHeadersPointer + block.fileOffset) as _..][..block.length as _],
-					);
-					y = nextY;
+						writeln!(text, "  </properties>").unwrap();
+						writeln!(
+							text,
+							r#"  <image source="{}" width="{pageWidth}" height="{pageHeight}"/>"#,
+							escape(&page.image),
+						)
+						.unwrap();
+						writeln!(text, " </tile>").unwrap();
+					}
+					writeln!(text, "</tileset>").unwrap();
+					"atlas.tsx"
 				}
-			}
-			image
+			};
+			let mediaType = match self {
+				Self::Flars => tar::TOML,
+				Self::TexturePackerHash | Self::TexturePackerArray => tar::JSON,
+				Self::Tiled => tar::XML,
+				Self::LibGDX | Self::Godot => tar::TEXT,
+			};
+			vec![(name.to_owned(), mediaType, text.into_bytes())]
 		}
 
-		pub fn drawNoisySquareTile(&mut self, mut destPoint: Vec2, srcImage: &Self, mut srcPoint: Vec2) {
-			destPoint[Y] += 1;
-			srcPoint[X] += SQUARE_TILE_SIZE - 1;
-			let [mut iY, mut jY] = [srcPoint[Y] * srcImage.width, destPoint[Y] * self.width];
-			for Δx in 0..SQUARE_TILE_SIZE {
-				let [mut i, mut j] = [srcPoint[X] + iY, destPoint[X] + jY];
-				for Δy in 0..SQUARE_TILE_SIZE {
-					match srcImage.data[i] {
-						FULLY_TRANSPARENT => {}
-						pixelValue => {
-							assert_eq!(self.data[j], FULLY_TRANSPARENT);
-							self.data[j] = pixelValue;
-						}
-					}
-					i += usize::MAX + if Δy % 2 == 0 { 0 } else { srcImage.width };
-					j += self.width;
+		fn texturePackerJSON(self, page: &Page, relatedMultiPacks: &[String]) -> String {
+			let (mut text, isHash, [pageWidth, pageHeight]) =
+				(String::new(), self == Self::TexturePackerHash, page.dimensions);
+			writeln!(text, "{{").unwrap();
+			writeln!(text, r#" "frames": {}"#, if isHash { "{" } else { "[" }).unwrap();
+			for (i, sprite) in page.sprites.iter().enumerate() {
+				let ([[x, y], [width, height]], [trimmedX, trimmedY], [tileWidth, tileHeight]) =
+					(sprite.rectangle, sprite.trimmedPoint, sprite.tileDimensions);
+				let [pivotX, pivotY] = sprite.pivot();
+				let filename = jsonString(&sprite.gid.to_string());
+				let mut fields = vec![
+					format!(r#""frame": {{ "x": {x}, "y": {y}, "w": {width}, "h": {height} }}"#),
+					r#""rotated": false"#.to_owned(),
+					format!(r#""trimmed": {}"#, sprite.isTrimmed()),
+					format!(
+						r#""spriteSourceSize": {{ "x": {trimmedX}, "y": {trimmedY}, "w": {width}, "h": {height} }}"#
+					),
+					format!(r#""sourceSize": {{ "w": {tileWidth}, "h": {tileHeight} }}"#),
+					format!(r#""pivot": {{ "x": {pivotX}, "y": {pivotY} }}"#),
+				];
+				if !isHash {
+					fields.insert(0, format!(r#""filename": {filename}"#));
 				}
-				destPoint.addAssign([
-					1,
-					(if Δx % 2 == 0 {
-						srcPoint[X] += 2;
-						usize::MAX
-					} else {
-						srcPoint[Y] += 1;
-						iY += srcImage.width;
-						1
-					})
-					.also(|&Δy| jY += self.width.mulSignumOf(Δy)),
-				]);
+				writeln!(
+					text,
+					"  {}{{ {} }}{}",
+					if isHash { format!("{filename}: ") } else { String::new() },
+					fields.join(", "),
+					if i == page.sprites.len() - 1 { "" } else { "," },
+				)
+				.unwrap();
 			}
+			writeln!(text, " {},", if isHash { "}" } else { "]" }).unwrap();
+			writeln!(
+				text,
+				concat!(
+					r#" "meta": {{ "app": "d2sw", "version": "1.0", "image": {}, "format": "RGBA8888","#,
+					r#" "size": {{ "w": {}, "h": {} }}, "scale": "1"{} }}"#,
+				),
+				jsonString(&page.image),
+				pageWidth,
+				pageHeight,
+				if relatedMultiPacks.is_empty() {
+					String::new()
+				} else {
+					format!(r#", "related_multi_packs": [{}]"#, relatedMultiPacks.join(", "))
+				},
+			)
+			.unwrap();
+			writeln!(text, "}}").unwrap();
+			text
+		}
+	}
+	impl fmt::Display for Format {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			f.write_str(self.name())
+		}
+	}
+	impl FromStr for Format {
+		type Err = String;
+		fn from_str(s: &str) -> Result<Self, Self::Err> {
+			Self::ALL.into_iter().find(|format| format.name() == s).ok_or_else(|| {
+				format!("{s:?} isn't one of {}", Vec::from_iter(Self::ALL.map(Self::name)).join(", "))
+			})
 		}
 	}
 }
@@ -862,15 +6357,19 @@ pub mod dt1 {
 use {
 	core::{
 		cmp::{max, min},
+		fmt,
+		mem::size_of,
 		ops::{Div, Rem},
+		str::FromStr,
 	},
 	dt1::BLOCKWIDTH,
 	glam::{IVec2, IVec3, IVec4},
 	serde::ser,
 	std::{
+		error,
 		fs::File,
 		io::{self, Read, Write},
-		os,
+		os, vec,
 	},
 };
 
@@ -1003,6 +6502,25 @@ impl Image {
 			j += ΔjNextRow;
 		}
 	}
+
+	/// Repeats the edge pixels of `rectangle` `by` times outwards, so that filtering at its edges doesn't
+	///   sample whatever is next to it.
+	pub fn extrude(&mut self, [[x, y], [width, height]]: Rectangle, by: usize) {
+		if width == 0 || height == 0 {
+			return;
+		}
+		for row in y..y + height {
+			let rowStart = row * self.width;
+			let [left, right] = [self.data[rowStart + x], self.data[rowStart + x + width - 1]];
+			self.data[rowStart + x - by..][..by].fill(left);
+			self.data[rowStart + x + width..][..by].fill(right);
+		}
+		let rowRange = |row: usize| row * self.width + x - by..row * self.width + x + width + by;
+		for Δy in 1..=by {
+			self.data.copy_within(rowRange(y), rowRange(y - Δy).start);
+			self.data.copy_within(rowRange(y + height - 1), rowRange(y + height - 1 + Δy).start);
+		}
+	}
 }
 #[allow(non_camel_case_types)]
 pub trait LenConst_Ext {
@@ -1047,8 +6565,6 @@ impl Vec2Ext for Vec2 {
 	}
 }
 pub const FULLY_TRANSPARENT: u8 = 0;
-pub const RED: u8 = 98;
-pub const BLACK: u8 = 172;
 impl dt1::DrawDestination for Image {
 	#[inline(always)]
 	fn width(&self) -> usize {
@@ -1060,6 +6576,22 @@ impl dt1::DrawDestination for Image {
 		self.data[atIndex] = value;
 	}
 }
+/// Blocks drawn translucently, each pixel blended with what's under it, as the game draws shadows and ghosts.
+pub struct Blended<'a> {
+	pub image: &'a mut Image,
+	pub blendTable: &'a palette::BlendTable,
+}
+impl dt1::DrawDestination for Blended<'_> {
+	#[inline(always)]
+	fn width(&self) -> usize {
+		self.image.width
+	}
+	#[inline(always)]
+	fn putpixel(&mut self, atIndex: usize, value: u8) {
+		let pixel = &mut self.image.data[atIndex];
+		*pixel = self.blendTable[usize::from(*pixel) << u8::BITS | usize::from(value)];
+	}
+}
 
 #[derive(Debug, Default, Clone)]
 pub struct TileColumns {
@@ -1088,76 +6620,254 @@ impl TileColumns {
 	}
 }
 
+/// How a block or tile sheet is laid out: in `TileColumns`, or wherever `pack` puts the tiles.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Layout {
+	#[default]
+	Columns,
+	Packed(pack::Heuristic),
+}
+impl Layout {
+	/// `None` for columns, which every sheet lays out its own way.
+	pub fn pack(self, dimensions: &[Vec2]) -> Option<pack::Packing> {
+		match self {
+			Self::Columns => None,
+			Self::Packed(heuristic) => Some(pack::pack(dimensions, heuristic)),
+		}
+	}
+}
+impl fmt::Display for Layout {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Columns => f.write_str("columns"),
+			Self::Packed(heuristic) => heuristic.fmt(f),
+		}
+	}
+}
+impl FromStr for Layout {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"columns" => Ok(Self::Columns),
+			_ => s.parse().map(Self::Packed).map_err(|_| {
+				format!(
+					"{s:?} isn't columns or one of {}",
+					Vec::from_iter(pack::Heuristic::ALL.map(pack::Heuristic::name)).join(", ")
+				)
+			}),
+		}
+	}
+}
+
 pub struct TilesIterator {
 	tilewidth: usize,
+	// how many columns of tiles the sheet has room for, walking it as columns
+	sheetWidth: usize,
 	pub tileColumns: TileColumns,
+	packedPoints: Option<vec::IntoIter<Vec2>>,
 }
 impl TilesIterator {
 	#[inline(always)]
 	pub fn new(tilewidth: usize, image: &Image) -> Self {
 		Self {
 			tilewidth,
+			sheetWidth: image.width,
 			tileColumns: TileColumns {
 				fullColumnHeight: image.height,
 				numOverflownColumns: 0,
 				lastColumnHeight: 0,
 			},
+			packedPoints: None,
+		}
+	}
+	/// The tiles of a sheet whose PNG is `info`, wherever a packed layout put them.
+	pub fn fromPNG(tilewidth: usize, image: &Image, info: &png::Info<'_>) -> Self {
+		Self {
+			packedPoints: pack::Packing::pointsOfPNG(info).map(Vec::into_iter),
+			..Self::new(tilewidth, image)
+		}
+	}
+	pub fn fromPoints(tilewidth: usize, points: Vec<Vec2>) -> Self {
+		Self {
+			tilewidth,
+			sheetWidth: usize::MAX,
+			tileColumns: TileColumns::default(),
+			packedPoints: Some(points.into_iter()),
 		}
 	}
 	#[inline(always)]
 	pub fn next(&mut self, tileHeight: usize) -> [usize; 2] {
+		if let Some(packedPoints) = &mut self.packedPoints {
+			return packedPoints.next().expect("more tiles than the sheet has packed points");
+		}
 		let tileColumns = self.tileColumns.clone();
-		if self.tileColumns.pushTile(tileHeight) != 0 {
+		let point = if self.tileColumns.pushTile(tileHeight) != 0 {
 			[self.tileColumns.numOverflownColumns * self.tilewidth, 0]
 		} else {
 			[tileColumns.numOverflownColumns * self.tilewidth, tileColumns.lastColumnHeight]
+		};
+		assert!(
+			point[X] + self.tilewidth <= self.sheetWidth,
+			"more tiles than the sheet has columns for; a packed one that lost its {} chunk?",
+			pack::Packing::PNG_KEYWORD,
+		);
+		point
+	}
+}
+
+/// What can go wrong reading or writing a DS1, DT1 or DC6; offsets are in bytes from the start of the file.
+pub enum Error {
+	Truncated {
+		offset: u64,
+		needed: u64,
+	},
+	BadPointer {
+		what: String,
+		pointer: i64,
+		offset: u64,
+	},
+	NonZeroPadding {
+		offset: u64,
+		len: usize,
+	},
+	TrailingBytes {
+		offset: u64,
+		len: usize,
+	},
+	UnsupportedVersion(String),
+	InvalidBlockFormat {
+		offset: u64,
+		format: [u8; 2],
+	},
+	InvalidData {
+		offset: u64,
+		reason: String,
+	},
+	/// fields that contradict one another, on writing
+	Inconsistent(String),
+	/// a tar stream without exactly one entry for what a binary needs from it
+	Role(String),
+	Io(io::Error),
+}
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Truncated { offset, needed } => {
+				write!(f, "truncated at byte {offset}: {needed} more bytes needed")
+			}
+			Self::BadPointer { what, pointer, offset } => {
+				write!(f, "{what} == {pointer} but its data is at byte {offset}")
+			}
+			Self::NonZeroPadding { offset, len } => {
+				write!(f, "{len} bytes of padding at byte {offset} aren't all zeros")
+			}
+			Self::TrailingBytes { offset, len } => write!(f, "{len} unexpected bytes at byte {offset}"),
+			Self::UnsupportedVersion(version) => f.write_str(version),
+			Self::InvalidBlockFormat { offset, format } => write!(f, "block format {format:?} at byte {offset}"),
+			Self::InvalidData { offset, reason } => write!(f, "at byte {offset}: {reason}"),
+			Self::Inconsistent(reason) | Self::Role(reason) => f.write_str(reason),
+			Self::Io(err) => write!(f, "{err}"),
 		}
 	}
 }
+impl fmt::Debug for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Display::fmt(self, f)
+	}
+}
+impl error::Error for Error {}
+impl From<io::Error> for Error {
+	fn from(err: io::Error) -> Self {
+		Self::Io(err)
+	}
+}
 
 pub trait ReadExt {
-	fn consumeZeros(&mut self, zerosCount: usize);
-	fn read_u8_array<const N: usize>(&mut self) -> [u8; N];
+	fn consumeZeros(&mut self, zerosCount: usize) -> Result<(), Error>;
+	fn read_u8_array<const N: usize>(&mut self) -> Result<[u8; N], Error>;
+	fn readLE<T: FromLEBytes>(&mut self) -> Result<T, Error>;
+	fn readCount(&mut self, what: &str) -> Result<usize, Error>;
+	fn ensureRemaining(&self, len: usize) -> Result<(), Error>;
 	fn remaining(&self) -> usize;
 }
 impl<T: AsRef<[u8]>> ReadExt for io::Cursor<T> {
 	#[inline(always)]
-	fn consumeZeros(&mut self, zerosCount: usize) {
-		let position = self.position() as usize;
-		self.set_position((position + zerosCount) as _);
-		let underlyingSlice = self.get_ref().as_ref();
-		assert!(allZeros(&underlyingSlice[position..self.position() as _]));
-
-		fn allZeros(byteSlice: &[u8]) -> bool {
-			for &byte in byteSlice {
-				if byte != 0 {
-					return false;
-				}
-			}
-			true
+	fn consumeZeros(&mut self, zerosCount: usize) -> Result<(), Error> {
+		let offset = self.position();
+		let zeros = self.readSlice(zerosCount)?;
+		if zeros.iter().any(|&byte| byte != 0) {
+			return Err(Error::NonZeroPadding { offset, len: zerosCount });
 		}
+		Ok(())
 	}
 	#[inline(always)]
-	fn read_u8_array<const N: usize>(&mut self) -> [u8; N] {
-		let position = self.position() as usize;
-		self.set_position((position + N) as _);
-		let underlyingSlice = self.get_ref().as_ref();
-		<[u8; N]>::try_from(&underlyingSlice[position..self.position() as _]).unwrap()
+	fn read_u8_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+		Ok(<[u8; N]>::try_from(self.readSlice(N)?).unwrap())
+	}
+	#[inline(always)]
+	fn readLE<U: FromLEBytes>(&mut self) -> Result<U, Error> {
+		Ok(U::fromLEBytes(self.readSlice(size_of::<U>())?))
+	}
+	/// An i32 count or size, which a negative one isn't.
+	#[inline(always)]
+	fn readCount(&mut self, what: &str) -> Result<usize, Error> {
+		let offset = self.position();
+		match self.readLE::<i32>()? {
+			count @ 0.. => Ok(count as _),
+			count => Err(Error::InvalidData { offset, reason: format!("{what} == {count} < 0") }),
+		}
+	}
+	#[inline(always)]
+	fn ensureRemaining(&self, len: usize) -> Result<(), Error> {
+		match self.remaining() {
+			remaining if remaining < len => {
+				Err(Error::Truncated { offset: self.position(), needed: (len - remaining) as _ })
+			}
+			_ => Ok(()),
+		}
 	}
 	#[inline(always)]
 	fn remaining(&self) -> usize {
 		let underlyingSlice = self.get_ref().as_ref();
-		underlyingSlice.len() - self.position() as usize
+		underlyingSlice.len().saturating_sub(self.position() as usize)
+	}
+}
+
+trait CursorExt {
+	fn readSlice(&mut self, len: usize) -> Result<&[u8], Error>;
+}
+impl<T: AsRef<[u8]>> CursorExt for io::Cursor<T> {
+	#[inline(always)]
+	fn readSlice(&mut self, len: usize) -> Result<&[u8], Error> {
+		self.ensureRemaining(len)?;
+		let position = self.position() as usize;
+		self.set_position((position + len) as _);
+		Ok(&self.get_ref().as_ref()[position..][..len])
 	}
 }
 
+pub trait FromLEBytes: Sized {
+	fn fromLEBytes(bytes: &[u8]) -> Self;
+}
+macro_rules! impl_FromLEBytes {
+	($($T:ty),*) => {$(
+		impl FromLEBytes for $T {
+			#[inline(always)]
+			fn fromLEBytes(bytes: &[u8]) -> Self {
+				Self::from_le_bytes(bytes.try_into().unwrap())
+			}
+		}
+	)*};
+}
+impl_FromLEBytes!(u8, i16, u16, i32, u32);
+
 trait WriteExt {
-	fn writeZeros(&mut self, zerosCount: usize);
+	fn writeZeros(&mut self, zerosCount: usize) -> io::Result<()>;
 }
 impl<T: Write> WriteExt for T {
 	#[inline(always)]
-	fn writeZeros(&mut self, zerosCount: usize) {
-		io::copy(&mut io::repeat(0).take(zerosCount as _), self).unwrap();
+	fn writeZeros(&mut self, zerosCount: usize) -> io::Result<()> {
+		io::copy(&mut io::repeat(0).take(zerosCount as _), self).map(drop)
 	}
 }
 